@@ -0,0 +1,8 @@
+// `cargo bench` で `reversi bench` と同じ既定のベンチマークを行う
+extern crate reversi;
+
+use reversi::bench::{self, Settings};
+
+fn main() {
+    print!("{}", bench::run(&Settings::default()));
+}
@@ -0,0 +1,32 @@
+// C から使うためのヘッダー reversi.h を src/ffi.rs から生成する
+//
+// ヘッダーは OUT_DIR に書き、tests/ffi.rs はそれをインクルードする。
+// ソースツリーの include/reversi.h は、環境変数 REVERSI_UPDATE_HEADER を設定してビルドしたときだけ書き換える。
+
+extern crate cbindgen;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=REVERSI_UPDATE_HEADER");
+    // tests/ffi.rs が C のコンパイラを選ぶのと、生成したヘッダーを見つけるのに使う
+    println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
+    println!("cargo:rustc-env=REVERSI_INCLUDE_DIR={}", out_dir);
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml")).unwrap();
+    let header = Path::new(&out_dir).join("reversi.h");
+    cbindgen::Builder::new()
+        .with_src(Path::new(&crate_dir).join("src/ffi.rs"))
+        .with_config(config)
+        .generate()
+        .expect("failed to generate reversi.h")
+        .write_to_file(&header);
+    if env::var_os("REVERSI_UPDATE_HEADER").is_some() {
+        fs::copy(&header, Path::new(&crate_dir).join("include/reversi.h")).expect("failed to update include/reversi.h");
+    }
+}
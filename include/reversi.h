@@ -0,0 +1,183 @@
+/* reversi の C API。build.rs が src/ffi.rs から生成するので、直接編集しないこと */
+
+#ifndef REVERSI_H
+#define REVERSI_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// パスを表すマスの番号
+#define REVERSI_PASS -1
+
+// マスの状態と手番
+typedef enum ReversiPiece {
+  REVERSI_PIECE_EMPTY = 0,
+  REVERSI_PIECE_BLACK = 1,
+  REVERSI_PIECE_WHITE = 2,
+} ReversiPiece;
+
+// 対局の状態
+typedef enum ReversiStatus {
+  REVERSI_STATUS_PLAYING = 0,
+  REVERSI_STATUS_BLACK_WON = 1,
+  REVERSI_STATUS_WHITE_WON = 2,
+  REVERSI_STATUS_DRAW = 3,
+} ReversiStatus;
+
+// 盤面のハンドル
+typedef struct ReversiBoard ReversiBoard;
+
+// コンピュータプレイヤーのハンドル
+//
+// 探索中に別のスレッドから `reversi_engine_stop` を呼べるよう、状態はすべてロックの内側に持つ。
+typedef struct ReversiEngine ReversiEngine;
+
+// 外部から手を与えて進める対局のハンドル
+typedef struct ReversiGame ReversiGame;
+
+// エンジンが最後に行った探索の結果
+typedef struct ReversiSearchInfo {
+  // 探索を終えた深さ。深さを報告しないエンジンでは 0
+  uint32_t depth;
+  // 手番から見た評価値（石の差）
+  int32_t score;
+  // 選んだ手
+  int32_t best;
+  // 探索にかかった時間
+  uint64_t elapsed_ms;
+} ReversiSearchInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// 初期局面の盤面を作る
+struct ReversiBoard *reversi_board_new(void);
+
+// `Board::to_position` の形式の文字列から盤面を作る。読めなければ NULL を返す
+//
+// `turn` が NULL でなければ手番を書き込む。
+//
+// # Safety
+//
+// `position` は NUL 終端の文字列、`turn` は NULL か書き込める領域を指していなければならない。
+struct ReversiBoard *reversi_board_from_position(const char *position,
+                                                 enum ReversiPiece *turn);
+
+// 盤面を複製する
+struct ReversiBoard *reversi_board_clone(const struct ReversiBoard *board);
+
+// 盤面を解放する。NULL の場合は何もしない
+void reversi_board_free(struct ReversiBoard *board);
+
+// マスの状態を返す。範囲外のマスは空きとする
+enum ReversiPiece reversi_board_get(const struct ReversiBoard *board, int32_t square);
+
+// 指定の色の石の数を返す
+uint32_t reversi_board_count(const struct ReversiBoard *board, enum ReversiPiece piece);
+
+// 指定の色の合法手を `out` に `len` 個まで書き込み、合法手の数を返す
+//
+// # Safety
+//
+// `out` は NULL か、`len` 個の `int32_t` を書き込める領域を指していなければならない。
+uintptr_t reversi_board_moves(const struct ReversiBoard *board,
+                              enum ReversiPiece piece,
+                              int32_t *out,
+                              uintptr_t len);
+
+// 指定の色の手を打つ。合法手でなければ盤面を変えずに false を返す
+bool reversi_board_do_move(struct ReversiBoard *board, enum ReversiPiece piece, int32_t square);
+
+// 盤面と手番を `Board::to_position` の形式で書き込む
+//
+// # Safety
+//
+// `buf` は NULL か、`len` バイトを書き込める領域を指していなければならない。
+uintptr_t reversi_board_to_position(const struct ReversiBoard *board,
+                                    enum ReversiPiece turn,
+                                    char *buf,
+                                    uintptr_t len);
+
+// 初期局面から黒の手番で始まる対局を作る
+struct ReversiGame *reversi_game_new(void);
+
+// `Board::to_position` の形式の局面から始まる対局を作る。局面が不正なら NULL を返す
+//
+// # Safety
+//
+// `position` は NUL 終端の文字列を指していなければならない。
+struct ReversiGame *reversi_game_from_position(const char *position);
+
+// 対局を解放する。NULL の場合は何もしない
+void reversi_game_free(struct ReversiGame *game);
+
+// 現在の盤面の複製を返す。不要になったら `reversi_board_free` で解放する
+struct ReversiBoard *reversi_game_board(const struct ReversiGame *game);
+
+// 手番を返す。終局していれば空きを返す
+enum ReversiPiece reversi_game_turn(const struct ReversiGame *game);
+
+// 手番の合法手を `out` に `len` 個まで書き込み、合法手の数を返す。終局していれば 0
+//
+// # Safety
+//
+// `out` は NULL か、`len` 個の `int32_t` を書き込める領域を指していなければならない。
+uintptr_t reversi_game_moves(const struct ReversiGame *game,
+                             int32_t *out,
+                             uintptr_t len);
+
+// 手番の手を打つ。`REVERSI_PASS` は打てる手がないときのみ受け付ける
+//
+// 不正な手や終局後の手は、対局を変えずに false を返す。
+bool reversi_game_play(struct ReversiGame *game, int32_t square);
+
+// 最後の1手を取り消す。取り消す手がなければ false を返す
+bool reversi_game_undo(struct ReversiGame *game);
+
+// 対局の状態を返す
+enum ReversiStatus reversi_game_status(const struct ReversiGame *game);
+
+// これまでの手を `f5d6c3` のような棋譜として書き込む
+//
+// # Safety
+//
+// `buf` は NULL か、`len` バイトを書き込める領域を指していなければならない。
+uintptr_t reversi_game_transcript(const struct ReversiGame *game,
+                                  char *buf,
+                                  uintptr_t len);
+
+// `alphabeta:7` のような `PlayerKind` の表記からエンジンを作る
+//
+// 読めない場合と、人間のプレイヤーを指定した場合は NULL を返す。
+//
+// # Safety
+//
+// `kind` は NUL 終端の文字列を指していなければならない。
+struct ReversiEngine *reversi_engine_new(const char *kind);
+
+// エンジンを解放する。NULL の場合は何もしない
+void reversi_engine_free(struct ReversiEngine *engine);
+
+// 対局の手番の手を考え、そのマスを返す。打てる手がないか終局していれば `REVERSI_PASS` を返す
+//
+// `time_ms` が 0 ならエンジンの深さまで探索し、それ以外はおよそその時間で探索を打ち切る。
+// 手は打たないので、対局を進めるには返ったマスを `reversi_game_play` に渡す。
+int32_t reversi_engine_best_move(const struct ReversiEngine *engine,
+                                 const struct ReversiGame *game,
+                                 uint32_t time_ms);
+
+// 別のスレッドから、探索中の `reversi_engine_best_move` をすぐに戻らせる
+void reversi_engine_stop(const struct ReversiEngine *engine);
+
+// 最後の探索の結果を `info` に書き込む。まだ探索していなければ false を返す
+bool reversi_engine_search_info(const struct ReversiEngine *engine,
+                                struct ReversiSearchInfo *info);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* REVERSI_H */
@@ -0,0 +1,362 @@
+use std::fmt;
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+use game::{self, Game, Manual, Play};
+use ggf::Record;
+use players::AlphaBetaPlayer;
+use json::Json;
+
+/// 悪手の判定に使う損失の下限（石の差）
+const INACCURACY: i8 = 2;
+const MISTAKE: i8 = 6;
+const BLUNDER: i8 = 12;
+
+/// 悪手に示す最善の進行の長さ
+const LINE_LENGTH: usize = 6;
+
+/// グラフの片側の幅。1文字を4石とする
+const GRAPH_WIDTH: i8 = 16;
+
+/// 最善手と比べた損失の大きさによる、手の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Label {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Label {
+    /// 損失から分類を決める。小さな損失は None
+    pub fn from_loss(loss: i8) -> Option<Label> {
+        match loss {
+            loss if loss >= BLUNDER => Some(Label::Blunder),
+            loss if loss >= MISTAKE => Some(Label::Mistake),
+            loss if loss >= INACCURACY => Some(Label::Inaccuracy),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Label::Inaccuracy => write!(f, "inaccuracy"),
+            Label::Mistake => write!(f, "mistake"),
+            Label::Blunder => write!(f, "blunder"),
+        }
+    }
+}
+
+/// 1手ごとの分析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlyAnalysis {
+    /// 何手目か。最初の手を 1 とし、パスも数える
+    pub ply: usize,
+    pub piece: Piece,
+    /// 打った手。パスの場合は None
+    pub played: Option<Coord>,
+    /// 打った後の局面の、黒から見た評価値
+    pub eval: i8,
+    /// 最善手。パスの場合は None
+    pub best: Option<Coord>,
+    /// 最善手と比べて失った石の差
+    pub loss: i8,
+    pub label: Option<Label>,
+    /// 悪手 (mistake 以上) の場合に、最善手から始まる進行。パスは含めない
+    pub line: Vec<Coord>,
+}
+
+/// 対局全体の分析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub depth: usize,
+    pub plies: Vec<PlyAnalysis>,
+}
+
+/// 保存した対局を読み、打ち直した対局を返す
+///
+/// GGF か、`-o` や `save` で書いた `f5d6c3` のような棋譜を読む。棋譜は `#` で始まる行を除いた最初の行を使い、
+/// `position` の局面（None なら初期局面）から打ち直す。
+pub fn load(text: &str, position: Option<(Board, Piece)>) -> Result<Game<Manual, Manual>, String> {
+    if text.trim_start().starts_with("(;") {
+        return Record::parse(text)?.game();
+    }
+    let line = text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or("the file has no moves")?;
+    let (board, turn) = position.unwrap_or((Board::new(), Piece::Black));
+    let mut game = Game::from_position(board, turn, Manual, Manual).map_err(|err| format!("invalid position: {}", err))?;
+    game.replay(&game::parse_transcript(line)?).map_err(|err| err.to_string())?;
+    Ok(game)
+}
+
+/// 対局のすべての局面をエンジンで調べ、1手ごとに最善手と比べる
+///
+/// 最初の局面から `Game` の履歴をたどり、各局面の合法手をすべて `depth` の深さで探索する。
+pub fn analyze<P1: Play, P2: Play>(game: &Game<P1, P2>, depth: usize) -> Report {
+    let engine = AlphaBetaPlayer::new(depth);
+    let (start, _) = game.start();
+    let mut board = start.clone();
+    let mut eval = 0;
+    let mut plies = Vec::new();
+    for (idx, ply) in game.history().iter().enumerate() {
+        let piece = ply.piece;
+        let mov = match ply.mov {
+            Some(ref mov) => mov,
+            None => {
+                plies.push(PlyAnalysis {
+                    ply: idx + 1,
+                    piece,
+                    played: None,
+                    eval,
+                    best: None,
+                    loss: 0,
+                    label: None,
+                    line: Vec::new(),
+                });
+                continue;
+            },
+        };
+        let scores = engine.analyze(piece, &board);
+        let (best, best_score) = (scores[0].0.pos, scores[0].1);
+        let score = scores.iter()
+            .find(|&(candidate, _)| candidate.pos == mov.pos)
+            .map_or(best_score, |&(_, score)| score);
+        let loss = best_score - score;
+        let label = Label::from_loss(loss);
+        let line = match label {
+            Some(label) if label >= Label::Mistake => best_line(piece, &board, depth),
+            _ => Vec::new(),
+        };
+        eval = match piece {
+            Piece::Black => score,
+            Piece::White => -score,
+        };
+        plies.push(PlyAnalysis { ply: idx + 1, piece, played: Some(mov.pos), eval, best: Some(best), loss, label, line });
+        board.do_move(piece, mov);
+    }
+    Report { depth, plies }
+}
+
+/// 最善手を打ち続けた進行を返す。深さは1手ごとに1つずつ浅くする
+fn best_line(mut piece: Piece, board: &Board, depth: usize) -> Vec<Coord> {
+    let mut board = board.clone();
+    let mut line = Vec::new();
+    for depth in (1..=depth).rev().take(LINE_LENGTH) {
+        if board.moves(piece).is_empty() {
+            piece = piece.opponent();
+            if board.moves(piece).is_empty() {
+                break;
+            }
+        }
+        let mov = AlphaBetaPlayer::new(depth).analyze(piece, &board).remove(0).0;
+        board.do_move(piece, &mov);
+        line.push(mov.pos);
+        piece = piece.opponent();
+    }
+    line
+}
+
+fn piece_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Black => "black",
+        Piece::White => "white",
+    }
+}
+
+/// 評価値を、0 を中心に黒を右、白を左に伸ばす棒にする
+fn graph(eval: i8) -> String {
+    let len = (eval / 4).clamp(-GRAPH_WIDTH, GRAPH_WIDTH);
+    let (white, black) = if len < 0 { (-len, 0) } else { (0, len) };
+    format!(
+        "{}{}|{}{}",
+        " ".repeat((GRAPH_WIDTH - white) as usize),
+        "#".repeat(white as usize),
+        "#".repeat(black as usize),
+        " ".repeat((GRAPH_WIDTH - black) as usize),
+    )
+}
+
+fn format_move(pos: Option<Coord>) -> String {
+    pos.map_or("pass".to_string(), |pos| pos.to_string())
+}
+
+impl Report {
+    /// 指定の色の手のうち、指定の分類の手の数
+    pub fn count(&self, piece: Piece, label: Label) -> usize {
+        self.plies.iter().filter(|ply| ply.piece == piece && ply.label == Some(label)).count()
+    }
+
+    /// 指定の色の、パスを除いた1手あたりの平均の損失
+    pub fn average_loss(&self, piece: Piece) -> f64 {
+        let losses: Vec<i8> = self.plies.iter()
+            .filter(|ply| ply.piece == piece && ply.played.is_some())
+            .map(|ply| ply.loss)
+            .collect();
+        if losses.is_empty() {
+            return 0.0;
+        }
+        losses.iter().map(|&loss| loss as f64).sum::<f64>() / losses.len() as f64
+    }
+
+    pub fn to_json(&self) -> Json {
+        let plies: Vec<Json> = self.plies.iter()
+            .map(|ply| Json::object(vec![
+                ("ply", ply.ply.into()),
+                ("piece", piece_name(ply.piece).into()),
+                ("move", ply.played.map(|pos| pos.to_string()).into()),
+                ("eval", ply.eval.into()),
+                ("best", ply.best.map(|pos| pos.to_string()).into()),
+                ("loss", ply.loss.into()),
+                ("label", ply.label.map(|label| label.to_string()).into()),
+                ("line", ply.line.iter().map(|pos| pos.to_string()).collect::<Vec<_>>().into()),
+            ]))
+            .collect();
+        let summary = |piece: Piece| Json::object(vec![
+            ("inaccuracies", self.count(piece, Label::Inaccuracy).into()),
+            ("mistakes", self.count(piece, Label::Mistake).into()),
+            ("blunders", self.count(piece, Label::Blunder).into()),
+            ("average_loss", self.average_loss(piece).into()),
+        ]);
+        Json::object(vec![
+            ("depth", self.depth.into()),
+            ("plies", plies.into()),
+            ("black", summary(Piece::Black)),
+            ("white", summary(Piece::White)),
+        ])
+    }
+}
+
+/// 1手ごとの評価値のグラフと悪手の一覧を、プレーンテキストで表す
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Analysis at depth {}", self.depth)?;
+        writeln!(f)?;
+        writeln!(f, "Ply  Move    Eval  Loss  {:^33}", "White | Black")?;
+        for ply in &self.plies {
+            let label = ply.label.map_or(String::new(), |label| format!("  {}", label));
+            writeln!(
+                f,
+                "{:>3}  {} {:<4}  {:>+4}  {:>4}  {}{}",
+                ply.ply,
+                if ply.piece == Piece::Black { "B" } else { "W" },
+                format_move(ply.played),
+                ply.eval,
+                ply.loss,
+                graph(ply.eval),
+                label,
+            )?;
+        }
+        let mistakes: Vec<&PlyAnalysis> = self.plies.iter()
+            .filter(|ply| ply.label.is_some_and(|label| label >= Label::Mistake))
+            .collect();
+        if !mistakes.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Mistakes:")?;
+            for ply in mistakes {
+                let line: Vec<String> = ply.line.iter().map(|pos| pos.to_string()).collect();
+                writeln!(
+                    f,
+                    "{:>3}. {:?} {} ({}, -{}): best {}, line {}",
+                    ply.ply,
+                    ply.piece,
+                    format_move(ply.played),
+                    ply.label.expect("a mistake has a label"),
+                    ply.loss,
+                    format_move(ply.best),
+                    line.join(" "),
+                )?;
+            }
+        }
+        writeln!(f)?;
+        for &piece in &[Piece::Black, Piece::White] {
+            writeln!(
+                f,
+                "{:?}: {} inaccuracies, {} mistakes, {} blunders, average loss {:.1}",
+                piece,
+                self.count(piece, Label::Inaccuracy),
+                self.count(piece, Label::Mistake),
+                self.count(piece, Label::Blunder),
+                self.average_loss(piece),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_label() {
+        assert_eq!(None, Label::from_loss(0));
+        assert_eq!(None, Label::from_loss(1));
+        assert_eq!(Some(Label::Inaccuracy), Label::from_loss(2));
+        assert_eq!(Some(Label::Mistake), Label::from_loss(6));
+        assert_eq!(Some(Label::Blunder), Label::from_loss(30));
+    }
+
+    #[test]
+    fn test_analysis_graph() {
+        assert_eq!(format!("{}|{}", " ".repeat(16), " ".repeat(16)), graph(0));
+        assert_eq!(format!("{}|##{}", " ".repeat(16), " ".repeat(14)), graph(8));
+        assert_eq!(format!("{}|{}", "#".repeat(16), " ".repeat(16)), graph(-64));
+    }
+
+    #[test]
+    fn test_analysis_load() {
+        let game = load("# Game 1: Black wins\nf5d6c3\nd3c4\n", None).unwrap();
+        assert_eq!("f5d6c3", game.transcript());
+        let game = load("(;GM[Othello]BO[8 -------- -------- -------- ---O*--- ---*O--- -------- -------- -------- *]B[F5]W[D6];)", None).unwrap();
+        assert_eq!("f5d6", game.transcript());
+        assert!(load("# nothing\n", None).is_err());
+        assert!(load("f5f5\n", None).is_err());
+    }
+
+    #[test]
+    fn test_analysis_report() {
+        let game = load("f5d6c3d3c4f4c5b3c2", None).unwrap();
+        let report = analyze(&game, 3);
+        assert_eq!(9, report.plies.len());
+        for (ply, analysis) in game.history().iter().zip(&report.plies) {
+            assert_eq!(ply.piece, analysis.piece);
+            assert_eq!(ply.mov.as_ref().map(|mov| mov.pos), analysis.played);
+            assert!(analysis.loss >= 0);
+            assert_eq!(Label::from_loss(analysis.loss), analysis.label);
+            let has_line = analysis.label.is_some_and(|label| label >= Label::Mistake);
+            assert_eq!(has_line, !analysis.line.is_empty());
+            if has_line {
+                assert_eq!(analysis.best, analysis.line.first().cloned());
+            }
+        }
+        let text = report.to_string();
+        assert!(text.starts_with("Analysis at depth 3\n"));
+        assert!(text.contains("  1  B f5  "));
+        assert!(text.contains("Black: "));
+        let json = Json::parse(&report.to_json().to_string()).unwrap();
+        assert_eq!(9, json.get("plies").and_then(Json::as_array).unwrap().len());
+        assert_eq!(Some("d6"), json.get("plies").and_then(Json::as_array).unwrap()[1].get("move").and_then(Json::as_str));
+        assert_eq!(Some(3.0), json.get("depth").and_then(Json::as_f64));
+    }
+
+    #[test]
+    fn test_analysis_finds_blunder() {
+        // 黒は a1 で1行目と a 列をまとめて取れるのに、中央の d3 に打つ
+        let rows = ["-OOOOOOX", "O-------", "O-------", "O--OX---", "O--XO---", "O-------", "O-------", "X-------"];
+        let position = format!("{} X", rows.concat());
+        let (board, turn) = Board::from_position(&position).unwrap();
+        let mut game = Game::from_position(board, turn, Manual, Manual).unwrap();
+        game.apply_at(Coord(3, 2)).unwrap();
+        let report = analyze(&game, 2);
+        let ply = &report.plies[0];
+        assert_eq!(Some(Coord(0, 0)), ply.best);
+        assert_eq!(Some(Label::Blunder), ply.label);
+        assert_eq!(2, ply.line.len());
+        assert_eq!(Some(&Coord(0, 0)), ply.line.first());
+        assert!(report.to_string().contains("Mistakes:\n  1. Black d3 (blunder, -"));
+        assert_eq!(1, report.count(Piece::Black, Label::Blunder));
+    }
+}
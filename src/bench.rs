@@ -0,0 +1,276 @@
+//! 合法手の生成と各プレイヤーの探索の速さを測るベンチマーク
+//!
+//! いつも同じ局面の組で測るので、盤面や探索の実装を変えた前後で数字を比べられる。
+//! 最適化なしのビルドでは意味のある数字にならないので、`--release` を付けて実行する。
+
+use std::fmt;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use piece::Piece;
+use board::{Board, DIRECTIONS};
+use coord::Coord;
+use clock::Instant;
+use players::{self, PlayerKind};
+
+/// 計測に使う局面。序盤、中盤 (空き 40)、終盤 (空き 16) の順
+pub const POSITIONS: [(&str, &str); 3] = [
+    ("opening", "---------------------------OX------XO--------------------------- X"),
+    ("midgame", "---X-------X-------X-OX---OOOOOO--OXXXOO-O-X-X-OO----X-------X-- X"),
+    ("endgame", "-XXXOXXX--XOOOOOOOOXOXOO--XOXXXOXOOOOXOO-XXO-O-OO-X--XOO--X--X-O X"),
+];
+
+/// 無作為に手を選ぶプレイヤーに与えるシード
+const SEED: u64 = 1;
+
+/// `POSITIONS` を名前、盤面、手番の組として返す
+pub fn positions() -> Vec<(&'static str, Board, Piece)> {
+    POSITIONS.iter()
+        .map(|&(name, position)| {
+            let (board, turn) = Board::from_position(position).expect("bundled positions are valid");
+            (name, board, turn)
+        })
+        .collect()
+}
+
+/// ベンチマークの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// 探索を測るプレイヤー。深さを持つ種類は 1 からその深さまでそれぞれ測る
+    pub players: Vec<PlayerKind>,
+    /// 合法手の生成に関する計測をそれぞれ繰り返す時間
+    pub duration: Duration,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            players: vec![PlayerKind::Dumb, PlayerKind::Random, PlayerKind::NegaMax(6), PlayerKind::AlphaBeta(9)],
+            duration: Duration::from_secs(1),
+        }
+    }
+}
+
+/// 同じ操作を繰り返した計測の結果
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: &'static str,
+    /// 操作を行った回数
+    pub count: u64,
+    pub elapsed: Duration,
+}
+impl Measurement {
+    /// 1秒あたりの回数
+    pub fn per_second(&self) -> f64 {
+        self.count as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// 1つの局面を1つの深さで探索した計測の結果
+#[derive(Debug, Clone)]
+pub struct SearchMeasurement {
+    pub player: PlayerKind,
+    pub position: &'static str,
+    /// 探索の深さ。深さを持たないプレイヤーでは None
+    pub depth: Option<usize>,
+    /// 探索を始めてから手を返すまでの時間
+    pub elapsed: Duration,
+    /// 訪れた局面の数。途中経過を報告しないプレイヤーでは None
+    pub nodes: Option<u64>,
+}
+impl SearchMeasurement {
+    /// 1秒あたりに訪れた局面の数
+    pub fn nodes_per_second(&self) -> Option<f64> {
+        self.nodes.map(|nodes| nodes as f64 / self.elapsed.as_secs_f64())
+    }
+}
+
+/// `f` を `duration` が過ぎるまで繰り返す。`f` は行った操作の回数を返す
+fn repeat<F: FnMut() -> u64>(name: &'static str, duration: Duration, mut f: F) -> Measurement {
+    let started = Instant::now();
+    let mut count = 0;
+    loop {
+        count += f();
+        if started.elapsed() >= duration {
+            break;
+        }
+    }
+    Measurement { name, count, elapsed: started.elapsed() }
+}
+
+/// 各局面の両者について `Board::moves` を呼ぶ
+pub fn bench_moves(positions: &[(&'static str, Board, Piece)], duration: Duration) -> Measurement {
+    repeat("moves", duration, || {
+        for (_, board, _) in positions {
+            for &piece in &[Piece::Black, Piece::White] {
+                black_box(board.moves(black_box(piece)));
+            }
+        }
+        positions.len() as u64 * 2
+    })
+}
+
+/// 各局面の手番の合法手をすべて、盤面を複製して `Board::do_move` で打つ
+pub fn bench_do_move(positions: &[(&'static str, Board, Piece)], duration: Duration) -> Measurement {
+    let moves: Vec<_> = positions.iter()
+        .map(|&(_, ref board, turn)| (board, turn, board.moves(turn)))
+        .collect();
+    repeat("do_move", duration, || {
+        let mut count = 0;
+        for &(board, turn, ref moves) in &moves {
+            for mov in moves {
+                let mut board = board.clone();
+                board.do_move(turn, black_box(mov));
+                black_box(board);
+                count += 1;
+            }
+        }
+        count
+    })
+}
+
+/// 各局面の空きマスすべてについて、両者の8方向の `get_flip` を呼ぶ
+pub fn bench_get_flip(positions: &[(&'static str, Board, Piece)], duration: Duration) -> Measurement {
+    let empties: Vec<(&Board, Vec<Coord>)> = positions.iter()
+        .map(|(_, board, _)| {
+            let empties = (0..64).filter_map(Coord::from_index).filter(|&pos| board.matrix()[pos].is_none());
+            (board, empties.collect())
+        })
+        .collect();
+    repeat("get_flip", duration, || {
+        let mut count = 0;
+        for &(board, ref empties) in &empties {
+            for &pos in empties {
+                for &piece in &[Piece::Black, Piece::White] {
+                    for &dir in DIRECTIONS.iter() {
+                        black_box(board.get_flip(black_box(piece), pos, dir));
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    })
+}
+
+/// 各局面を `kind` の 1 からその深さまでの深さで探索し、それぞれの時間を測る
+///
+/// 深さごとに新しいプレイヤーを作るので、前の探索の置換表は引き継がない。
+pub fn bench_search(kind: PlayerKind, positions: &[(&'static str, Board, Piece)]) -> Vec<SearchMeasurement> {
+    let depths: Vec<Option<usize>> = match kind.depth() {
+        Some(depth) => (1..=depth).map(Some).collect(),
+        None => vec![None],
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut measurements = Vec::new();
+    for &(name, ref board, turn) in positions {
+        for &depth in &depths {
+            let mut player = depth.map_or(kind, |depth| kind.with_depth(depth)).build(SEED);
+            let started = Instant::now();
+            let (_, info) = players::search(&mut player, turn, board, &[], None, &stop, None);
+            measurements.push(SearchMeasurement {
+                player: kind,
+                position: name,
+                depth,
+                elapsed: started.elapsed(),
+                nodes: info.map(|info| info.nodes),
+            });
+        }
+    }
+    measurements
+}
+
+/// ベンチマークの結果
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub generation: Vec<Measurement>,
+    pub search: Vec<SearchMeasurement>,
+}
+
+/// `POSITIONS` で設定に従ってすべてのベンチマークを行う
+pub fn run(settings: &Settings) -> Report {
+    let positions = positions();
+    let generation = vec![
+        bench_moves(&positions, settings.duration),
+        bench_do_move(&positions, settings.duration),
+        bench_get_flip(&positions, settings.duration),
+    ];
+    let search = settings.players.iter()
+        .flat_map(|&kind| bench_search(kind, &positions))
+        .collect();
+    Report { generation, search }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Move generation ({} positions)", POSITIONS.len())?;
+        writeln!(f)?;
+        writeln!(f, "{:<10} {:>12} {:>10} {:>14}", "Benchmark", "Calls", "ns/call", "Calls/s")?;
+        for measurement in &self.generation {
+            writeln!(
+                f,
+                "{:<10} {:>12} {:>10.1} {:>14.0}",
+                measurement.name,
+                measurement.count,
+                measurement.elapsed.as_secs_f64() * 1e9 / measurement.count as f64,
+                measurement.per_second(),
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Search (time to depth)")?;
+        writeln!(f)?;
+        writeln!(f, "{:<14} {:<8} {:>5} {:>10} {:>12} {:>12}", "Player", "Position", "Depth", "Time (ms)", "Nodes", "Nodes/s")?;
+        for measurement in &self.search {
+            let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            writeln!(
+                f,
+                "{:<14} {:<8} {:>5} {:>10.3} {:>12} {:>12}",
+                measurement.player.to_string(),
+                measurement.position,
+                optional(measurement.depth.map(|depth| depth.to_string())),
+                measurement.elapsed.as_secs_f64() * 1e3,
+                optional(measurement.nodes.map(|nodes| nodes.to_string())),
+                optional(measurement.nodes_per_second().map(|rate| format!("{:.0}", rate))),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_positions() {
+        let empties: Vec<u8> = positions().iter().map(|(_, board, _)| board.empties()).collect();
+        assert_eq!(vec![60, 40, 16], empties);
+        assert!(positions().iter().all(|&(_, ref board, turn)| !board.moves(turn).is_empty()));
+    }
+
+    #[test]
+    fn test_bench_run() {
+        let settings = Settings {
+            players: vec![PlayerKind::Dumb, PlayerKind::NegaMax(2), PlayerKind::AlphaBeta(3)],
+            duration: Duration::from_millis(1),
+        };
+        let report = run(&settings);
+        assert_eq!(vec!["moves", "do_move", "get_flip"], report.generation.iter().map(|m| m.name).collect::<Vec<_>>());
+        assert!(report.generation.iter().all(|m| m.count > 0));
+        // 深さを持たない dumb は局面ごとに1回、negamax と alphabeta は深さの数だけ測る
+        assert_eq!(3 * (1 + 2 + 3), report.search.len());
+        let nodes = |kind| report.search.iter().filter(|m| m.player == kind && m.position == "midgame").map(|m| m.nodes).collect::<Vec<_>>();
+        assert_eq!(vec![None], nodes(PlayerKind::Dumb));
+        for kind in [PlayerKind::NegaMax(2), PlayerKind::AlphaBeta(3)] {
+            let nodes: Vec<u64> = nodes(kind).into_iter().map(Option::unwrap).collect();
+            assert!(nodes.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", nodes);
+        }
+        // 枝刈りをしない negamax は、同じ深さでも alphabeta より多くの局面を訪れる
+        let at_depth = |kind, depth| report.search.iter()
+            .find(|m| m.player == kind && m.position == "midgame" && m.depth == Some(depth))
+            .and_then(|m| m.nodes)
+            .unwrap();
+        assert!(at_depth(PlayerKind::NegaMax(2), 2) > at_depth(PlayerKind::AlphaBeta(3), 2));
+        assert!(report.to_string().contains("alphabeta:3"));
+    }
+}
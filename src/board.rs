@@ -1,5 +1,6 @@
 use std::fmt;
 use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 use smallvec::SmallVec;
 use piece::*;
 use coord::Coord;
@@ -10,7 +11,7 @@ const MATRIX_SIZE: usize = 8;
 /// 盤面の石の状態のみを保持する構造体
 ///
 /// インデックスによってアクセスできる。
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Matrix([[Option<Piece>; MATRIX_SIZE]; MATRIX_SIZE]);
 
 impl Matrix {
@@ -40,6 +41,54 @@ impl Matrix {
     pub fn size(&self) -> usize {
         MATRIX_SIZE
     }
+
+    /// 指定の色の石の数を数える
+    pub fn count(&self, piece: Piece) -> u8 {
+        self.0.iter()
+            .flat_map(|row| row.iter())
+            .filter(|&&cell| cell == Some(piece))
+            .count() as u8
+    }
+
+    /// 左上から右下へ、黒を `X`、白を `O`、空きマスを `-` とした 64 文字で盤面を表す
+    pub fn to_position_string(&self) -> String {
+        self.0.iter()
+            .flat_map(|row| row.iter())
+            .map(|&cell| match cell {
+                B => 'X',
+                W => 'O',
+                _ => '-',
+            })
+            .collect()
+    }
+}
+impl Default for Matrix {
+    fn default() -> Self {
+        Matrix::new()
+    }
+}
+/// `to_position_string` の形式の文字列を読む
+///
+/// 空白は無視し、黒は `*`、空きマスは `.` でもよい。
+impl FromStr for Matrix {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cells: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if cells.len() != MATRIX_SIZE * MATRIX_SIZE {
+            return Err(format!("a position needs {} squares, found {}", MATRIX_SIZE * MATRIX_SIZE, cells.len()));
+        }
+        let mut matrix = Matrix([[N; MATRIX_SIZE]; MATRIX_SIZE]);
+        for (idx, &cell) in cells.iter().enumerate() {
+            let pos = Coord((idx % MATRIX_SIZE) as i8, (idx / MATRIX_SIZE) as i8);
+            matrix[pos] = match cell {
+                'X' | 'x' | '*' | 'B' | 'b' => B,
+                'O' | 'o' | 'W' | 'w' => W,
+                '-' | '.' => N,
+                _ => return Err(format!("invalid square {:?} at {}", cell, pos)),
+            };
+        }
+        Ok(matrix)
+    }
 }
 /// `[]` 演算子のオーバーロード
 impl Index<Coord> for Matrix {
@@ -48,7 +97,11 @@ impl Index<Coord> for Matrix {
     ///
     /// 座標が盤面の範囲外であった場合は None が返る。
     fn index(&self, index: Coord) -> &Self::Output {
-        unimplemented!();
+        if !self.is_in_range(index) {
+            return &N;
+        }
+        let Coord(x, y) = index;
+        &self.0[y as usize][x as usize]
     }
 }
 /// `[]=` 演算子のオーバーロード
@@ -57,7 +110,8 @@ impl IndexMut<Coord> for Matrix {
     ///
     /// 座標が盤面の範囲外であった場合の挙動は未定義
     fn index_mut(&mut self, index: Coord) -> &mut Self::Output {
-        unimplemented!();
+        let Coord(x, y) = index;
+        &mut self.0[y as usize][x as usize]
     }
 }
 impl fmt::Display for Matrix {
@@ -108,7 +162,7 @@ pub type Moves<'a> = SmallVec<[Move; MATRIX_SIZE * MATRIX_SIZE]>;
 
 /// 隣接する8マスを指すそれぞれのベクトル
 ///
-/// ```
+/// ```text
 /// +---+---+---+
 /// | 0 | 1 | 2 |
 /// +---+---+---+
@@ -117,7 +171,7 @@ pub type Moves<'a> = SmallVec<[Move; MATRIX_SIZE * MATRIX_SIZE]>;
 /// | 5 | 6 | 7 |
 /// +---+---+---+
 /// ```
-const DIRECTIONS: [Coord; 8] = [
+pub(crate) const DIRECTIONS: [Coord; 8] = [
     Coord(-1, -1), //左上
     Coord(0, -1),  //真上
     Coord(1, -1),  //右上
@@ -129,7 +183,7 @@ const DIRECTIONS: [Coord; 8] = [
 ];
 
 /// 盤面にリバーシの操作を実装したもの
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Board {
     matrix: Matrix,
     pub black: u8,
@@ -137,6 +191,15 @@ pub struct Board {
 }
 
 impl Board {
+    /// 中央に4つの石を置いた初期局面を返す
+    ///
+    /// ```
+    /// use reversi::{Board, Piece};
+    ///
+    /// let board = Board::new();
+    /// assert_eq!((2, 2), (board.black, board.white));
+    /// assert_eq!(4, board.moves(Piece::Black).len());
+    /// ```
     pub fn new() -> Self {
         Board {
             matrix: Matrix::new(),
@@ -145,32 +208,121 @@ impl Board {
         }
     }
 
+    /// 任意の盤面から Board を生成する
+    ///
+    /// 石の数は盤面から数える。
+    pub fn from_matrix(matrix: Matrix) -> Self {
+        let black = matrix.count(Piece::Black);
+        let white = matrix.count(Piece::White);
+        Board { matrix, black, white }
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    /// 盤面の 64 文字に続けて、手番を `X` か `O` で表した文字列を返す
+    pub fn to_position(&self, turn: Piece) -> String {
+        let turn = match turn {
+            Piece::Black => 'X',
+            Piece::White => 'O',
+        };
+        format!("{} {}", self.matrix.to_position_string(), turn)
+    }
+
+    /// `to_position` の形式の文字列を読み、盤面と手番を返す
+    ///
+    /// ```
+    /// use reversi::{Board, Piece};
+    ///
+    /// let position = Board::new().to_position(Piece::White);
+    /// assert_eq!(Ok((Board::new(), Piece::White)), Board::from_position(&position));
+    /// assert!(Board::from_position("XO?").is_err());
+    /// ```
+    pub fn from_position(s: &str) -> Result<(Board, Piece), String> {
+        let s = s.trim();
+        let (matrix, turn) = match s.char_indices().next_back() {
+            Some((idx, turn)) => (&s[..idx], turn),
+            None => return Err("empty position".to_string()),
+        };
+        let turn = match turn {
+            'X' | 'x' | '*' | 'B' | 'b' => Piece::Black,
+            'O' | 'o' | 'W' | 'w' => Piece::White,
+            _ => return Err(format!("invalid side to move: {:?}", turn)),
+        };
+        Ok((Board::from_matrix(matrix.parse()?), turn))
+    }
+
     /// 指定の色の石を指定の位置に置いたとき、指定の方向へひっくり返せる石の数を返す
     ///
     /// * `piece` - 置く石の色
     /// * `pos` - 石を置く位置
     /// * `dir` - ひっくり返せる石を探す方向。`DIRECTIONS` の要素のいずれかが渡される
-    fn get_flip(&self, piece: Piece, mut pos: Coord, dir: Coord) -> u8 {
-        unimplemented!();
+    pub(crate) fn get_flip(&self, piece: Piece, mut pos: Coord, dir: Coord) -> u8 {
+        let opponent = Some(piece.opponent());
+        let mut flip = 0;
+        pos += dir;
+        while self.matrix[pos] == opponent {
+            flip += 1;
+            pos += dir;
+        }
+        if self.matrix[pos] == Some(piece) {
+            flip
+        } else {
+            0
+        }
     }
 
     /// 指定の色の石を指定の位置に置いたときの `Move` を返す
     ///
     /// 戻り値の `Move` には8方向分の `get_flip` の結果が含まれる
     fn get_move(&self, piece: Piece, pos: Coord) -> Move {
-        unimplemented!();
+        let mut flips = ZERO_FLIP;
+        if self.matrix[pos].is_none() {
+            for (flip, &dir) in flips.iter_mut().zip(DIRECTIONS.iter()) {
+                *flip = self.get_flip(piece, pos, dir);
+            }
+        }
+        Move { pos, flips }
     }
 
     /// 合法な Move のリストを返す
     ///
     /// 盤面の左上から右下まで走査して、合法手を探し出す
-    pub fn moves(&self, piece: Piece) -> Moves {
-        unimplemented!();
+    ///
+    /// ```
+    /// use reversi::{Board, Coord, Piece};
+    ///
+    /// let mut board = Board::new();
+    /// let mov = board.moves(Piece::Black).into_iter().find(|mov| mov.pos == "d3".parse().unwrap()).unwrap();
+    /// board.do_move(Piece::Black, &mov);
+    /// assert_eq!((4, 1), (board.black, board.white));
+    /// assert_eq!(Some(Piece::Black), board.matrix()[Coord(3, 2)]);
+    /// ```
+    pub fn moves(&self, piece: Piece) -> Moves<'_> {
+        let mut moves = Moves::new();
+        for y in 0..self.matrix.size() {
+            for x in 0..self.matrix.size() {
+                let mov = self.get_move(piece, Coord(x as i8, y as i8));
+                if mov.is_legal() {
+                    moves.push(mov);
+                }
+            }
+        }
+        moves
+    }
+
+    /// 空きマスの数を返す
+    pub fn empties(&self) -> u8 {
+        (MATRIX_SIZE * MATRIX_SIZE) as u8 - self.black - self.white
     }
 
     /// 指定の色のカウンタへのミュータブルな参照を返す
     fn count_mut(&mut self, piece: Piece) -> &mut u8 {
-        unimplemented!();
+        match piece {
+            Piece::Black => &mut self.black,
+            Piece::White => &mut self.white,
+        }
     }
 
     /// 石を指定の位置から指定の方向へ指定の数だけ指定の色にひっくり返す
@@ -182,12 +334,26 @@ impl Board {
     ///
     /// ひっくり返した分だけ `black`/`white` の数を増減させる必要がある
     fn do_flip(&mut self, piece: Piece, mut pos: Coord, dir: Coord, flip: u8) {
-        unimplemented!();
+        for _ in 0..flip {
+            pos += dir;
+            self.matrix[pos] = Some(piece);
+        }
+        *self.count_mut(piece) += flip;
+        *self.count_mut(piece.opponent()) -= flip;
     }
 
     /// 指定の色で指定の「手」を打つ
     pub fn do_move(&mut self, piece: Piece, mov: &Move) {
-        unimplemented!();
+        for (&flip, &dir) in mov.flips.iter().zip(DIRECTIONS.iter()) {
+            self.do_flip(piece, mov.pos, dir, flip);
+        }
+        self.matrix[mov.pos] = Some(piece);
+        *self.count_mut(piece) += 1;
+    }
+}
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
     }
 }
 impl fmt::Display for Board {
@@ -198,6 +364,7 @@ impl fmt::Display for Board {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::useless_vec)]
 mod tests {
     use piece::*;
     use super::*;
@@ -248,6 +415,31 @@ mod tests {
         assert_eq!(B, matrix[Coord(0, 0)]);
     }
 
+    #[test]
+    fn test_matrix_position_string() {
+        let matrix = Matrix::new();
+        let s = matrix.to_position_string();
+        assert_eq!(
+            "---------------------------OX------XO---------------------------",
+            s,
+        );
+        assert_eq!(Ok(matrix), s.parse());
+        assert_eq!(Ok(SAMPLE_MATRIX), SAMPLE_MATRIX.to_position_string().parse());
+        assert!("XO-".parse::<Matrix>().is_err());
+        assert!(s.replace('X', "?").parse::<Matrix>().is_err());
+    }
+
+    #[test]
+    fn test_board_position() {
+        let position = SAMPLE_BOARD.to_position(Piece::White);
+        assert!(position.ends_with(" O"));
+        let (board, turn) = Board::from_position(&position).unwrap();
+        assert_eq!(SAMPLE_BOARD, board);
+        assert_eq!(Piece::White, turn);
+        assert!(Board::from_position(&position.replace(" O", " ?")).is_err());
+        assert!(Board::from_position("").is_err());
+    }
+
     #[test]
     fn test_move_is_legal() {
         let b = Board::new();
@@ -0,0 +1,945 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+use clock::TimeControl;
+use game::{self, Game, Outcome, Play, Resignation};
+use players::{Human, PlayerKind};
+use coach::{self, Coach};
+use analysis;
+use perft;
+use bench;
+use openings;
+use rng::Rng;
+use remote;
+use server;
+use online;
+use tui;
+use tournament::{self, Format, Sprt};
+
+pub const USAGE: &str = "\
+Usage: reversi [OPTIONS]
+       reversi tournament [TOURNAMENT OPTIONS] <PLAYER>...
+       reversi openings [OPENINGS OPTIONS]
+       reversi nboard [PLAYER]
+       reversi gtp [PLAYER]
+       reversi serve [SERVE OPTIONS]
+       reversi host [HOST OPTIONS]
+       reversi join <ADDRESS> [PLAYER]
+       reversi online <ADDRESS> --user <NAME> [ONLINE OPTIONS] [PLAYER]
+       reversi analyze [ANALYZE OPTIONS] <FILE>
+       reversi perft [PERFT OPTIONS] <DEPTH>
+       reversi bench [--duration <SECS>] [PLAYER]...
+
+Options:
+  -b, --black <PLAYER>     Black player (default: alphabeta:7)
+  -w, --white <PLAYER>     White player (default: human)
+      --position <POS>     Start from POS: 64 squares of X, O or -, row by row
+                           from a1 to h8, then the side to move, e.g. \"---...--- X\"
+      --opening <MOVES>    Play from the position after MOVES, e.g. f5d6c3
+      --xot                Start each game from a random bundled XOT opening
+  -n, --games <N>          Number of games to play (default: 1)
+      --seed <N>           Seed for random players (default: current time)
+  -t, --time <CONTROL>     Time control in seconds: 300, 300+2 or byoyomi:5
+  -o, --transcript <FILE>  Write the moves of each game to FILE
+      --ponder             Let engines think on the opponent's time
+      --no-ponder          Never ponder (default when no human plays)
+  -q, --quiet              Print only the results
+  -v, --verbose            Also print every move and the time it took
+      --coach              Show the best moves to human players, warn before
+                           mistakes and review them after the game
+      --coach-threshold <DISCS>
+                           Warn before moves losing more than DISCS (default: 4)
+      --tui                Play one game in a full-screen terminal UI with
+                           cursor and mouse input
+  -h, --help               Print this help
+
+Tournament options:
+      --gauntlet           Only pair the first player with each of the others
+      --rounds <N>         Color-swapped game pairs per pairing (default: 10)
+  -t, --time <CONTROL>     Time control for every game
+      --sprt <ELO0,ELO1>   Stop a pairing once SPRT decides between ELO0 and ELO1
+      --openings <FILE>    Openings to play, one move list per line
+                           (default: the bundled XOT suite)
+      --seed <N>           Seed for opening order and random players
+
+Openings options (generates balanced openings):
+  -n, --count <N>          Number of openings (default: 100)
+      --plies <N>          Random moves per opening (default: 8)
+      --depth <N>          Search depth of the evaluation (default: 6)
+      --threshold <DISCS>  Largest accepted |evaluation| (default: 2)
+      --seed <N>           Seed for the random moves
+
+NBoard and GTP modes (speak the NBoard engine protocol or a GTP-like
+protocol on stdin/stdout):
+  PLAYER                   Engine to search with (default: alphabeta:7)
+                           NBoard hints always use alphabeta at the engine's
+                           depth (7 for players without one)
+
+Serve options (HTTP/JSON API on localhost):
+      --port <PORT>        Port to listen on (default: 8080)
+      --cors <ORIGIN>      Allow browsers on ORIGIN (or * for any) to call the API
+      --max-games <N>      Games that may be open at once (default: 100)
+      --max-matches <N>    Engine matches that may run at once (default: 4)
+
+Host options (wait for an opponent to join over TCP):
+      --port <PORT>        Port to listen on (default: 7878)
+      --color <COLOR>      Color to play, black or white (default: black)
+  -p, --player <PLAYER>    Player on this side (default: human)
+  -t, --time <CONTROL>     Time control for both sides
+      --timeout <SECS>     Seconds to wait for each remote move when there is
+                           no time control (default: 300)
+
+Join (connect to a host at ADDRESS, e.g. 192.168.0.2:7878):
+  PLAYER                   Player on this side (default: human)
+
+Online options (play on a game server at ADDRESS, e.g. example.com:5000):
+      --user <NAME>        Login name
+      --password <PASS>    Password (default: $REVERSI_PASSWORD)
+      --seek <TIME>        Seek an opponent with TIME such as 5:00 (default)
+      --accept <ID>        Accept the seek with ID instead
+  PLAYER                   Engine to play with (default: alphabeta:7)
+
+Analyze options (review a saved game: a transcript such as f5d6c3 or GGF):
+      --depth <N>          Search depth for every position (default: 8)
+      --position <POS>     Position the transcript starts from
+      --json               Print the report as JSON
+
+Perft options (count the positions DEPTH plies ahead, passes included):
+      --position <POS>     Position to count from (default: the initial one)
+      --divide             Print the count below each move of the position
+
+Bench options (measure move generation and search on fixed positions;
+build with --release):
+      --duration <SECS>    Seconds to repeat each move generation benchmark
+                           (default: 1)
+  PLAYER                   Engines whose time to each depth is measured
+                           (default: dumb random negamax:6 alphabeta:9)
+
+Players:
+  human, dumb, random, negamax[:DEPTH], alphabeta[:DEPTH]
+";
+
+/// 対局の様子をどれだけ表示するか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// コマンドラインで指定された対局の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub black: PlayerKind,
+    pub white: PlayerKind,
+    /// 対局を始める局面と手番。None の場合は初期局面から始める
+    pub position: Option<(Board, Piece)>,
+    /// `position` から打つ序盤の候補。複数ある場合は対局ごとに無作為に選ぶ
+    pub openings: Vec<Vec<Coord>>,
+    pub games: usize,
+    pub seed: u64,
+    pub time_control: Option<TimeControl>,
+    pub transcript: Option<String>,
+    pub pondering: bool,
+    pub verbosity: Verbosity,
+    /// 人間のプレイヤーにつけるコーチが警告する損失（石の差）。None ならコーチをつけない
+    pub coach: Option<i8>,
+    /// 全画面の端末 UI で対局する
+    pub tui: bool,
+}
+impl Options {
+    /// 対局で打つ序盤を `openings` から選ぶ。候補が1つだけなら乱数を消費しない
+    pub fn pick_opening(&self, rng: &mut Rng) -> &[Coord] {
+        match self.openings.len() {
+            1 => &self.openings[0],
+            len => &self.openings[rng.below(len)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play(Options),
+    Tournament(Vec<PlayerKind>, tournament::Settings),
+    Openings {
+        count: usize,
+        plies: usize,
+        depth: usize,
+        threshold: i8,
+        seed: u64,
+    },
+    NBoard(PlayerKind),
+    Gtp(PlayerKind),
+    Serve(server::Settings),
+    Host(u16, remote::Settings),
+    Join(String, PlayerKind),
+    Online(online::Settings),
+    Analyze(AnalyzeSettings),
+    Perft(PerftSettings),
+    Bench(bench::Settings),
+    Help,
+}
+
+/// `analyze` サブコマンドの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzeSettings {
+    pub path: String,
+    pub depth: usize,
+    /// 棋譜を打ち始める局面。None の場合は初期局面
+    pub position: Option<(Board, Piece)>,
+    pub json: bool,
+}
+
+/// `perft` サブコマンドの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerftSettings {
+    pub depth: usize,
+    /// 数え始める局面と手番。None の場合は初期局面
+    pub position: Option<(Board, Piece)>,
+    /// 手ごとの数も表示する
+    pub divide: bool,
+}
+
+/// 現在時刻から作ったシード
+pub fn default_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// コマンドライン引数（プログラム名を除く）を読む
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let first = args.next();
+    match first.as_deref() {
+        Some("tournament") => parse_tournament(args),
+        Some("openings") => parse_openings(args),
+        Some("nboard") => parse_engine(args).map(Command::NBoard),
+        Some("gtp") => parse_engine(args).map(Command::Gtp),
+        Some("serve") => parse_serve(args),
+        Some("host") => parse_host(args),
+        Some("join") => parse_join(args),
+        Some("online") => parse_online(args),
+        Some("analyze") => parse_analyze(args),
+        Some("perft") => parse_perft(args),
+        Some("bench") => parse_bench(args),
+        // サブコマンドでなければ、最初の引数から対局の設定として読む
+        _ => parse_play(first.into_iter().chain(args)),
+    }
+}
+
+/// サブコマンドなしで起動したときの対局の設定を読む
+fn parse_play<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut options = Options {
+        black: PlayerKind::AlphaBeta(7),
+        white: PlayerKind::Human,
+        position: None,
+        openings: vec![Vec::new()],
+        games: 1,
+        seed: 0,
+        time_control: None,
+        transcript: None,
+        pondering: false,
+        verbosity: Verbosity::Normal,
+        coach: None,
+        tui: false,
+    };
+    let mut seed = None;
+    let mut pondering = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "-b" | "--black" => options.black = value()?.parse()?,
+            "-w" | "--white" => options.white = value()?.parse()?,
+            "--position" => options.position = Some(Board::from_position(&value()?)?),
+            "--opening" => options.openings = vec![game::parse_transcript(&value()?)?],
+            "--xot" => options.openings = openings::suite(),
+            "-n" | "--games" => {
+                options.games = match value()?.parse() {
+                    Ok(games) if games > 0 => games,
+                    _ => return Err("--games must be a positive integer".to_string()),
+                }
+            },
+            "--seed" => seed = Some(value()?.parse().map_err(|_| "--seed must be an integer".to_string())?),
+            "-t" | "--time" => options.time_control = Some(value()?.parse()?),
+            "-o" | "--transcript" => options.transcript = Some(value()?),
+            "--ponder" => pondering = Some(true),
+            "--no-ponder" => pondering = Some(false),
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--coach" => options.coach = Some(options.coach.unwrap_or(coach::DEFAULT_THRESHOLD)),
+            "--coach-threshold" => {
+                let threshold = value()?.parse().map_err(|_| "--coach-threshold must be a number of discs".to_string())?;
+                options.coach = Some(threshold);
+            },
+            "--tui" => options.tui = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    if options.tui && (options.games > 1 || options.transcript.is_some()) {
+        return Err("--tui plays a single game without a transcript".to_string());
+    }
+    let has_human = options.black == PlayerKind::Human || options.white == PlayerKind::Human;
+    if options.coach.is_some() && (!has_human || options.tui) {
+        return Err("--coach needs a human player outside the terminal UI".to_string());
+    }
+    options.pondering = pondering.unwrap_or(has_human);
+    options.seed = seed.unwrap_or_else(default_seed);
+    Ok(Command::Play(options))
+}
+
+/// `tournament` サブコマンドの引数を読む
+fn parse_tournament<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut settings = tournament::Settings {
+        format: Format::RoundRobin,
+        rounds: 10,
+        time_control: None,
+        sprt: None,
+        openings: openings::suite(),
+        seed: 0,
+    };
+    let mut seed = None;
+    let mut entrants = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--gauntlet" => settings.format = Format::Gauntlet,
+            "--rounds" => {
+                settings.rounds = match value()?.parse() {
+                    Ok(rounds) if rounds > 0 => rounds,
+                    _ => return Err("--rounds must be a positive integer".to_string()),
+                }
+            },
+            "-t" | "--time" => settings.time_control = Some(value()?.parse()?),
+            "--sprt" => {
+                let value = value()?;
+                let bounds: Vec<f64> = value.split(',').filter_map(|elo| elo.trim().parse().ok()).collect();
+                match bounds[..] {
+                    [elo0, elo1] if elo0 < elo1 => settings.sprt = Some(Sprt::new(elo0, elo1)),
+                    _ => return Err(format!("invalid SPRT bounds: {:?}", value)),
+                }
+            },
+            "--openings" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path, err))?;
+                settings.openings = openings::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+            },
+            "--seed" => seed = Some(value()?.parse().map_err(|_| "--seed must be an integer".to_string())?),
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => match arg.parse()? {
+                PlayerKind::Human => return Err("humans cannot enter a tournament".to_string()),
+                kind => entrants.push(kind),
+            },
+        }
+    }
+    if entrants.len() < 2 {
+        return Err("a tournament needs at least two players".to_string());
+    }
+    settings.seed = seed.unwrap_or_else(default_seed);
+    Ok(Command::Tournament(entrants, settings))
+}
+
+/// `openings` サブコマンドの引数を読む
+fn parse_openings<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let (mut count, mut plies, mut depth, mut threshold, mut seed) = (100, 8, 6, 2, None);
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg));
+        let invalid = |_| format!("invalid value for {}", arg);
+        match arg.as_str() {
+            "-n" | "--count" => count = value?.parse().map_err(invalid)?,
+            "--plies" => plies = value?.parse().map_err(invalid)?,
+            "--depth" => depth = value?.parse().map_err(invalid)?,
+            "--threshold" => threshold = value?.parse().map_err(invalid)?,
+            "--seed" => seed = Some(value?.parse().map_err(invalid)?),
+            "-h" | "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    if count == 0 || plies == 0 || depth == 0 || threshold < 0 {
+        return Err("--count, --plies and --depth must be positive and --threshold non-negative".to_string());
+    }
+    let seed = seed.unwrap_or_else(default_seed);
+    Ok(Command::Openings { count, plies, depth, threshold, seed })
+}
+
+/// `nboard` と `gtp` サブコマンドの引数として、エンジンにするプレイヤーを読む
+fn parse_engine<I: Iterator<Item = String>>(mut args: I) -> Result<PlayerKind, String> {
+    let kind = match args.next() {
+        Some(arg) => arg.parse()?,
+        None => PlayerKind::AlphaBeta(7),
+    };
+    if kind == PlayerKind::Human {
+        return Err("the engine cannot be a human".to_string());
+    }
+    if let Some(arg) = args.next() {
+        return Err(format!("unexpected argument: {}", arg));
+    }
+    Ok(kind)
+}
+
+/// `serve` サブコマンドの引数を読む
+fn parse_serve<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut settings = server::Settings::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                settings.port = value.parse().map_err(|_| format!("invalid port: {:?}", value))?;
+            },
+            "--cors" => {
+                settings.cors = Some(args.next().ok_or_else(|| format!("missing value for {}", arg))?);
+            },
+            "--max-games" => {
+                let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                settings.max_games = value.parse().map_err(|_| format!("invalid number of games: {:?}", value))?;
+            },
+            "--max-matches" => {
+                let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                settings.max_matches = value.parse().map_err(|_| format!("invalid number of matches: {:?}", value))?;
+            },
+            "-h" | "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    Ok(Command::Serve(settings))
+}
+
+/// `host` サブコマンドの引数を読む
+fn parse_host<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut port = 7878;
+    let mut settings = remote::Settings {
+        color: Piece::Black,
+        player: PlayerKind::Human,
+        time_control: None,
+        timeout: remote::DEFAULT_TIMEOUT,
+    };
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--port" => {
+                let value = value?;
+                port = value.parse().map_err(|_| format!("invalid port: {:?}", value))?;
+            },
+            "--color" => {
+                settings.color = match value?.as_str() {
+                    "black" => Piece::Black,
+                    "white" => Piece::White,
+                    color => return Err(format!("invalid color: {:?}", color)),
+                }
+            },
+            "-p" | "--player" => settings.player = value?.parse()?,
+            "-t" | "--time" => settings.time_control = Some(value?.parse()?),
+            "--timeout" => {
+                settings.timeout = match value?.parse() {
+                    Ok(secs) if secs > 0 => Duration::from_secs(secs),
+                    _ => return Err("--timeout must be a positive integer".to_string()),
+                }
+            },
+            "-h" | "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    Ok(Command::Host(port, settings))
+}
+
+/// `join` サブコマンドの引数を読む
+fn parse_join<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let address = match args.next() {
+        Some(ref arg) if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+        Some(address) => address,
+        None => return Err("missing the address to join".to_string()),
+    };
+    let kind = match args.next() {
+        Some(arg) => arg.parse()?,
+        None => PlayerKind::Human,
+    };
+    if let Some(arg) = args.next() {
+        return Err(format!("unexpected argument: {}", arg));
+    }
+    Ok(Command::Join(address, kind))
+}
+
+/// `online` サブコマンドの引数を読む
+fn parse_online<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut settings = online::Settings {
+        address: String::new(),
+        name: String::new(),
+        password: env::var("REVERSI_PASSWORD").unwrap_or_default(),
+        request: online::Request::Seek("5:00".to_string()),
+        player: PlayerKind::AlphaBeta(7),
+    };
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--user" => settings.name = value()?,
+            "--password" => settings.password = value()?,
+            "--seek" => settings.request = online::Request::Seek(value()?),
+            "--accept" => {
+                let value = value()?;
+                let id = value.parse().map_err(|_| format!("invalid seek id: {:?}", value))?;
+                settings.request = online::Request::Accept(id);
+            },
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => rest.push(arg),
+        }
+    }
+    let mut rest = rest.into_iter();
+    settings.address = rest.next().ok_or("missing the server address")?;
+    if let Some(kind) = rest.next() {
+        settings.player = kind.parse()?;
+    }
+    if let Some(arg) = rest.next() {
+        return Err(format!("unexpected argument: {}", arg));
+    }
+    if settings.name.is_empty() {
+        return Err("--user is required".to_string());
+    }
+    if settings.player == PlayerKind::Human {
+        return Err("the online player must be an engine".to_string());
+    }
+    Ok(Command::Online(settings))
+}
+
+/// `analyze` サブコマンドの引数を読む
+fn parse_analyze<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut path = None;
+    let mut settings = AnalyzeSettings {
+        path: String::new(),
+        depth: 8,
+        position: None,
+        json: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--depth" => {
+                settings.depth = match value()?.parse() {
+                    Ok(depth) if depth > 0 => depth,
+                    _ => return Err("--depth must be a positive integer".to_string()),
+                };
+            },
+            "--position" => settings.position = Some(Board::from_position(&value()?)?),
+            "--json" => settings.json = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    settings.path = path.ok_or("missing the file to analyze")?;
+    Ok(Command::Analyze(settings))
+}
+
+/// 保存した対局を分析し、報告を表示する
+pub fn analyze(settings: &AnalyzeSettings) -> Result<(), String> {
+    let text = fs::read_to_string(&settings.path).map_err(|err| format!("{}: {}", settings.path, err))?;
+    let game = analysis::load(&text, settings.position.clone())?;
+    let report = analysis::analyze(&game, settings.depth);
+    if settings.json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/// `perft` サブコマンドの引数を読む
+fn parse_perft<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut depth = None;
+    let mut settings = PerftSettings {
+        depth: 0,
+        position: None,
+        divide: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => {
+                let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                settings.position = Some(Board::from_position(&value)?);
+            },
+            "--divide" => settings.divide = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if depth.is_none() => {
+                depth = Some(arg.parse().map_err(|_| "DEPTH must be a non-negative integer".to_string())?);
+            },
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    settings.depth = depth.ok_or("missing the depth to count")?;
+    Ok(Command::Perft(settings))
+}
+
+/// 末端局面の数を表示する。`divide` なら根の手ごとの数を先に並べる
+pub fn perft(settings: &PerftSettings) {
+    let (board, turn) = settings.position.clone().unwrap_or((Board::new(), Piece::Black));
+    let started = Instant::now();
+    let nodes = if settings.divide {
+        let divided = perft::divide(&board, turn, settings.depth);
+        for &(pos, nodes) in &divided {
+            println!("{} {}", pos.map_or("pass".to_string(), |pos| pos.to_string()), nodes);
+        }
+        if divided.is_empty() {
+            // 深さ 0 か終局した局面では、その局面自体が唯一の末端になる
+            perft::perft(&board, turn, settings.depth)
+        } else {
+            divided.iter().map(|&(_, nodes)| nodes).sum()
+        }
+    } else {
+        perft::perft(&board, turn, settings.depth)
+    };
+    println!("{} nodes in {:.3}s", nodes, started.elapsed().as_secs_f64());
+}
+
+/// `bench` サブコマンドの引数を読む
+fn parse_bench<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut settings = bench::Settings::default();
+    let mut players = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--duration" => {
+                let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                settings.duration = match value.parse::<f64>() {
+                    Ok(secs) if secs > 0.0 && secs.is_finite() => Duration::from_secs_f64(secs),
+                    _ => return Err("--duration must be a positive number of seconds".to_string()),
+                };
+            },
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => match arg.parse()? {
+                PlayerKind::Human => return Err("a human player cannot be benchmarked".to_string()),
+                kind => players.push(kind),
+            },
+        }
+    }
+    if !players.is_empty() {
+        settings.players = players;
+    }
+    Ok(Command::Bench(settings))
+}
+
+/// 対局者を作る。コーチの指定があれば人間のプレイヤーにつける
+fn build(options: &Options, kind: PlayerKind, seed: u64) -> Box<dyn Play + Send> {
+    match (kind, options.coach) {
+        (PlayerKind::Human, Some(threshold)) => Box::new(Human::new().with_coach(Coach::new(coach::DEPTH, threshold))),
+        (kind, _) => kind.build(seed),
+    }
+}
+
+/// 設定に従って対局を行う
+pub fn run(options: &Options) -> Result<(), String> {
+    if options.tui {
+        return tui::run(options);
+    }
+    let mut transcript = match options.transcript {
+        Some(ref path) => Some(File::create(path).map_err(|err| format!("{}: {}", path, err))?),
+        None => None,
+    };
+    let mut rng = Rng::new(options.seed);
+    if options.verbosity == Verbosity::Verbose {
+        println!("Seed: {}", options.seed);
+    }
+    let (mut black_wins, mut white_wins, mut draws) = (0, 0, 0);
+    for idx in 0..options.games {
+        let black = build(options, options.black, rng.next_u64());
+        let white = build(options, options.white, rng.next_u64());
+        let mut game = match options.position {
+            Some((ref board, turn)) => Game::from_position(board.clone(), turn, black, white)
+                .map_err(|err| format!("invalid position: {}", err))?,
+            None => Game::new(black, white),
+        };
+        if let Some(control) = options.time_control {
+            game.set_time_control(control);
+        }
+        game.set_pondering(options.pondering);
+        game.set_takebacks(true);
+        game.replay(options.pick_opening(&mut rng)).map_err(|err| format!("invalid opening: {}", err))?;
+        loop {
+            if options.verbosity != Verbosity::Quiet {
+                game.print();
+            }
+            let plies = game.history().len();
+            let started = Instant::now();
+            let is_continued = game.step();
+            if options.verbosity == Verbosity::Verbose && game.history().len() > plies {
+                let ply = &game.history()[plies];
+                println!("{} ({:.2}s)", ply, started.elapsed().as_secs_f64());
+            }
+            if !is_continued {
+                break;
+            }
+        }
+        let outcome = game.outcome().expect("the game is over");
+        if options.verbosity != Verbosity::Quiet {
+            println!("{}", game.board());
+        }
+        if options.games > 1 {
+            println!("Game {}: {}", idx + 1, outcome);
+        } else {
+            println!("{}", outcome);
+        }
+        match outcome.winner() {
+            Some(Piece::Black) => black_wins += 1,
+            Some(Piece::White) => white_wins += 1,
+            None => draws += 1,
+        }
+        if let Some(ref mut file) = transcript {
+            writeln!(file, "# Game {}: {}", idx + 1, outcome)
+                .and_then(|_| writeln!(file, "{}", game.transcript()))
+                .map_err(|err| err.to_string())?;
+        }
+        if let Outcome::Resigned(_, Resignation::Quit) = outcome {
+            break;
+        }
+    }
+    if options.games > 1 {
+        println!("Black {} - {} White ({} draws)", black_wins, white_wins, draws);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_cli_defaults() {
+        let options = match parse(args("--seed 1")).unwrap() {
+            Command::Play(options) => options,
+            command => panic!("unexpected command: {:?}", command),
+        };
+        assert_eq!(PlayerKind::AlphaBeta(7), options.black);
+        assert_eq!(PlayerKind::Human, options.white);
+        assert_eq!(1, options.games);
+        assert_eq!(1, options.seed);
+        assert!(options.pondering);
+        assert_eq!(Verbosity::Normal, options.verbosity);
+    }
+
+    #[test]
+    fn test_cli_options() {
+        let command = parse(args(
+            "-b negamax:3 --white random --opening f5d6 -n 4 --seed 42 -t 60+1 -o out.txt -q",
+        )).unwrap();
+        let expected = Options {
+            black: PlayerKind::NegaMax(3),
+            white: PlayerKind::Random,
+            position: None,
+            openings: vec![vec![Coord(5, 4), Coord(3, 5)]],
+            games: 4,
+            seed: 42,
+            time_control: Some(TimeControl::Increment(Duration::from_secs(60), Duration::from_secs(1))),
+            transcript: Some("out.txt".to_string()),
+            pondering: false,
+            verbosity: Verbosity::Quiet,
+            coach: None,
+            tui: false,
+        };
+        assert_eq!(Command::Play(expected), command);
+        assert_eq!(Ok(Command::Help), parse(args("-b dumb --help")));
+    }
+
+    #[test]
+    fn test_cli_errors() {
+        assert!(parse(args("--black")).is_err());
+        assert!(parse(args("--black chess")).is_err());
+        assert!(parse(args("--black dumb:3")).is_err());
+        assert!(parse(args("--white alphabeta:0")).is_err());
+        assert!(parse(args("--games 0")).is_err());
+        assert!(parse(args("--time fast")).is_err());
+        assert!(parse(args("--opening f5x")).is_err());
+        assert!(parse(args("--frobnicate")).is_err());
+        assert!(parse(args("--tui --games 2")).is_err());
+        assert!(parse(args("-b dumb -w dumb --coach")).is_err());
+        assert!(parse(args("--coach-threshold many")).is_err());
+    }
+
+    #[test]
+    fn test_cli_analyze() {
+        let expected = AnalyzeSettings {
+            path: "game.txt".to_string(),
+            depth: 10,
+            position: None,
+            json: true,
+        };
+        assert_eq!(Ok(Command::Analyze(expected)), parse(args("analyze --depth 10 game.txt --json")));
+        assert!(parse(args("analyze")).is_err());
+        assert!(parse(args("analyze --depth 0 game.txt")).is_err());
+        assert!(parse(args("analyze a.txt b.txt")).is_err());
+    }
+
+    #[test]
+    fn test_cli_perft() {
+        let expected = PerftSettings {
+            depth: 6,
+            position: Some((Board::new(), Piece::White)),
+            divide: true,
+        };
+        let position = Board::new().to_position(Piece::White);
+        let parsed = parse(vec!["perft", "--divide", "6", "--position", &position].into_iter().map(String::from));
+        assert_eq!(Ok(Command::Perft(expected)), parsed);
+        assert_eq!(Ok(Command::Perft(PerftSettings { depth: 0, position: None, divide: false })), parse(args("perft 0")));
+        assert!(parse(args("perft")).is_err());
+        assert!(parse(args("perft -1")).is_err());
+        assert!(parse(args("perft 3 4")).is_err());
+    }
+
+    #[test]
+    fn test_cli_bench() {
+        assert_eq!(Ok(Command::Bench(bench::Settings::default())), parse(args("bench")));
+        let expected = bench::Settings {
+            players: vec![PlayerKind::AlphaBeta(9), PlayerKind::Random],
+            duration: Duration::from_millis(500),
+        };
+        assert_eq!(Ok(Command::Bench(expected)), parse(args("bench alphabeta:9 --duration 0.5 random")));
+        assert!(parse(args("bench human")).is_err());
+        assert!(parse(args("bench --duration 0")).is_err());
+    }
+
+    #[test]
+    fn test_cli_coach() {
+        let coach = |s: &str| match parse(args(s)) {
+            Ok(Command::Play(options)) => options.coach,
+            command => panic!("unexpected command: {:?}", command),
+        };
+        assert_eq!(None, coach("-w human"));
+        assert_eq!(Some(coach::DEFAULT_THRESHOLD), coach("--coach"));
+        assert_eq!(Some(8), coach("--coach-threshold 8 --coach"));
+    }
+
+    #[test]
+    fn test_cli_tournament() {
+        let command = parse(args("tournament --gauntlet --rounds 4 --sprt -10,20 --seed 3 alphabeta:5 dumb random"));
+        let settings = tournament::Settings {
+            format: Format::Gauntlet,
+            rounds: 4,
+            time_control: None,
+            sprt: Some(Sprt::new(-10.0, 20.0)),
+            openings: openings::suite(),
+            seed: 3,
+        };
+        let entrants = vec![PlayerKind::AlphaBeta(5), PlayerKind::Dumb, PlayerKind::Random];
+        assert_eq!(Ok(Command::Tournament(entrants, settings)), command);
+        assert!(parse(args("tournament dumb")).is_err());
+        assert!(parse(args("tournament dumb human")).is_err());
+        assert!(parse(args("tournament --sprt 20,10 dumb random")).is_err());
+        assert!(parse(args("tournament --rounds 0 dumb random")).is_err());
+        assert!(parse(args("tournament --openings /nonexistent dumb random")).is_err());
+    }
+
+    #[test]
+    fn test_cli_openings() {
+        let command = parse(args("openings -n 5 --plies 6 --threshold 0 --seed 9"));
+        let expected = Command::Openings {
+            count: 5,
+            plies: 6,
+            depth: 6,
+            threshold: 0,
+            seed: 9,
+        };
+        assert_eq!(Ok(expected), command);
+        assert!(parse(args("openings --plies 0")).is_err());
+        assert!(parse(args("openings --depth deep")).is_err());
+        match parse(args("--xot")).unwrap() {
+            Command::Play(options) => assert_eq!(openings::SUITE.len(), options.openings.len()),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_cli_nboard() {
+        assert_eq!(Ok(Command::NBoard(PlayerKind::AlphaBeta(7))), parse(args("nboard")));
+        assert_eq!(Ok(Command::NBoard(PlayerKind::NegaMax(4))), parse(args("nboard negamax:4")));
+        assert!(parse(args("nboard human")).is_err());
+        assert!(parse(args("nboard dumb random")).is_err());
+        assert_eq!(Ok(Command::Gtp(PlayerKind::Random)), parse(args("gtp random")));
+        assert_eq!(Ok(Command::Serve(server::Settings::default())), parse(args("serve")));
+        let settings = server::Settings { port: 3000, cors: Some("*".to_string()), max_games: 10, max_matches: 2 };
+        assert_eq!(Ok(Command::Serve(settings)), parse(args("serve --port 3000 --cors * --max-games 10 --max-matches 2")));
+        assert!(parse(args("serve --port http")).is_err());
+        assert!(parse(args("serve --cors")).is_err());
+    }
+
+    #[test]
+    fn test_cli_host_and_join() {
+        let settings = remote::Settings {
+            color: Piece::White,
+            player: PlayerKind::AlphaBeta(5),
+            time_control: Some(TimeControl::SuddenDeath(Duration::from_secs(60))),
+            timeout: remote::DEFAULT_TIMEOUT,
+        };
+        let command = parse(args("host --port 9000 --color white -p alphabeta:5 -t 60"));
+        assert_eq!(Ok(Command::Host(9000, settings)), command);
+        assert!(parse(args("host --color red")).is_err());
+        assert!(parse(args("host --timeout 0")).is_err());
+        let command = parse(args("join localhost:7878"));
+        assert_eq!(Ok(Command::Join("localhost:7878".to_string(), PlayerKind::Human)), command);
+        assert_eq!(Ok(Command::Join("h:1".to_string(), PlayerKind::Dumb)), parse(args("join h:1 dumb")));
+        assert!(parse(args("join")).is_err());
+        assert!(parse(args("join h:1 dumb dumb")).is_err());
+    }
+
+    #[test]
+    fn test_cli_online() {
+        let settings = online::Settings {
+            address: "example.com:5000".to_string(),
+            name: "bot".to_string(),
+            password: "pw".to_string(),
+            request: online::Request::Accept(12),
+            player: PlayerKind::NegaMax(4),
+        };
+        let command = parse(args("online example.com:5000 --user bot --password pw --accept 12 negamax:4"));
+        assert_eq!(Ok(Command::Online(settings)), command);
+        match parse(args("online h:1 --user bot --seek 1:00")) {
+            Ok(Command::Online(settings)) => assert_eq!(online::Request::Seek("1:00".to_string()), settings.request),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        assert!(parse(args("online h:1")).is_err());
+        assert!(parse(args("online --user bot")).is_err());
+        assert!(parse(args("online h:1 --user bot --accept x")).is_err());
+        assert!(parse(args("online h:1 --user bot human")).is_err());
+    }
+
+    #[test]
+    fn test_cli_position() {
+        let position = format!("{} O", Board::new().matrix().to_position_string());
+        let mut argv = args("-b dumb -w dumb --position");
+        argv.push(position);
+        match parse(argv).unwrap() {
+            Command::Play(options) => assert_eq!(Some((Board::new(), Piece::White)), options.position),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        assert!(parse(args("--position XO-")).is_err());
+    }
+
+    #[test]
+    fn test_cli_run_rejects_illegal_position() {
+        let mut argv = args("-b dumb -w dumb -q --position");
+        argv.push(format!("{} X", "-".repeat(64)));
+        let options = match parse(argv).unwrap() {
+            Command::Play(options) => options,
+            command => panic!("unexpected command: {:?}", command),
+        };
+        assert!(run(&options).is_err());
+    }
+
+    #[test]
+    fn test_cli_run_rejects_illegal_opening() {
+        let options = match parse(args("-b dumb -w dumb --opening a1 -q")).unwrap() {
+            Command::Play(options) => options,
+            command => panic!("unexpected command: {:?}", command),
+        };
+        assert!(run(&options).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::cmp;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 探索の時間を測る時刻
+///
+/// wasm32 では std の `Instant` が使えないので、ブラウザの時計を使う `web_time` のものを使う。
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+/// 持ち時間の方式を表す列挙型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    /// 持ち時間を使い切った時点で負けになる
+    SuddenDeath(Duration),
+    /// 持ち時間に加えて、1手指すごとに2つ目の値だけ時間が加算される
+    Increment(Duration, Duration),
+    /// 1手ごとに決まった時間が与えられ、使い残した分は持ち越されない
+    Byoyomi(Duration),
+}
+
+/// コマンドラインなどで使う、持ち時間の文字列表記を読む
+///
+/// 秒単位で、`300` は切れ負け、`300+2` はフィッシャー方式、`byoyomi:5` は1手ごとの秒読みを表す。
+impl FromStr for TimeControl {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let secs = |s: &str| match s.parse::<f64>() {
+            Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_millis((secs * 1000.0) as u64)),
+            _ => Err(format!("invalid time control: {:?}", s)),
+        };
+        if let Some(per_move) = s.strip_prefix("byoyomi:") {
+            return Ok(TimeControl::Byoyomi(secs(per_move)?));
+        }
+        match s.find('+') {
+            Some(idx) => Ok(TimeControl::Increment(secs(&s[..idx])?, secs(&s[idx + 1..])?)),
+            None => Ok(TimeControl::SuddenDeath(secs(s)?)),
+        }
+    }
+}
+
+/// 対局者1人分の時計
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Duration,
+    is_flagged: bool,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let remaining = match control {
+            TimeControl::SuddenDeath(total) => total,
+            TimeControl::Increment(total, _) => total,
+            TimeControl::Byoyomi(per_move) => per_move,
+        };
+        Clock {
+            control,
+            remaining,
+            is_flagged: false,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// 今の手番で使うことのできる残り時間を返す
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// 時間切れになっているかどうかを返す
+    pub fn is_flagged(&self) -> bool {
+        self.is_flagged
+    }
+
+    /// 1手に使った時間を時計に反映する
+    ///
+    /// 時間切れになった場合は `false` を返す。
+    pub fn consume(&mut self, elapsed: Duration) -> bool {
+        if elapsed > self.remaining {
+            self.remaining = Duration::from_secs(0);
+            self.is_flagged = true;
+            return false;
+        }
+        self.remaining = match self.control {
+            TimeControl::SuddenDeath(_) => self.remaining - elapsed,
+            TimeControl::Increment(_, increment) => self.remaining - elapsed + increment,
+            TimeControl::Byoyomi(per_move) => per_move,
+        };
+        true
+    }
+
+    /// 残りの手数から、次の1手に使ってよい時間の目安を返す
+    ///
+    /// * `moves_left` - 自分がこの先打つと見込まれる手の数
+    pub fn allot(&self, moves_left: u32) -> Duration {
+        let moves_left = cmp::max(moves_left, 1);
+        let allotted = match self.control {
+            TimeControl::SuddenDeath(_) => self.remaining / moves_left,
+            TimeControl::Increment(_, increment) => self.remaining / moves_left + increment,
+            TimeControl::Byoyomi(_) => self.remaining,
+        };
+        // 時計の誤差で時間切れにならないよう、残り時間の 9 割までに抑える
+        cmp::min(allotted, self.remaining / 10 * 9)
+    }
+}
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.remaining.as_secs();
+        write!(f, "{}:{:02}.{}", secs / 60, secs % 60, self.remaining.subsec_millis() / 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_control_from_str() {
+        assert_eq!(Ok(TimeControl::SuddenDeath(Duration::from_secs(300))), "300".parse());
+        assert_eq!(
+            Ok(TimeControl::Increment(Duration::from_secs(60), Duration::from_millis(500))),
+            "60+0.5".parse(),
+        );
+        assert_eq!(Ok(TimeControl::Byoyomi(Duration::from_secs(5))), "byoyomi:5".parse());
+        assert!("".parse::<TimeControl>().is_err());
+        assert!("-1".parse::<TimeControl>().is_err());
+        assert!("60+".parse::<TimeControl>().is_err());
+        assert!("byoyomi:".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_clock_sudden_death() {
+        let mut clock = Clock::new(TimeControl::SuddenDeath(Duration::from_secs(10)));
+        assert!(clock.consume(Duration::from_secs(4)));
+        assert_eq!(Duration::from_secs(6), clock.remaining());
+        assert!(!clock.consume(Duration::from_secs(7)));
+        assert!(clock.is_flagged());
+        assert_eq!(Duration::from_secs(0), clock.remaining());
+    }
+
+    #[test]
+    fn test_clock_increment() {
+        let control = TimeControl::Increment(Duration::from_secs(10), Duration::from_secs(2));
+        let mut clock = Clock::new(control);
+        assert!(clock.consume(Duration::from_secs(5)));
+        assert_eq!(Duration::from_secs(7), clock.remaining());
+        assert!(clock.consume(Duration::from_secs(7)));
+        assert_eq!(Duration::from_secs(2), clock.remaining());
+    }
+
+    #[test]
+    fn test_clock_byoyomi() {
+        let mut clock = Clock::new(TimeControl::Byoyomi(Duration::from_secs(3)));
+        assert!(clock.consume(Duration::from_secs(2)));
+        assert_eq!(Duration::from_secs(3), clock.remaining());
+        assert!(!clock.consume(Duration::from_millis(3001)));
+    }
+
+    #[test]
+    fn test_clock_allot() {
+        let clock = Clock::new(TimeControl::SuddenDeath(Duration::from_secs(60)));
+        assert_eq!(Duration::from_secs(2), clock.allot(30));
+        assert_eq!(Duration::from_secs(54), clock.allot(0));
+        let clock = Clock::new(TimeControl::Byoyomi(Duration::from_secs(10)));
+        assert_eq!(Duration::from_secs(9), clock.allot(30));
+    }
+
+    #[test]
+    fn test_clock_display() {
+        let clock = Clock::new(TimeControl::SuddenDeath(Duration::from_millis(83_400)));
+        assert_eq!("1:23.4", clock.to_string());
+    }
+}
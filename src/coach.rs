@@ -0,0 +1,163 @@
+use std::fmt;
+use piece::Piece;
+use board::{Board, Move};
+use coord::Coord;
+use players::AlphaBetaPlayer;
+
+/// コーチが局面を調べる深さ
+pub const DEPTH: usize = 6;
+
+/// 警告する損失の既定値（石の差）
+pub const DEFAULT_THRESHOLD: i8 = 4;
+
+/// 手番ごとに見せる候補手の数
+const TOP_MOVES: usize = 3;
+
+/// 対局後に振り返る悪手の数
+const REPORTED_MISTAKES: usize = 3;
+
+/// 最善手より評価値を落とした手
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mistake {
+    /// 何手目か。最初の手を 1 とする
+    pub ply: usize,
+    pub played: Coord,
+    pub best: Coord,
+    /// 最善手と比べて失った石の差
+    pub loss: i8,
+}
+
+impl fmt::Display for Mistake {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}. {} lost {} discs (best: {})", self.ply, self.played, self.loss, self.best)
+    }
+}
+
+/// 人間のプレイヤーの手番に候補手を示し、悪手を打つ前に警告するコーチ
+///
+/// 打った手はすべて最善手と比べ、対局後に損失の大きかった手を振り返る。
+pub struct Coach {
+    engine: AlphaBetaPlayer,
+    /// この石数より多く損をする手を打とうとしたら警告する
+    threshold: i8,
+    mistakes: Vec<Mistake>,
+}
+
+impl Coach {
+    pub fn new(depth: usize, threshold: i8) -> Self {
+        Coach {
+            engine: AlphaBetaPlayer::new(depth),
+            threshold,
+            mistakes: Vec::new(),
+        }
+    }
+
+    /// 合法手を評価値の高い順に並べた分析を返す
+    pub fn analyze(&self, piece: Piece, board: &Board) -> Vec<(Move, i8)> {
+        self.engine.analyze(piece, board)
+    }
+
+    /// 分析のうち上位の候補手を `d3 +2, c4 +0` のように並べる
+    pub fn advice(analysis: &[(Move, i8)]) -> String {
+        let moves: Vec<String> = analysis.iter()
+            .take(TOP_MOVES)
+            .map(|&(ref mov, score)| format!("{} {:+}", mov.pos, score))
+            .collect();
+        moves.join(", ")
+    }
+
+    /// 分析に含まれる手について、最善手と比べた損失を返す
+    pub fn loss(analysis: &[(Move, i8)], pos: Coord) -> Option<i8> {
+        let best = analysis.first()?.1;
+        analysis.iter().find(|&(mov, _)| mov.pos == pos).map(|&(_, score)| best - score)
+    }
+
+    /// 打とうとしている手が、警告するほど損をするなら警告文を返す
+    pub fn warning(&self, analysis: &[(Move, i8)], pos: Coord) -> Option<String> {
+        let loss = Coach::loss(analysis, pos)?;
+        if loss <= self.threshold {
+            return None;
+        }
+        let best = analysis[0].0.pos;
+        Some(format!("{} loses {} discs compared with {}", pos, loss, best))
+    }
+
+    /// `ply` 手目に打った手を記録する
+    ///
+    /// 待ったで取り消された手の記録は、同じ手数以降の手を記録するときに捨てる。
+    pub fn record(&mut self, ply: usize, analysis: &[(Move, i8)], pos: Coord) {
+        self.mistakes.retain(|mistake| mistake.ply < ply);
+        let loss = match Coach::loss(analysis, pos) {
+            Some(loss) if loss > 0 => loss,
+            _ => return,
+        };
+        self.mistakes.push(Mistake { ply, played: pos, best: analysis[0].0.pos, loss });
+    }
+
+    /// 損失の大きかった手から順に返す
+    pub fn mistakes(&self) -> Vec<&Mistake> {
+        let mut mistakes: Vec<&Mistake> = self.mistakes.iter().collect();
+        mistakes.sort_by_key(|mistake| (-mistake.loss, mistake.ply));
+        mistakes
+    }
+
+    /// 対局後に見せる、損失の大きかった手の振り返り
+    pub fn report(&self) -> String {
+        let mistakes = self.mistakes();
+        if mistakes.is_empty() {
+            return "Coach: no mistakes. Well played!".to_string();
+        }
+        let mut report = "Coach: your biggest mistakes".to_string();
+        for mistake in mistakes.into_iter().take(REPORTED_MISTAKES) {
+            report.push_str(&format!("\n  {}", mistake));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(scores: &[(&str, i8)]) -> Vec<(Move, i8)> {
+        let board = Board::new();
+        scores.iter()
+            .map(|&(pos, score)| {
+                let pos: Coord = pos.parse().unwrap();
+                let mov = board.moves(Piece::Black).into_iter().find(|mov| mov.pos == pos).unwrap();
+                (mov, score)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coach_advice() {
+        let coach = Coach::new(DEPTH, 4);
+        let analysis = analysis(&[("d3", 3), ("c4", 0), ("f5", -2), ("e6", -6)]);
+        assert_eq!("d3 +3, c4 +0, f5 -2", Coach::advice(&analysis));
+        assert_eq!(Some(0), Coach::loss(&analysis, Coord(3, 2)));
+        assert_eq!(Some(9), Coach::loss(&analysis, Coord(4, 5)));
+        assert_eq!(None, Coach::loss(&analysis, Coord(0, 0)));
+        assert_eq!(None, coach.warning(&analysis, Coord(2, 3)));
+        assert_eq!(Some("e6 loses 9 discs compared with d3".to_string()), coach.warning(&analysis, Coord(4, 5)));
+    }
+
+    #[test]
+    fn test_coach_report() {
+        let mut coach = Coach::new(DEPTH, 4);
+        assert_eq!("Coach: no mistakes. Well played!", coach.report());
+        let analysis = analysis(&[("d3", 3), ("c4", 0), ("f5", -2), ("e6", -6)]);
+        coach.record(1, &analysis, Coord(3, 2));
+        coach.record(3, &analysis, Coord(2, 3));
+        coach.record(5, &analysis, Coord(4, 5));
+        coach.record(7, &analysis, Coord(5, 4));
+        assert_eq!(vec![5, 7, 3], coach.mistakes().iter().map(|mistake| mistake.ply).collect::<Vec<_>>());
+        assert_eq!(
+            "Coach: your biggest mistakes\n  5. e6 lost 9 discs (best: d3)\n  7. f5 lost 5 discs (best: d3)\n  3. c4 lost 3 discs (best: d3)",
+            coach.report(),
+        );
+        // 待ったで5手目から打ち直した場合、それ以降の記録は捨てられる
+        coach.record(5, &analysis, Coord(3, 2));
+        assert_eq!(vec![3], coach.mistakes().iter().map(|mistake| mistake.ply).collect::<Vec<_>>());
+    }
+}
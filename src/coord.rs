@@ -1,21 +1,41 @@
 use std::ops::{Add, AddAssign};
 use std::fmt;
+use std::str::FromStr;
 
 /// ベクトルを表現する構造体
-#[derive(Debug, Clone, Copy, PartialEq)]
+///
+/// 盤面の座標として使う場合は、左上の `a1` を `Coord(0, 0)` とした (列, 行) になる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Coord(pub i8, pub i8);
 
+impl Coord {
+    /// 左上の `a1` を 0、右下の `h8` を 63 とする `行 * 8 + 列` の番号から座標を作る。範囲外なら None
+    pub fn from_index(index: i32) -> Option<Coord> {
+        if (0..64).contains(&index) {
+            Some(Coord((index % 8) as i8, (index / 8) as i8))
+        } else {
+            None
+        }
+    }
+
+    /// `Coord::from_index` の番号を返す
+    pub fn index(&self) -> i32 {
+        self.1 as i32 * 8 + self.0 as i32
+    }
+}
+
 /// `+` 演算子のオーバーロード
 impl Add for Coord {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        unimplemented!();
+        Coord(self.0 + rhs.0, self.1 + rhs.1)
     }
 }
 /// `+=` 演算子のオーバーロード
 impl AddAssign for Coord {
     fn add_assign(&mut self, rhs: Self) {
-        unimplemented!();
+        self.0 += rhs.0;
+        self.1 += rhs.1;
     }
 }
 const X_AXIS: [char; 8] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
@@ -24,6 +44,33 @@ impl fmt::Display for Coord {
         write!(f, "{}{}", X_AXIS[self.0 as usize], self.1 + 1)
     }
 }
+/// `d3` のような表記を座標として読む
+///
+/// 列の文字は大文字でもよい。
+///
+/// ```
+/// use reversi::Coord;
+///
+/// assert_eq!(Ok(Coord(3, 2)), "D3".parse());
+/// assert_eq!("d3", Coord(3, 2).to_string());
+/// assert!("i9".parse::<Coord>().is_err());
+/// ```
+impl FromStr for Coord {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let (col, row) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(col), Some(row), None) => (col.to_ascii_lowercase(), row),
+            _ => return Err(format!("invalid coordinate: {:?}", s)),
+        };
+        let x = X_AXIS.iter().position(|&c| c == col);
+        let y = row.to_digit(10).filter(|y| (1..=8).contains(y));
+        match (x, y) {
+            (Some(x), Some(y)) => Ok(Coord(x as i8, y as i8 - 1)),
+            _ => Err(format!("invalid coordinate: {:?}", s)),
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -45,4 +92,26 @@ mod tests {
         let expected = Coord(3, 8);
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_coord_from_str() {
+        assert_eq!(Ok(Coord(3, 2)), "d3".parse());
+        assert_eq!(Ok(Coord(7, 7)), "H8".parse());
+        assert_eq!(Ok(Coord(0, 0)), "a1".parse());
+        assert!("i1".parse::<Coord>().is_err());
+        assert!("a9".parse::<Coord>().is_err());
+        assert!("a0".parse::<Coord>().is_err());
+        assert!("a10".parse::<Coord>().is_err());
+        assert!("".parse::<Coord>().is_err());
+    }
+
+    #[test]
+    fn test_coord_index() {
+        assert_eq!(Some(Coord(0, 0)), Coord::from_index(0));
+        assert_eq!(Some(Coord(3, 2)), Coord::from_index(19));
+        assert_eq!(Some(Coord(7, 7)), Coord::from_index(63));
+        assert_eq!(None, Coord::from_index(-1));
+        assert_eq!(None, Coord::from_index(64));
+        assert_eq!(19, Coord(3, 2).index());
+    }
 }
@@ -0,0 +1,441 @@
+//! C から使うための ABI
+//!
+//! 盤面・対局・エンジンは中身の見えないハンドルとして渡し、`*_new` で作って `*_free` で解放する。
+//! ヘッダーはビルド時に build.rs が cbindgen で `OUT_DIR` に生成する。リポジトリの `include/reversi.h` は、
+//! 環境変数 `REVERSI_UPDATE_HEADER` を設定してビルドしたときだけその写しで書き換わる。
+//!
+//! マスは左上の `a1` を 0、右下の `h8` を 63 とする `行 * 8 + 列` の番号で表し、パスは `REVERSI_PASS` とする。
+//! 文字列を返す関数は `snprintf` と同じく、バッファに収まる分だけ NUL 終端で書き込み、書きたかった文字数を返す。
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use piece::Piece;
+use coord::Coord;
+use board::Board;
+use clock::TimeControl;
+use game::{Game, Manual, Play};
+use players::{self, PlayerKind};
+
+/// パスを表すマスの番号
+pub const REVERSI_PASS: i32 = -1;
+
+/// マスの状態と手番
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReversiPiece {
+    Empty = 0,
+    Black = 1,
+    White = 2,
+}
+
+/// 対局の状態
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReversiStatus {
+    Playing = 0,
+    BlackWon = 1,
+    WhiteWon = 2,
+    Draw = 3,
+}
+
+/// エンジンが最後に行った探索の結果
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReversiSearchInfo {
+    /// 探索を終えた深さ。深さを報告しないエンジンでは 0
+    pub depth: u32,
+    /// 手番から見た評価値（石の差）
+    pub score: i32,
+    /// 選んだ手
+    pub best: i32,
+    /// 探索にかかった時間
+    pub elapsed_ms: u64,
+}
+
+/// 盤面のハンドル
+pub struct ReversiBoard(Board);
+
+/// 外部から手を与えて進める対局のハンドル
+pub struct ReversiGame(Game<Manual, Manual>);
+
+/// コンピュータプレイヤーのハンドル
+///
+/// 探索中に別のスレッドから `reversi_engine_stop` を呼べるよう、状態はすべてロックの内側に持つ。
+pub struct ReversiEngine {
+    player: Mutex<Box<dyn Play + Send>>,
+    stop: Arc<AtomicBool>,
+    info: Mutex<Option<ReversiSearchInfo>>,
+}
+
+fn to_piece(piece: ReversiPiece) -> Option<Piece> {
+    match piece {
+        ReversiPiece::Empty => None,
+        ReversiPiece::Black => Some(Piece::Black),
+        ReversiPiece::White => Some(Piece::White),
+    }
+}
+
+fn from_piece(piece: Option<Piece>) -> ReversiPiece {
+    match piece {
+        None => ReversiPiece::Empty,
+        Some(Piece::Black) => ReversiPiece::Black,
+        Some(Piece::White) => ReversiPiece::White,
+    }
+}
+
+fn from_coord(pos: Option<Coord>) -> i32 {
+    pos.map_or(REVERSI_PASS, |pos| pos.index())
+}
+
+/// `len` 個まで書き込み、合法手の数を返す
+unsafe fn write_squares(squares: &[i32], out: *mut i32, len: usize) -> usize {
+    if !out.is_null() {
+        let out = slice::from_raw_parts_mut(out, len);
+        for (dst, &src) in out.iter_mut().zip(squares) {
+            *dst = src;
+        }
+    }
+    squares.len()
+}
+
+/// `snprintf` のように NUL 終端で書き込み、`s` の長さを返す
+unsafe fn write_string(s: &str, buf: *mut c_char, len: usize) -> usize {
+    if !buf.is_null() && len > 0 {
+        let n = s.len().min(len - 1);
+        ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, n);
+        *buf.add(n) = 0;
+    }
+    s.len()
+}
+
+unsafe fn read_string<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn squares(board: &Board, piece: Piece) -> Vec<i32> {
+    board.moves(piece).into_iter().map(|mov| from_coord(Some(mov.pos))).collect()
+}
+
+/// 初期局面の盤面を作る
+#[no_mangle]
+pub extern "C" fn reversi_board_new() -> Box<ReversiBoard> {
+    Box::new(ReversiBoard(Board::new()))
+}
+
+/// `Board::to_position` の形式の文字列から盤面を作る。読めなければ NULL を返す
+///
+/// `turn` が NULL でなければ手番を書き込む。
+///
+/// # Safety
+///
+/// `position` は NUL 終端の文字列、`turn` は NULL か書き込める領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_board_from_position(position: *const c_char, turn: *mut ReversiPiece) -> Option<Box<ReversiBoard>> {
+    let (board, piece) = Board::from_position(read_string(position)?).ok()?;
+    if !turn.is_null() {
+        *turn = from_piece(Some(piece));
+    }
+    Some(Box::new(ReversiBoard(board)))
+}
+
+/// 盤面を複製する
+#[no_mangle]
+pub extern "C" fn reversi_board_clone(board: &ReversiBoard) -> Box<ReversiBoard> {
+    Box::new(ReversiBoard(board.0.clone()))
+}
+
+/// 盤面を解放する。NULL の場合は何もしない
+#[no_mangle]
+pub extern "C" fn reversi_board_free(board: Option<Box<ReversiBoard>>) {
+    drop(board);
+}
+
+/// マスの状態を返す。範囲外のマスは空きとする
+#[no_mangle]
+pub extern "C" fn reversi_board_get(board: &ReversiBoard, square: i32) -> ReversiPiece {
+    match Coord::from_index(square) {
+        Some(pos) => from_piece(board.0.matrix()[pos]),
+        None => ReversiPiece::Empty,
+    }
+}
+
+/// 指定の色の石の数を返す
+#[no_mangle]
+pub extern "C" fn reversi_board_count(board: &ReversiBoard, piece: ReversiPiece) -> u32 {
+    match to_piece(piece) {
+        Some(Piece::Black) => board.0.black as u32,
+        Some(Piece::White) => board.0.white as u32,
+        None => board.0.empties() as u32,
+    }
+}
+
+/// 指定の色の合法手を `out` に `len` 個まで書き込み、合法手の数を返す
+///
+/// # Safety
+///
+/// `out` は NULL か、`len` 個の `int32_t` を書き込める領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_board_moves(board: &ReversiBoard, piece: ReversiPiece, out: *mut i32, len: usize) -> usize {
+    match to_piece(piece) {
+        Some(piece) => write_squares(&squares(&board.0, piece), out, len),
+        None => 0,
+    }
+}
+
+/// 指定の色の手を打つ。合法手でなければ盤面を変えずに false を返す
+#[no_mangle]
+pub extern "C" fn reversi_board_do_move(board: &mut ReversiBoard, piece: ReversiPiece, square: i32) -> bool {
+    let (piece, pos) = match (to_piece(piece), Coord::from_index(square)) {
+        (Some(piece), Some(pos)) => (piece, pos),
+        _ => return false,
+    };
+    match board.0.moves(piece).into_iter().find(|mov| mov.pos == pos) {
+        Some(mov) => {
+            board.0.do_move(piece, &mov);
+            true
+        },
+        None => false,
+    }
+}
+
+/// 盤面と手番を `Board::to_position` の形式で書き込む
+///
+/// # Safety
+///
+/// `buf` は NULL か、`len` バイトを書き込める領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_board_to_position(board: &ReversiBoard, turn: ReversiPiece, buf: *mut c_char, len: usize) -> usize {
+    let turn = to_piece(turn).unwrap_or(Piece::Black);
+    write_string(&board.0.to_position(turn), buf, len)
+}
+
+/// 初期局面から黒の手番で始まる対局を作る
+#[no_mangle]
+pub extern "C" fn reversi_game_new() -> Box<ReversiGame> {
+    Box::new(ReversiGame(Game::new(Manual, Manual)))
+}
+
+/// `Board::to_position` の形式の局面から始まる対局を作る。局面が不正なら NULL を返す
+///
+/// # Safety
+///
+/// `position` は NUL 終端の文字列を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_game_from_position(position: *const c_char) -> Option<Box<ReversiGame>> {
+    let (board, turn) = Board::from_position(read_string(position)?).ok()?;
+    let game = Game::from_position(board, turn, Manual, Manual).ok()?;
+    Some(Box::new(ReversiGame(game)))
+}
+
+/// 対局を解放する。NULL の場合は何もしない
+#[no_mangle]
+pub extern "C" fn reversi_game_free(game: Option<Box<ReversiGame>>) {
+    drop(game);
+}
+
+/// 現在の盤面の複製を返す。不要になったら `reversi_board_free` で解放する
+#[no_mangle]
+pub extern "C" fn reversi_game_board(game: &ReversiGame) -> Box<ReversiBoard> {
+    Box::new(ReversiBoard(game.0.board().clone()))
+}
+
+/// 手番を返す。終局していれば空きを返す
+#[no_mangle]
+pub extern "C" fn reversi_game_turn(game: &ReversiGame) -> ReversiPiece {
+    match game.0.outcome() {
+        Some(_) => ReversiPiece::Empty,
+        None => from_piece(Some(game.0.turn())),
+    }
+}
+
+/// 手番の合法手を `out` に `len` 個まで書き込み、合法手の数を返す。終局していれば 0
+///
+/// # Safety
+///
+/// `out` は NULL か、`len` 個の `int32_t` を書き込める領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_game_moves(game: &ReversiGame, out: *mut i32, len: usize) -> usize {
+    if game.0.outcome().is_some() {
+        return 0;
+    }
+    write_squares(&squares(game.0.board(), game.0.turn()), out, len)
+}
+
+/// 手番の手を打つ。`REVERSI_PASS` は打てる手がないときのみ受け付ける
+///
+/// 不正な手や終局後の手は、対局を変えずに false を返す。
+#[no_mangle]
+pub extern "C" fn reversi_game_play(game: &mut ReversiGame, square: i32) -> bool {
+    if square == REVERSI_PASS {
+        return game.0.apply(None).is_ok();
+    }
+    match Coord::from_index(square) {
+        Some(pos) => game.0.apply_at(pos).is_ok(),
+        None => false,
+    }
+}
+
+/// 最後の1手を取り消す。取り消す手がなければ false を返す
+#[no_mangle]
+pub extern "C" fn reversi_game_undo(game: &mut ReversiGame) -> bool {
+    game.0.undo().is_some()
+}
+
+/// 対局の状態を返す
+#[no_mangle]
+pub extern "C" fn reversi_game_status(game: &ReversiGame) -> ReversiStatus {
+    match game.0.outcome().map(|outcome| outcome.winner()) {
+        None => ReversiStatus::Playing,
+        Some(Some(Piece::Black)) => ReversiStatus::BlackWon,
+        Some(Some(Piece::White)) => ReversiStatus::WhiteWon,
+        Some(None) => ReversiStatus::Draw,
+    }
+}
+
+/// これまでの手を `f5d6c3` のような棋譜として書き込む
+///
+/// # Safety
+///
+/// `buf` は NULL か、`len` バイトを書き込める領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_game_transcript(game: &ReversiGame, buf: *mut c_char, len: usize) -> usize {
+    write_string(&game.0.transcript(), buf, len)
+}
+
+/// `alphabeta:7` のような `PlayerKind` の表記からエンジンを作る
+///
+/// 読めない場合と、人間のプレイヤーを指定した場合は NULL を返す。
+///
+/// # Safety
+///
+/// `kind` は NUL 終端の文字列を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn reversi_engine_new(kind: *const c_char) -> Option<Box<ReversiEngine>> {
+    let kind: PlayerKind = read_string(kind)?.parse().ok()?;
+    if kind == PlayerKind::Human {
+        return None;
+    }
+    Some(Box::new(ReversiEngine {
+        player: Mutex::new(kind.build(0)),
+        stop: Arc::new(AtomicBool::new(false)),
+        info: Mutex::new(None),
+    }))
+}
+
+/// エンジンを解放する。NULL の場合は何もしない
+#[no_mangle]
+pub extern "C" fn reversi_engine_free(engine: Option<Box<ReversiEngine>>) {
+    drop(engine);
+}
+
+/// 対局の手番の手を考え、そのマスを返す。打てる手がないか終局していれば `REVERSI_PASS` を返す
+///
+/// `time_ms` が 0 ならエンジンの深さまで探索し、それ以外はおよそその時間で探索を打ち切る。
+/// 手は打たないので、対局を進めるには返ったマスを `reversi_game_play` に渡す。
+#[no_mangle]
+pub extern "C" fn reversi_engine_best_move(engine: &ReversiEngine, game: &ReversiGame, time_ms: u32) -> i32 {
+    let game = &game.0;
+    if game.outcome().is_some() {
+        return REVERSI_PASS;
+    }
+    let time = match time_ms {
+        0 => None,
+        ms => Some(Duration::from_millis(ms as u64)),
+    };
+    engine.stop.store(false, Ordering::SeqCst);
+    let started = Instant::now();
+    let mut player = engine.player.lock().unwrap();
+    let (mov, last) = players::search(&mut *player, game.turn(), game.board(), game.history(), time.map(TimeControl::Byoyomi), &engine.stop, None);
+    let best = from_coord(mov.map(|mov| mov.pos));
+    *engine.info.lock().unwrap() = Some(ReversiSearchInfo {
+        depth: last.as_ref().map_or(0, |info| info.depth as u32),
+        score: last.as_ref().map_or(0, |info| info.score as i32),
+        best,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    });
+    best
+}
+
+/// 別のスレッドから、探索中の `reversi_engine_best_move` をすぐに戻らせる
+#[no_mangle]
+pub extern "C" fn reversi_engine_stop(engine: &ReversiEngine) {
+    engine.stop.store(true, Ordering::SeqCst);
+}
+
+/// 最後の探索の結果を `info` に書き込む。まだ探索していなければ false を返す
+#[no_mangle]
+pub extern "C" fn reversi_engine_search_info(engine: &ReversiEngine, info: &mut ReversiSearchInfo) -> bool {
+    match *engine.info.lock().unwrap() {
+        Some(last) => {
+            *info = last;
+            true
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use super::*;
+
+    #[test]
+    fn test_ffi_board() {
+        let mut board = reversi_board_new();
+        let mut moves = [0; 64];
+        let count = unsafe { reversi_board_moves(&board, ReversiPiece::Black, moves.as_mut_ptr(), moves.len()) };
+        assert_eq!(&[19, 26, 37, 44], &moves[..count]);
+        assert!(!reversi_board_do_move(&mut board, ReversiPiece::Black, 0));
+        assert!(reversi_board_do_move(&mut board, ReversiPiece::Black, 19));
+        assert_eq!(ReversiPiece::Black, reversi_board_get(&board, 27));
+        assert_eq!(4, reversi_board_count(&board, ReversiPiece::Black));
+        let mut buf = [0 as c_char; 8];
+        let len = unsafe { reversi_board_to_position(&board, ReversiPiece::White, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(66, len);
+        assert_eq!(b"-------", unsafe { CStr::from_ptr(buf.as_ptr()) }.to_bytes());
+    }
+
+    #[test]
+    fn test_ffi_engine() {
+        let kind = CString::new("alphabeta:3").unwrap();
+        let engine = unsafe { reversi_engine_new(kind.as_ptr()) }.unwrap();
+        let mut game = reversi_game_new();
+        let mut info = ReversiSearchInfo { depth: 0, score: 0, best: 0, elapsed_ms: 0 };
+        assert!(!reversi_engine_search_info(&engine, &mut info));
+        let best = reversi_engine_best_move(&engine, &game, 0);
+        assert!(reversi_engine_search_info(&engine, &mut info));
+        assert_eq!((3, best), (info.depth, info.best));
+        assert!(reversi_game_play(&mut game, best));
+        assert_eq!(ReversiPiece::White, reversi_game_turn(&game));
+        let human = CString::new("human").unwrap();
+        assert!(unsafe { reversi_engine_new(human.as_ptr()) }.is_none());
+    }
+
+    #[test]
+    fn test_ffi_engine_budget() {
+        // 深さ 20 の negamax は、時間の予算か中断がなければ戻らない
+        let kind = CString::new("negamax:20").unwrap();
+        let engine = unsafe { reversi_engine_new(kind.as_ptr()) }.unwrap();
+        let game = reversi_game_new();
+        let started = Instant::now();
+        assert!((0..64).contains(&reversi_engine_best_move(&engine, &game, 100)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        ::std::thread::scope(|scope| {
+            let thinking = scope.spawn(|| reversi_engine_best_move(&engine, &game, 0));
+            // 探索を始めるときに中断のフラグが戻されるので、戻るまで繰り返し止める
+            while !thinking.is_finished() {
+                ::std::thread::sleep(Duration::from_millis(50));
+                reversi_engine_stop(&engine);
+            }
+            assert!((0..64).contains(&thinking.join().unwrap()));
+        });
+    }
+}
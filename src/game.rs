@@ -1,12 +1,197 @@
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use piece::Piece;
 use board::{Board, Move};
+use coord::Coord;
+use clock::{Clock, Instant, TimeControl};
+
+/// 対局の結果を表す列挙型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// 双方とも打てる手がなくなって終局した
+    Finished { black: u8, white: u8 },
+    /// 指定の色が時間切れで負けた
+    Timeout(Piece),
+    /// 指定の色が反則により負けた
+    Forfeit(Piece, MoveError),
+    /// 指定の色が対局を放棄して負けた
+    Resigned(Piece, Resignation),
+}
+impl Outcome {
+    /// 勝った色を返す。引き分けの場合は None
+    pub fn winner(&self) -> Option<Piece> {
+        match *self {
+            Outcome::Finished { black, white } if black > white => Some(Piece::Black),
+            Outcome::Finished { black, white } if black < white => Some(Piece::White),
+            Outcome::Finished { .. } => None,
+            Outcome::Timeout(loser) | Outcome::Forfeit(loser, _) | Outcome::Resigned(loser, _) => {
+                Some(loser.opponent())
+            },
+        }
+    }
+}
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Finished { black, white } => match self.winner() {
+                Some(winner) => write!(f, "{:?} wins (B {} - {} W)", winner, black, white),
+                None => write!(f, "Draw (B {} - {} W)", black, white),
+            },
+            Outcome::Timeout(loser) => write!(f, "{:?} wins on time", loser.opponent()),
+            Outcome::Forfeit(loser, err) => write!(f, "{:?} wins by forfeit ({})", loser.opponent(), err),
+            Outcome::Resigned(loser, reason) => write!(f, "{:?} wins ({:?} {})", loser.opponent(), loser, reason),
+        }
+    }
+}
+
+/// 対局者が対局を放棄した理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resignation {
+    /// 自分から投了した
+    Resigned,
+    /// 接続が切れた
+    Disconnected,
+    /// 決められた時間内に応答がなかった
+    NoResponse,
+    /// 対局をやめてプログラムを終了する
+    Quit,
+}
+impl fmt::Display for Resignation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Resignation::Resigned => write!(f, "resigned"),
+            Resignation::Disconnected => write!(f, "disconnected"),
+            Resignation::NoResponse => write!(f, "stopped responding"),
+            Resignation::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+/// 不正な手を表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// 合法手でない手を打とうとした
+    IllegalMove(Piece, Coord),
+    /// 合法手があるのにパスしようとした
+    IllegalPass(Piece),
+    /// 対局はすでに終わっている
+    GameOver,
+}
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveError::IllegalMove(piece, pos) => write!(f, "{:?} cannot play {}", piece, pos),
+            MoveError::IllegalPass(piece) => write!(f, "{:?} cannot pass while having legal moves", piece),
+            MoveError::GameOver => write!(f, "the game is already over"),
+        }
+    }
+}
+impl error::Error for MoveError {}
+
+/// 対局を始められない局面を表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    /// `Board` の石の数が盤面の石の数と一致しない
+    CountMismatch { piece: Piece, expected: u8, actual: u8 },
+    /// 中央の4マスに空きがある
+    EmptyCenter,
+    /// 双方とも打てる手がない
+    NoMovesLeft,
+}
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PositionError::CountMismatch { piece, expected, actual } => {
+                write!(f, "{:?} count is {} but the board has {} discs", piece, expected, actual)
+            },
+            PositionError::EmptyCenter => write!(f, "the four center squares must be occupied"),
+            PositionError::NoMovesLeft => write!(f, "neither side has a legal move"),
+        }
+    }
+}
+impl error::Error for PositionError {}
+
+/// プレイヤーが不正な手を返したときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMovePolicy {
+    /// 直ちに反則負けとする
+    Forfeit,
+    /// 指定の回数まで考え直させ、それでも不正なら反則負けとする
+    Retry(u32),
+}
+
+/// 対局中に打たれた1手の記録
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ply {
+    pub piece: Piece,
+    /// 打った手。パスの場合は None
+    pub mov: Option<Move>,
+}
+
+impl fmt::Display for Ply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mov {
+            Some(ref mov) => write!(f, "{:?} {}", self.piece, mov.pos),
+            None => write!(f, "{:?} pass", self.piece),
+        }
+    }
+}
+
+/// 対局を始めた局面と手番、そこから打たれた手の列
+///
+/// `Game` と違って対局者を持たないので、保存したり送ったりするのに使う。
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    pub start: (Board, Piece),
+    /// パスを含めた手。パスは None
+    pub moves: Vec<Option<Coord>>,
+}
+
+impl History {
+    /// 最初の局面から手を打ち直し、外部から手を与える対局として返す
+    ///
+    /// 局面が不正な場合や、不正な手が含まれている場合はエラーになる。
+    /// 終局の連続したパスは記録されないので、双方とも打てなければパスを補って終局させる。
+    pub fn replay(&self) -> Result<Game<Manual, Manual>, String> {
+        let (ref board, turn) = self.start;
+        let mut game = Game::from_position(board.clone(), turn, Manual, Manual)
+            .map_err(|err| format!("invalid position: {}", err))?;
+        for &mov in &self.moves {
+            match mov {
+                Some(pos) => game.apply_at(pos),
+                None => game.apply(None),
+            }.map_err(|err| err.to_string())?;
+        }
+        let board = game.board();
+        if board.moves(Piece::Black).is_empty() && board.moves(Piece::White).is_empty() {
+            while game.outcome().is_none() {
+                game.apply(None).map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(game)
+    }
+}
 
 pub struct Game<P1, P2> {
+    /// 対局を始めた局面と手番
+    start: (Board, Piece),
     board: Board,
     turn: Piece,
     black: P1,
     white: P2,
     is_passed: bool,
+    black_clock: Option<Clock>,
+    white_clock: Option<Clock>,
+    outcome: Option<Outcome>,
+    illegal_move_policy: IllegalMovePolicy,
+    is_pondering: bool,
+    history: Vec<Ply>,
+    stop: Arc<AtomicBool>,
+    is_started: bool,
+    search_listener: Option<Arc<SearchListener>>,
+    takebacks: bool,
 }
 
 impl<P1, P2> Game<P1, P2>
@@ -14,45 +199,894 @@ where
     P1: Play,
     P2: Play,
 {
+    /// 初期局面から黒の手番で始まる対局を作る
+    ///
+    /// ```
+    /// use reversi::{Game, Outcome};
+    /// use reversi::players::{AlphaBetaPlayer, DumbPlayer};
+    ///
+    /// let mut game = Game::new(AlphaBetaPlayer::new(3), DumbPlayer);
+    /// while game.step() {}
+    /// match game.outcome() {
+    ///     Some(Outcome::Finished { black, white }) => assert_eq!(game.board().black + game.board().white, black + white),
+    ///     outcome => panic!("unexpected outcome: {:?}", outcome),
+    /// }
+    /// ```
     pub fn new(black: P1, white: P2) -> Self {
-        let board = Board::new();
+        Game::with_board(Board::new(), Piece::Black, black, white)
+    }
+
+    /// 任意の局面から対局を始める
+    ///
+    /// 石の数が盤面と一致しない場合や、中央に空きがある場合、双方とも打てない場合はエラーになる。
+    /// 手番の側に打てる手がない場合は、最初の手番でパスすることになる。
+    pub fn from_position(board: Board, turn: Piece, black: P1, white: P2) -> Result<Self, PositionError> {
+        for &piece in &[Piece::Black, Piece::White] {
+            let expected = match piece {
+                Piece::Black => board.black,
+                Piece::White => board.white,
+            };
+            let actual = board.matrix().count(piece);
+            if expected != actual {
+                return Err(PositionError::CountMismatch { piece, expected, actual });
+            }
+        }
+        let center = [Coord(3, 3), Coord(4, 3), Coord(3, 4), Coord(4, 4)];
+        if center.iter().any(|&pos| board.matrix()[pos].is_none()) {
+            return Err(PositionError::EmptyCenter);
+        }
+        if board.moves(turn).is_empty() && board.moves(turn.opponent()).is_empty() {
+            return Err(PositionError::NoMovesLeft);
+        }
+        Ok(Game::with_board(board, turn, black, white))
+    }
+
+    fn with_board(board: Board, turn: Piece, black: P1, white: P2) -> Self {
         Game {
+            start: (board.clone(), turn),
             board,
-            turn: Piece::Black,
+            turn,
             black,
             white,
             is_passed: false,
+            black_clock: None,
+            white_clock: None,
+            outcome: None,
+            illegal_move_policy: IllegalMovePolicy::Forfeit,
+            is_pondering: false,
+            history: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            is_started: false,
+            search_listener: None,
+            takebacks: false,
+        }
+    }
+
+    /// 両対局者の時計を指定の持ち時間でセットする
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.black_clock = Some(Clock::new(control));
+        self.white_clock = Some(Clock::new(control));
+    }
+
+    /// プレイヤーが不正な手を返したときの扱いを設定する
+    pub fn set_illegal_move_policy(&mut self, policy: IllegalMovePolicy) {
+        self.illegal_move_policy = policy;
+    }
+
+    /// 相手の手番の間もプレイヤーに先読みさせるかどうかを設定する
+    pub fn set_pondering(&mut self, is_pondering: bool) {
+        self.is_pondering = is_pondering;
+    }
+
+    /// 思考中のプレイヤーが報告する探索の途中経過を受け取る関数を設定する
+    ///
+    /// 関数はプレイヤーが考えているスレッドから呼ばれる。
+    pub fn set_search_listener(&mut self, listener: Arc<SearchListener>) {
+        self.search_listener = Some(listener);
+    }
+
+    /// プレイヤーに待ったを許すかどうかを設定する
+    ///
+    /// 許した場合、`Play::takeback` で求められると自分の最後の手まで取り消す。
+    pub fn set_takebacks(&mut self, takebacks: bool) {
+        self.takebacks = takebacks;
+    }
+
+    /// 指定の色の時計を返す。持ち時間のない対局では None
+    pub fn clock(&self, piece: Piece) -> Option<&Clock> {
+        match piece {
+            Piece::Black => self.black_clock.as_ref(),
+            Piece::White => self.white_clock.as_ref(),
         }
     }
 
+    /// 対局の結果を返す。対局中は None
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// 対局を始めた局面と、そのときの手番を返す
+    pub fn start(&self) -> (&Board, Piece) {
+        (&self.start.0, self.start.1)
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn turn(&self) -> Piece {
+        self.turn
+    }
+
+    /// これまでに打たれた手を古い順に返す
+    pub fn history(&self) -> &[Ply] {
+        &self.history
+    }
+
+    /// 最初の局面とこれまでの手を、対局者を除いて返す
+    pub fn to_history(&self) -> History {
+        History {
+            start: self.start.clone(),
+            moves: self.history.iter().map(|ply| ply.mov.as_ref().map(|mov| mov.pos)).collect(),
+        }
+    }
+
+    /// 思考中のプレイヤーに探索の中断を求めるためのフラグを返す
+    ///
+    /// 別のスレッドから `true` をセットすると、対応しているプレイヤーはすぐに手を返す。
+    /// フラグは手番が変わるたびに `false` に戻される。
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// 対局を終わらせ、両プレイヤーに結果を知らせる
+    fn finish(&mut self, outcome: Outcome) {
+        self.outcome = Some(outcome);
+        self.black.stop_pondering();
+        self.white.stop_pondering();
+        self.black.game_over(&outcome);
+        self.white.game_over(&outcome);
+    }
+
+    /// 手番のプレイヤーに1手を打たせる。対局が続くなら true を返す
     pub fn step(&mut self) -> bool {
-        let mov = match self.turn {
-            Piece::Black => self.black.play(self.turn, &self.board),
-            Piece::White => self.white.play(self.turn, &self.board),
+        if self.outcome.is_some() {
+            return false;
+        }
+        if !self.is_started {
+            self.black.new_game(Piece::Black, &self.board);
+            self.white.new_game(Piece::White, &self.board);
+            self.is_started = true;
+        }
+        self.stop.store(false, Ordering::SeqCst);
+        let started = Instant::now();
+        let mut retries = 0;
+        let result = loop {
+            let clock = match self.turn {
+                Piece::Black => self.black_clock.as_ref(),
+                Piece::White => self.white_clock.as_ref(),
+            };
+            let ctx = Context {
+                clock,
+                history: &self.history,
+                stop: &self.stop,
+                listener: self.search_listener.as_deref(),
+                takebacks: self.takebacks,
+            };
+            let mov = match self.turn {
+                Piece::Black => self.black.think(self.turn, &self.board, &ctx),
+                Piece::White => self.white.think(self.turn, &self.board, &ctx),
+            };
+            let resignation = match self.turn {
+                Piece::Black => self.black.resignation(),
+                Piece::White => self.white.resignation(),
+            };
+            if let Some(reason) = resignation {
+                let turn = self.turn;
+                self.finish(Outcome::Resigned(turn, reason));
+                return false;
+            }
+            let takeback = match self.turn {
+                Piece::Black => self.black.takeback(),
+                Piece::White => self.white.takeback(),
+            };
+            if takeback && self.takebacks {
+                self.take_back();
+                return true;
+            }
+            let result = self.validate(&mov).map(|_| mov);
+            if let Err(ref err) = result {
+                match self.turn {
+                    Piece::Black => self.black.move_rejected(err),
+                    Piece::White => self.white.move_rejected(err),
+                }
+                if let IllegalMovePolicy::Retry(max) = self.illegal_move_policy {
+                    if retries < max {
+                        retries += 1;
+                        continue;
+                    }
+                }
+            }
+            break result;
+        };
+        let clock = match self.turn {
+            Piece::Black => self.black_clock.as_mut(),
+            Piece::White => self.white_clock.as_mut(),
         };
+        if let Some(clock) = clock {
+            if !clock.consume(started.elapsed()) {
+                let turn = self.turn;
+                self.finish(Outcome::Timeout(turn));
+                return false;
+            }
+        }
+        match result {
+            Ok(mov) => {
+                let turn = self.turn;
+                self.commit(mov);
+                if self.is_pondering && self.outcome.is_none() {
+                    match turn {
+                        Piece::Black => self.black.ponder(turn, &self.board),
+                        Piece::White => self.white.ponder(turn, &self.board),
+                    }
+                }
+            },
+            Err(err) => {
+                let turn = self.turn;
+                self.finish(Outcome::Forfeit(turn, err));
+            },
+        }
+        self.outcome.is_none()
+    }
+
+    /// 手番のプレイヤーの手として、指定の手を検証した上で打つ
+    ///
+    /// プレイヤーに考えさせずに外部から手を与えるときに使う。`None` はパスを表す。
+    pub fn apply(&mut self, mov: Option<Move>) -> Result<(), MoveError> {
+        self.validate(&mov)?;
+        self.commit(mov);
+        Ok(())
+    }
+
+    /// 座標で指定した手を、手番のプレイヤーの手として打つ
+    pub fn apply_at(&mut self, pos: Coord) -> Result<(), MoveError> {
+        if self.outcome.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        let mov = self.board.moves(self.turn).into_iter().find(|mov| mov.pos == pos);
         match mov {
-            Some(mov) => {
-                self.board.do_move(self.turn, &mov);
+            Some(mov) => self.apply(Some(mov)),
+            None => Err(MoveError::IllegalMove(self.turn, pos)),
+        }
+    }
+
+    /// 最後の1手を取り消し、取り消した手を返す
+    ///
+    /// 盤面は最初の局面から打ち直して求める。対局者には知らせず、時計も戻さない。
+    /// 取り消す手がない場合や、時間切れや反則で対局が終わっている場合は None を返す。
+    pub fn undo(&mut self) -> Option<Ply> {
+        match self.outcome {
+            // 連続パスで終わった対局では、最後のパスは履歴に残っていない
+            Some(Outcome::Finished { .. }) => {
+                self.outcome = None;
+                return Some(Ply { piece: self.turn, mov: None });
+            },
+            Some(_) => return None,
+            None => {},
+        }
+        let ply = self.history.pop()?;
+        let mut board = self.start.0.clone();
+        for ply in &self.history {
+            if let Some(ref mov) = ply.mov {
+                board.do_move(ply.piece, mov);
+            }
+        }
+        self.board = board;
+        self.turn = ply.piece;
+        self.is_passed = self.history.last().is_some_and(|ply| ply.mov.is_none());
+        Some(ply)
+    }
+
+    /// 手番のプレイヤーが最後に打った手まで取り消す。取り消せる手がなければ何もしない
+    ///
+    /// 相手の手と、その間のパスもまとめて取り消すので、手番は変わらない。
+    fn take_back(&mut self) {
+        let turn = self.turn;
+        if !self.history.iter().any(|ply| ply.piece == turn && ply.mov.is_some()) {
+            return;
+        }
+        self.black.stop_pondering();
+        self.white.stop_pondering();
+        while let Some(ply) = self.undo() {
+            if ply.piece == turn && ply.mov.is_some() {
+                break;
+            }
+        }
+    }
+
+    /// パスを省略した棋譜を、必要なパスを補いながら打っていく
+    ///
+    /// ```
+    /// use reversi::Game;
+    /// use reversi::game::{parse_transcript, Manual};
+    ///
+    /// let mut game = Game::new(Manual, Manual);
+    /// game.replay(&parse_transcript("f5d6c3").unwrap()).unwrap();
+    /// assert_eq!("f5d6c3", game.transcript());
+    /// ```
+    pub fn replay(&mut self, moves: &[Coord]) -> Result<(), MoveError> {
+        for &pos in moves {
+            if self.outcome.is_none() && self.board.moves(self.turn).is_empty() {
+                self.apply(None)?;
+            }
+            self.apply_at(pos)?;
+        }
+        Ok(())
+    }
+
+    /// これまでの手を `f5d6c3` のようにパスを省略してつなげた棋譜を返す
+    pub fn transcript(&self) -> String {
+        self.history.iter()
+            .filter_map(|ply| ply.mov.as_ref())
+            .map(|mov| mov.pos.to_string())
+            .collect()
+    }
+
+    /// 指定の手が手番のプレイヤーにとって合法かどうかを検証する
+    fn validate(&self, mov: &Option<Move>) -> Result<(), MoveError> {
+        if self.outcome.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        let moves = self.board.moves(self.turn);
+        match *mov {
+            Some(ref mov) if !moves.contains(mov) => Err(MoveError::IllegalMove(self.turn, mov.pos)),
+            None if !moves.is_empty() => Err(MoveError::IllegalPass(self.turn)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 検証済みの手を打ち、手番を進める
+    fn commit(&mut self, mov: Option<Move>) {
+        match mov {
+            Some(ref mov) => {
+                self.board.do_move(self.turn, mov);
                 self.is_passed = false;
             },
             None => {
                 if self.is_passed {
                     // game is over
-                    return false;
+                    let (black, white) = (self.board.black, self.board.white);
+                    self.finish(Outcome::Finished { black, white });
+                    return;
                 }
                 self.is_passed = true;
             }
         }
+        let ply = Ply { piece: self.turn, mov };
+        match self.turn {
+            Piece::Black => {
+                self.white.stop_pondering();
+                self.white.opponent_moved(&ply, &self.board);
+            },
+            Piece::White => {
+                self.black.stop_pondering();
+                self.black.opponent_moved(&ply, &self.board);
+            },
+        }
+        self.history.push(ply);
         self.turn = self.turn.opponent();
-        true
     }
 
     pub fn print(&self) {
         println!("{}", self.board);
+        if let (Some(black), Some(white)) = (&self.black_clock, &self.white_clock) {
+            println!("Time: B {} - {} W", black, white);
+        }
         println!("Turn: {:?}", self.turn);
     }
 }
 
+/// `f5d6c3` のような棋譜を座標の列として読む
+pub fn parse_transcript(s: &str) -> Result<Vec<Coord>, String> {
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return Err(format!("invalid transcript: {:?}", s));
+    }
+    (0..s.len()).step_by(2).map(|idx| s[idx..idx + 2].parse()).collect()
+}
+
+/// 探索するプレイヤーが報告する途中経過
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub piece: Piece,
+    /// 探索を終えた深さ
+    pub depth: usize,
+    /// `piece` から見た評価値（石の差）
+    pub score: i8,
+    /// その深さでの最善手
+    pub best: Option<Coord>,
+    /// 探索を始めてから訪れた局面の数。反復深化の浅い探索の分も含む
+    pub nodes: u64,
+}
+
+/// 探索の途中経過を受け取る関数
+pub type SearchListener = dyn Fn(&SearchInfo) + Send + Sync;
+
+/// プレイヤーが手を考える際に参照できる対局の状況
+pub struct Context<'a> {
+    /// 自分の時計。持ち時間のない対局では None
+    pub clock: Option<&'a Clock>,
+    /// これまでに打たれた手
+    pub history: &'a [Ply],
+    /// 探索の中断を求められているかどうかのフラグ
+    pub stop: &'a Arc<AtomicBool>,
+    /// 探索の途中経過の報告先。報告を必要としない場合は None
+    pub listener: Option<&'a SearchListener>,
+    /// 待ったが許されているかどうか
+    pub takebacks: bool,
+}
+impl<'a> Context<'a> {
+    /// 直前に相手が打った手を返す。初手の場合は None
+    pub fn last_move(&self) -> Option<&'a Ply> {
+        self.history.last()
+    }
+
+    /// 探索の中断を求められているかどうかを返す
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    /// 探索の途中経過を報告する
+    pub fn report(&self, info: &SearchInfo) {
+        if let Some(listener) = self.listener {
+            listener(info);
+        }
+    }
+}
+
+/// 対局者が実装するトレイト
+///
+/// 最低限 `play` を実装すれば `Game` で対局させられる。
+///
+/// ```
+/// use reversi::{Board, Game, Move, Piece, Play};
+///
+/// /// いちばん多くひっくり返せる手を打つ
+/// struct Greedy;
+/// impl Play for Greedy {
+///     fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+///         board.moves(piece).into_iter().max_by_key(|mov| {
+///             let mut board = board.clone();
+///             board.do_move(piece, mov);
+///             board.black as i8 - board.white as i8
+///         })
+///     }
+/// }
+///
+/// let mut game = Game::new(Greedy, Greedy);
+/// while game.step() {}
+/// assert!(game.outcome().is_some());
+/// ```
 pub trait Play {
     fn play(&mut self, piece: Piece, board: &Board) -> Option<Move>;
+
+    /// 対局の状況を踏まえて手を考える
+    ///
+    /// 持ち時間などを気にしないプレイヤーは `play` だけを実装すればよい。
+    fn think(&mut self, piece: Piece, board: &Board, _ctx: &Context) -> Option<Move> {
+        self.play(piece, board)
+    }
+
+    /// 対局が始まるときに、自分の色と最初の盤面を受け取る
+    fn new_game(&mut self, _piece: Piece, _board: &Board) {}
+
+    /// 相手が手を打った（パスを含む）直後に、その手と打った後の盤面を受け取る
+    fn opponent_moved(&mut self, _ply: &Ply, _board: &Board) {}
+
+    /// 自分が手を打った直後に、相手の手番の間の先読みを始める
+    ///
+    /// 先読みはバックグラウンドで行い、すぐに戻らなければならない。
+    /// `board` は自分が打った後の盤面。
+    fn ponder(&mut self, _piece: Piece, _board: &Board) {}
+
+    /// 先読みを止める。相手が手を打ったときと対局が終わったときに呼ばれる
+    fn stop_pondering(&mut self) {}
+
+    /// 返した手が不正として拒否されたときに、その理由を受け取る
+    fn move_rejected(&mut self, _err: &MoveError) {}
+
+    /// 対局が終わったときに結果を受け取る
+    fn game_over(&mut self, _outcome: &Outcome) {}
+
+    /// `think` から戻った直後に呼ばれ、対局を放棄するならその理由を返す
+    ///
+    /// 投了したり接続が切れたりしたプレイヤーは、手の代わりにここで理由を返す。
+    fn resignation(&mut self) -> Option<Resignation> {
+        None
+    }
+
+    /// `think` から戻った直後に呼ばれ、待ったをして自分の最後の手を打ち直すなら true を返す
+    ///
+    /// 待ったが許されている (`Context::takebacks`) 場合だけ、`think` で返した手の代わりに扱われる。
+    fn takeback(&mut self) -> bool {
+        false
+    }
+}
+
+/// 手をすべて `Game::apply` などで外部から与える対局のための、自分では考えない対局者
+///
+/// `step` で手番が回ってきた場合はパスを返す。
+pub struct Manual;
+impl Play for Manual {
+    fn play(&mut self, _piece: Piece, _board: &Board) -> Option<Move> {
+        None
+    }
+}
+
+/// `Box<dyn Play>` のように、実行時に選んだプレイヤーを対局させるための実装
+impl<P: Play + ?Sized> Play for Box<P> {
+    fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+        (**self).play(piece, board)
+    }
+
+    fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+        (**self).think(piece, board, ctx)
+    }
+
+    fn new_game(&mut self, piece: Piece, board: &Board) {
+        (**self).new_game(piece, board)
+    }
+
+    fn opponent_moved(&mut self, ply: &Ply, board: &Board) {
+        (**self).opponent_moved(ply, board)
+    }
+
+    fn ponder(&mut self, piece: Piece, board: &Board) {
+        (**self).ponder(piece, board)
+    }
+
+    fn stop_pondering(&mut self) {
+        (**self).stop_pondering()
+    }
+
+    fn move_rejected(&mut self, err: &MoveError) {
+        (**self).move_rejected(err)
+    }
+
+    fn game_over(&mut self, outcome: &Outcome) {
+        (**self).game_over(outcome)
+    }
+
+    fn resignation(&mut self) -> Option<Resignation> {
+        (**self).resignation()
+    }
+
+    fn takeback(&mut self) -> bool {
+        (**self).takeback()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+    use players::AlphaBetaPlayer;
+    use super::*;
+
+    struct First;
+    impl Play for First {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            board.moves(piece).into_iter().next()
+        }
+    }
+
+    struct Slow(Duration);
+    impl Play for Slow {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            thread::sleep(self.0);
+            board.moves(piece).into_iter().next()
+        }
+    }
+
+    /// `illegal` の末尾から順に不正な手（true）か不正なパス（false）を返し、尽きたら合法手を返すプレイヤー
+    struct Cheater {
+        illegal: Vec<bool>,
+        rejected: Vec<MoveError>,
+    }
+    impl Play for Cheater {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            match self.illegal.pop() {
+                Some(true) => board.moves(piece.opponent()).into_iter().next(),
+                Some(false) => None,
+                None => board.moves(piece).into_iter().next(),
+            }
+        }
+
+        fn move_rejected(&mut self, err: &MoveError) {
+            self.rejected.push(*err);
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        piece: Option<Piece>,
+        last_moves: Vec<Option<Ply>>,
+        opponent_moves: usize,
+        outcome: Option<Outcome>,
+    }
+    impl Play for Recorder {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            board.moves(piece).into_iter().next()
+        }
+
+        fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+            self.last_moves.push(ctx.last_move().cloned());
+            self.play(piece, board)
+        }
+
+        fn new_game(&mut self, piece: Piece, _board: &Board) {
+            self.piece = Some(piece);
+        }
+
+        fn opponent_moved(&mut self, ply: &Ply, _board: &Board) {
+            assert_eq!(self.piece, Some(ply.piece.opponent()));
+            self.opponent_moves += 1;
+        }
+
+        fn game_over(&mut self, outcome: &Outcome) {
+            self.outcome = Some(*outcome);
+        }
+    }
+
+    #[test]
+    fn test_game_finishes() {
+        let mut game = Game::new(First, First);
+        while game.step() {}
+        let outcome = game.outcome().unwrap();
+        match outcome {
+            Outcome::Finished { black, white } => assert!(black + white <= 64),
+            _ => panic!("unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_game_timeout() {
+        let mut game = Game::new(First, Slow(Duration::from_millis(30)));
+        game.set_time_control(TimeControl::Byoyomi(Duration::from_millis(10)));
+        assert!(game.step());
+        assert!(!game.step());
+        assert_eq!(Some(Outcome::Timeout(Piece::White)), game.outcome());
+        assert_eq!(Some(Piece::Black), game.outcome().unwrap().winner());
+        assert!(game.clock(Piece::White).unwrap().is_flagged());
+        assert!(!game.step());
+    }
+
+    #[test]
+    fn test_game_engines_keep_time() {
+        let mut game = Game::new(AlphaBetaPlayer::new(7), AlphaBetaPlayer::new(7));
+        game.set_time_control(TimeControl::SuddenDeath(Duration::from_millis(500)));
+        while game.step() {}
+        match game.outcome().unwrap() {
+            Outcome::Finished { .. } => {},
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_game_notifies_players() {
+        let mut game = Game::new(Recorder::default(), Recorder::default());
+        assert!(game.step());
+        assert!(game.step());
+        assert_eq!(2, game.history().len());
+        assert_eq!(Some(Piece::Black), game.black.piece);
+        assert_eq!(Some(Piece::White), game.white.piece);
+        assert_eq!(None, game.black.last_moves[0]);
+        assert_eq!(Some(game.history()[0].clone()), game.white.last_moves[0]);
+        assert_eq!(1, game.black.opponent_moves);
+        assert_eq!(1, game.white.opponent_moves);
+        while game.step() {}
+        assert_eq!(game.outcome(), game.black.outcome);
+        assert_eq!(game.outcome(), game.white.outcome);
+    }
+
+    #[test]
+    fn test_game_forfeits_illegal_move() {
+        let mut game = Game::new(First, Cheater { illegal: vec![true], rejected: vec![] });
+        assert!(game.step());
+        assert!(!game.step());
+        let err = game.white.rejected[0];
+        match err {
+            MoveError::IllegalMove(Piece::White, _) => {},
+            err => panic!("unexpected error: {:?}", err),
+        }
+        assert_eq!(Some(Outcome::Forfeit(Piece::White, err)), game.outcome());
+        assert_eq!(1, game.history().len());
+    }
+
+    #[test]
+    fn test_game_forfeits_illegal_pass() {
+        let mut game = Game::new(Cheater { illegal: vec![false], rejected: vec![] }, First);
+        assert!(!game.step());
+        assert_eq!(vec![MoveError::IllegalPass(Piece::Black)], game.black.rejected);
+        assert_eq!(
+            Some(Outcome::Forfeit(Piece::Black, MoveError::IllegalPass(Piece::Black))),
+            game.outcome(),
+        );
+    }
+
+    #[test]
+    fn test_game_retries_illegal_moves() {
+        let mut game = Game::new(Cheater { illegal: vec![true, false], rejected: vec![] }, First);
+        game.set_illegal_move_policy(IllegalMovePolicy::Retry(2));
+        assert!(game.step());
+        assert_eq!(2, game.black.rejected.len());
+        assert_eq!(1, game.history().len());
+    }
+
+    struct Quitter;
+    impl Play for Quitter {
+        fn play(&mut self, _piece: Piece, _board: &Board) -> Option<Move> {
+            None
+        }
+
+        fn resignation(&mut self) -> Option<Resignation> {
+            Some(Resignation::Disconnected)
+        }
+    }
+
+    #[test]
+    fn test_game_resignation() {
+        let mut game = Game::new(First, Quitter);
+        assert!(game.step());
+        assert!(!game.step());
+        let outcome = game.outcome().unwrap();
+        assert_eq!(Outcome::Resigned(Piece::White, Resignation::Disconnected), outcome);
+        assert_eq!(Some(Piece::Black), outcome.winner());
+        assert_eq!("Black wins (White disconnected)", outcome.to_string());
+        assert_eq!(1, game.history().len());
+    }
+
+    /// 手番のたびに待ったを求める
+    struct Regretter;
+    impl Play for Regretter {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            board.moves(piece).into_iter().next()
+        }
+
+        fn takeback(&mut self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_game_takeback() {
+        let mut game = Game::new(Regretter, First);
+        // 待ったが許されていなければ、返した手がそのまま打たれる
+        assert!(game.step());
+        assert!(game.step());
+        assert_eq!(2, game.history().len());
+        game.set_takebacks(true);
+        assert!(game.step());
+        assert_eq!(0, game.history().len());
+        assert_eq!(Piece::Black, game.turn());
+        assert_eq!(&Board::new(), game.board());
+        // 取り消せる手がなければ何も起きない
+        assert!(game.step());
+        assert_eq!(0, game.history().len());
+    }
+
+    #[test]
+    fn test_game_apply() {
+        let mut game = Game::new(First, First);
+        let mov = game.board().moves(Piece::Black)[0].clone();
+        assert_eq!(Err(MoveError::IllegalPass(Piece::Black)), game.apply(None));
+        assert_eq!(Ok(()), game.apply(Some(mov.clone())));
+        assert_eq!(Piece::White, game.turn());
+        assert_eq!(Err(MoveError::IllegalMove(Piece::White, mov.pos)), game.apply(Some(mov)));
+    }
+
+    #[test]
+    fn test_game_with_pondering() {
+        let mut game = Game::new(AlphaBetaPlayer::new(3), First);
+        game.set_pondering(true);
+        while game.step() {}
+        assert!(game.outcome().is_some());
+    }
+
+    #[test]
+    fn test_game_replay() {
+        let mut game = Game::new(First, First);
+        let moves = parse_transcript("f5d6 c3d3").unwrap();
+        assert_eq!(Ok(()), game.replay(&moves));
+        assert_eq!("f5d6c3d3", game.transcript());
+        assert_eq!(Piece::Black, game.turn());
+        assert_eq!(
+            Err(MoveError::IllegalMove(Piece::Black, Coord(0, 0))),
+            game.replay(&[Coord(0, 0)]),
+        );
+        assert!(parse_transcript("f5d").is_err());
+        assert!(parse_transcript("f5z9").is_err());
+    }
+
+    #[test]
+    fn test_game_replay_with_passes() {
+        // 黒が9手で白を全滅させる最短の棋譜
+        let mut game = Game::new(First, First);
+        let moves = parse_transcript("d3c3b3d2e1d6d7e3f4").unwrap();
+        assert_eq!(Ok(()), game.replay(&moves));
+        assert_eq!(0, game.board().white);
+        assert!(game.step(), "white passes");
+        assert!(!game.step(), "black passes");
+        assert_eq!(Some(Outcome::Finished { black: 13, white: 0 }), game.outcome());
+    }
+
+    #[test]
+    fn test_game_undo() {
+        let mut game = Game::new(First, First);
+        assert!(game.undo().is_none());
+        let moves = parse_transcript("d3c3b3d2e1d6d7e3f4").unwrap();
+        game.replay(&moves).unwrap();
+        game.step();
+        game.step();
+        assert!(game.outcome().is_some());
+
+        let ply = game.undo().unwrap();
+        assert_eq!((Piece::Black, None), (ply.piece, ply.mov));
+        assert_eq!(None, game.outcome());
+        let ply = game.undo().unwrap();
+        assert_eq!((Piece::White, None), (ply.piece, ply.mov));
+        assert_eq!(Some(Coord(5, 3)), game.undo().and_then(|ply| ply.mov).map(|mov| mov.pos));
+        assert_eq!(Piece::Black, game.turn());
+        assert_eq!(8, game.history().len());
+
+        let mut expected = Game::new(First, First);
+        expected.replay(&moves[..8]).unwrap();
+        assert_eq!(expected.board(), game.board());
+        assert_eq!(Ok(()), game.apply_at(Coord(5, 3)));
+    }
+
+    #[test]
+    fn test_game_from_position() {
+        // 白の手番だが白は打てないので、最初の手番でパスになる
+        let position = "\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXXXXX\
+            XXXXX-OX O";
+        let (board, turn) = Board::from_position(position).unwrap();
+        let mut game = Game::from_position(board.clone(), turn, First, First).unwrap();
+        assert_eq!((&board, Piece::White), game.start());
+        assert!(game.step());
+        assert_eq!(Some(Ply { piece: Piece::White, mov: None }), game.history().first().cloned());
+        assert!(game.step());
+        assert_eq!(Piece::White, game.turn());
+        while game.step() {}
+        assert_eq!(Some(Outcome::Finished { black: 64, white: 0 }), game.outcome());
+    }
+
+    #[test]
+    fn test_game_from_invalid_position() {
+        let mut board = Board::new();
+        board.black = 3;
+        assert_eq!(
+            Some(PositionError::CountMismatch { piece: Piece::Black, expected: 3, actual: 2 }),
+            Game::from_position(board, Piece::Black, First, First).err(),
+        );
+        let empty = "-".repeat(64);
+        let board = Board::from_matrix(empty.parse().unwrap());
+        assert_eq!(
+            Some(PositionError::EmptyCenter),
+            Game::from_position(board, Piece::Black, First, First).err(),
+        );
+        let full = format!("{}{}", "X".repeat(32), "O".repeat(32));
+        let board = Board::from_matrix(full.parse().unwrap());
+        assert_eq!(
+            Some(PositionError::NoMovesLeft),
+            Game::from_position(board, Piece::Black, First, First).err(),
+        );
+    }
 }
@@ -0,0 +1,187 @@
+use std::fmt;
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+use game::{Game, History, Manual};
+
+/// GGF (Generic Game Format) で書かれたリバーシの棋譜
+///
+/// NBoard や対局サーバーとのやり取りに使う。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub black_name: String,
+    pub white_name: String,
+    /// 対局を始めた局面と手番
+    pub start: (Board, Piece),
+    /// 打たれた手。パスは None で、GGF では明示的に記録される
+    pub moves: Vec<Option<Coord>>,
+    /// `TI` の持ち時間の表記
+    pub time: Option<String>,
+    /// `RE` の結果の表記
+    pub result: Option<String>,
+}
+
+impl Record {
+    /// 初期局面から始める空の棋譜を作る
+    pub fn new(black_name: &str, white_name: &str) -> Self {
+        Record {
+            black_name: black_name.to_string(),
+            white_name: white_name.to_string(),
+            start: (Board::new(), Piece::Black),
+            moves: Vec::new(),
+            time: None,
+            result: None,
+        }
+    }
+
+    /// `(;GM[Othello]...;)` の形式の文字列を読む
+    ///
+    /// 手についている評価値や消費時間 (`B[F5/1.2/3.4]`) は読み捨てる。
+    pub fn parse(s: &str) -> Result<Record, String> {
+        let s = s.trim();
+        let body = match (s.strip_prefix("(;"), s.ends_with(";)")) {
+            (Some(body), true) => &body[..body.len() - 2],
+            _ => return Err("GGF must be enclosed in (; and ;)".to_string()),
+        };
+        let mut record = Record::new("", "");
+        let mut start = None;
+        let mut rest = body;
+        while !rest.trim().is_empty() {
+            let open = rest.find('[').ok_or_else(|| format!("missing value after {:?}", rest.trim()))?;
+            let close = open + rest[open..].find(']').ok_or("unterminated value")?;
+            let key = rest[..open].trim();
+            let value = &rest[open + 1..close];
+            rest = &rest[close + 1..];
+            match key {
+                "GM" if !value.eq_ignore_ascii_case("othello") => {
+                    return Err(format!("not an Othello game: {}", value));
+                },
+                "PB" => record.black_name = value.to_string(),
+                "PW" => record.white_name = value.to_string(),
+                "TI" => record.time = Some(value.to_string()),
+                "RE" => record.result = Some(value.to_string()),
+                "TY" if value.trim() != "8" => return Err(format!("unsupported board type: {}", value)),
+                "BO" => start = Some(parse_board(value)?),
+                "B" | "W" => record.moves.push(parse_move(value)?),
+                _ => {},
+            }
+        }
+        record.start = start.ok_or("missing BO")?;
+        Ok(record)
+    }
+
+    /// 最初の局面から手を打ち直し、外部から手を与える対局として返す
+    ///
+    /// 局面が不正な場合や、不正な手が含まれている場合はエラーになる。
+    pub fn game(&self) -> Result<Game<Manual, Manual>, String> {
+        History { start: self.start.clone(), moves: self.moves.clone() }.replay()
+    }
+}
+
+/// `BO[8 ---...--- *]` の値を読む
+fn parse_board(value: &str) -> Result<(Board, Piece), String> {
+    let value = value.trim();
+    match value.strip_prefix('8') {
+        Some(position) => Board::from_position(position),
+        None => Err(format!("unsupported board: {}", value)),
+    }
+}
+
+/// `F5/1.2/3.4` のような手の値を読む。パスは `PA`
+fn parse_move(value: &str) -> Result<Option<Coord>, String> {
+    let pos = value.split('/').next().unwrap_or("").trim();
+    if pos.eq_ignore_ascii_case("pa") {
+        Ok(None)
+    } else {
+        pos.parse().map(Some)
+    }
+}
+
+/// GGF では黒を `*` で表す
+fn to_ggf_position(board: &Board, turn: Piece) -> String {
+    board.to_position(turn).replace('X', "*")
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (ref board, turn) = self.start;
+        write!(f, "(;GM[Othello]PC[reversi]PB[{}]PW[{}]", self.black_name, self.white_name)?;
+        if let Some(ref result) = self.result {
+            write!(f, "RE[{}]", result)?;
+        }
+        if let Some(ref time) = self.time {
+            write!(f, "TI[{}]", time)?;
+        }
+        write!(f, "TY[8]BO[8 {}]", to_ggf_position(board, turn))?;
+        // パスも明示的に記録されているので、手番は交互になる
+        let mut piece = turn;
+        for mov in &self.moves {
+            let key = match piece {
+                Piece::Black => "B",
+                Piece::White => "W",
+            };
+            match *mov {
+                Some(pos) => write!(f, "{}[{}]", key, pos.to_string().to_uppercase())?,
+                None => write!(f, "{}[PA]", key)?,
+            }
+            piece = piece.opponent();
+        }
+        write!(f, ";)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "(;GM[Othello]PC[NBoard]DT[2014-02-21 20:52:27 GMT]PB[Human]PW[Edax]\
+        RE[?]TI[5:00]TY[8]\
+        BO[8 ---------------------------O*------*O--------------------------- *]\
+        B[F5//1.0]W[F6/-2.00/0.01]B[E6];)";
+
+    #[test]
+    fn test_ggf_parse() {
+        let record = Record::parse(SAMPLE).unwrap();
+        assert_eq!("Human", record.black_name);
+        assert_eq!("Edax", record.white_name);
+        assert_eq!(Some("5:00".to_string()), record.time);
+        assert_eq!((Board::new(), Piece::Black), record.start);
+        assert_eq!(vec![Some(Coord(5, 4)), Some(Coord(5, 5)), Some(Coord(4, 5))], record.moves);
+    }
+
+    #[test]
+    fn test_ggf_roundtrip() {
+        let mut record = Record::new("alphabeta:7", "human");
+        record.moves = vec![Some(Coord(5, 4)), None, Some(Coord(0, 7))];
+        record.result = Some("+2.00".to_string());
+        let s = record.to_string();
+        assert!(s.contains("BO[8 ---------------------------O*------*O--------------------------- *]"));
+        assert!(s.contains("B[F5]W[PA]B[A8]"));
+        assert_eq!(Ok(record), Record::parse(&s));
+    }
+
+    #[test]
+    fn test_ggf_game() {
+        let record = Record::parse(SAMPLE).unwrap();
+        let game = record.game().unwrap();
+        assert_eq!(Piece::White, game.turn());
+        assert_eq!(3, game.history().len());
+        // 黒が a1 に打つと盤面が埋まって終わる棋譜
+        let mut record = Record::new("", "");
+        record.start = Board::from_position(&format!("-OXXXXXX{} X", "X".repeat(56))).unwrap();
+        record.moves = vec![Some(Coord(0, 0))];
+        let game = record.game().unwrap();
+        assert!(game.outcome().is_some());
+        record.moves = vec![Some(Coord(1, 0))];
+        assert!(record.game().is_err());
+    }
+
+    #[test]
+    fn test_ggf_parse_errors() {
+        assert!(Record::parse("GM[Othello]").is_err());
+        assert!(Record::parse("(;GM[Chess]BO[8 ---------------------------O*------*O--------------------------- *];)").is_err());
+        assert!(Record::parse("(;GM[Othello]B[F5];)").is_err());
+        assert!(Record::parse("(;GM[Othello]BO[8 ---------------------------O*------*O--------------------------- *]B[Z9];)").is_err());
+        assert!(Record::parse("(;GM[Othello]BO[8 --- *;)").is_err());
+    }
+}
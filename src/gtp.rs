@@ -0,0 +1,253 @@
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use piece::Piece;
+use coord::Coord;
+use game::{Game, Manual, Play};
+use players::{self, PlayerKind};
+
+/// 対応しているコマンドの一覧
+const COMMANDS: [&str; 13] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "play",
+    "genmove",
+    "showboard",
+    "undo",
+    "final_score",
+];
+
+/// 囲碁の GTP にならった行単位のプロトコルで操作するエンジン
+///
+/// 成功した応答は `= 結果`、失敗は `? 理由` で始まり、空行で終わる。
+/// コマンドの前に数字の ID を付けると、応答にも同じ ID が付く。
+pub struct Engine {
+    player: Box<dyn Play + Send>,
+    game: Game<Manual, Manual>,
+}
+
+impl Engine {
+    pub fn new(kind: PlayerKind) -> Self {
+        Engine {
+            player: kind.build(0),
+            game: Game::new(Manual, Manual),
+        }
+    }
+
+    pub fn game(&self) -> &Game<Manual, Manual> {
+        &self.game
+    }
+
+    /// 1つのコマンドを実行し、応答の本文を返す
+    pub fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match (command, args) {
+            ("protocol_version", []) => Ok("2".to_string()),
+            ("name", []) => Ok("reversi".to_string()),
+            ("version", []) => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            ("known_command", [name]) => Ok(COMMANDS.contains(name).to_string()),
+            ("list_commands", []) => Ok(COMMANDS.join("\n")),
+            ("quit", []) => Ok(String::new()),
+            ("boardsize", [size]) => match size.parse::<usize>() {
+                Ok(8) => Ok(String::new()),
+                Ok(_) | Err(_) => Err("unacceptable size".to_string()),
+            },
+            ("clear_board", []) => {
+                self.game = Game::new(Manual, Manual);
+                Ok(String::new())
+            },
+            ("play", [color, vertex]) => {
+                let pos = if vertex.eq_ignore_ascii_case("pass") {
+                    None
+                } else {
+                    Some(vertex.parse()?)
+                };
+                let piece = self.prepare(color)?;
+                let result = match pos {
+                    Some(pos) => self.game.apply_at(pos),
+                    None => self.game.apply(None),
+                };
+                result.map_err(|err| {
+                    self.rollback(piece);
+                    format!("illegal move: {}", err)
+                })?;
+                Ok(String::new())
+            },
+            ("genmove", [color]) => {
+                let piece = self.prepare(color)?;
+                let stop = Arc::new(AtomicBool::new(false));
+                let (mov, _) = players::search(&mut self.player, piece, self.game.board(), self.game.history(), None, &stop, None);
+                let pos = mov.as_ref().map(|mov| mov.pos);
+                self.game.apply(mov).map_err(|err| {
+                    self.rollback(piece);
+                    format!("engine failed: {}", err)
+                })?;
+                Ok(format_vertex(pos))
+            },
+            ("showboard", []) => {
+                // 空行は応答の終わりを表すので、盤面の表示から取り除く
+                let board = self.game.board().to_string().replace("\n\n", "\n");
+                Ok(format!("\n{}\n{:?} to move", board, self.game.turn()))
+            },
+            ("undo", []) => match self.game.undo() {
+                Some(_) => Ok(String::new()),
+                None => Err("cannot undo".to_string()),
+            },
+            ("final_score", []) => {
+                let board = self.game.board();
+                Ok(match board.black as i32 - board.white as i32 {
+                    0 => "0".to_string(),
+                    diff if diff > 0 => format!("B+{}", diff),
+                    diff => format!("W+{}", -diff),
+                })
+            },
+            _ if COMMANDS.contains(&command) => Err("syntax error".to_string()),
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    /// `color` の側が打てるように手番を合わせる
+    ///
+    /// 相手の手番で相手に打てる手がない場合は、相手のパスを補う。
+    fn prepare(&mut self, color: &str) -> Result<Piece, String> {
+        let piece = parse_color(color)?;
+        if self.game.outcome().is_some() {
+            return Err("game is over".to_string());
+        }
+        if self.game.turn() != piece {
+            if !self.game.board().moves(self.game.turn()).is_empty() {
+                return Err(format!("it is {:?}'s turn", self.game.turn()));
+            }
+            self.game.apply(None).map_err(|err| err.to_string())?;
+        }
+        Ok(piece)
+    }
+
+    /// 失敗したコマンドのために `prepare` で補ったパスを取り消す
+    fn rollback(&mut self, piece: Piece) {
+        if self.game.turn() != piece {
+            return;
+        }
+        let is_padded = self.game.history().last().is_some_and(|ply| ply.piece != piece && ply.mov.is_none());
+        if is_padded {
+            self.game.undo();
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Result<Piece, String> {
+    match s.to_lowercase().as_str() {
+        "b" | "black" => Ok(Piece::Black),
+        "w" | "white" => Ok(Piece::White),
+        _ => Err(format!("invalid color: {:?}", s)),
+    }
+}
+
+fn format_vertex(pos: Option<Coord>) -> String {
+    match pos {
+        Some(pos) => pos.to_string().to_uppercase(),
+        None => "pass".to_string(),
+    }
+}
+
+/// `quit` か入力の終わりまで、1行ずつコマンドを読んで応答する
+///
+/// `#` 以降はコメントとして無視する。
+pub fn run<R: BufRead, W: Write>(kind: PlayerKind, input: R, mut output: W) -> io::Result<()> {
+    let mut engine = Engine::new(kind);
+    for line in input.lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("");
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let id = match words.first() {
+            Some(word) if word.parse::<u32>().is_ok() => words.remove(0),
+            Some(_) => "",
+            None => continue,
+        };
+        let (command, args) = match words.split_first() {
+            Some((&command, args)) => (command, args),
+            None => continue,
+        };
+        match engine.execute(command, args) {
+            Ok(ref response) if response.is_empty() => write!(output, "={}\n\n", id)?,
+            Ok(response) => write!(output, "={} {}\n\n", id, response)?,
+            Err(err) => write!(output, "?{} {}\n\n", id, err)?,
+        }
+        output.flush()?;
+        if command == "quit" {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        run(PlayerKind::AlphaBeta(3), Cursor::new(input), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_gtp_session() {
+        let output = session("1 protocol_version\nboardsize 8\n# comment\n\nplay black d3\n2 genmove white\nundo\nfinal_score\nquit\nname\n");
+        let responses: Vec<&str> = output.split("\n\n").collect();
+        assert_eq!("=1 2", responses[0]);
+        assert_eq!("=", responses[1]);
+        assert_eq!("=", responses[2]);
+        let genmove = responses[3];
+        assert!(["=2 C3", "=2 C5", "=2 E3"].contains(&genmove), "{}", genmove);
+        assert_eq!("=", responses[4]);
+        assert_eq!("= B+3", responses[5]);
+        assert_eq!("=", responses[6]);
+        assert_eq!("", responses[7]);
+    }
+
+    #[test]
+    fn test_gtp_errors() {
+        let mut engine = Engine::new(PlayerKind::Dumb);
+        assert_eq!(Err("unacceptable size".to_string()), engine.execute("boardsize", &["19"]));
+        assert_eq!(Err("unknown command".to_string()), engine.execute("komi", &["6.5"]));
+        assert_eq!(Err("syntax error".to_string()), engine.execute("play", &["black"]));
+        assert!(engine.execute("play", &["white", "d3"]).is_err());
+        assert!(engine.execute("play", &["black", "a1"]).is_err());
+        assert!(engine.execute("play", &["black", "pass"]).is_err());
+        assert!(engine.execute("play", &["red", "d3"]).is_err());
+        assert!(engine.execute("undo", &[]).is_err());
+        assert_eq!(Ok("true".to_string()), engine.execute("known_command", &["genmove"]));
+        assert_eq!(Ok("false".to_string()), engine.execute("known_command", &["komi"]));
+        assert!(engine.game().history().is_empty());
+    }
+
+    #[test]
+    fn test_gtp_fills_in_passes() {
+        let mut engine = Engine::new(PlayerKind::AlphaBeta(3));
+        for vertex in &["d3", "c3", "b3", "d2", "e1", "d6", "d7", "e3"] {
+            let color = if engine.game().turn() == Piece::Black { "black" } else { "white" };
+            engine.execute("play", &[color, vertex]).unwrap();
+        }
+        assert_eq!(Ok("F4".to_string()), engine.execute("genmove", &["black"]));
+        // 白は打てないので白のパスが補われ、黒も打てないのでパスして終局する
+        assert_eq!(Ok("pass".to_string()), engine.execute("genmove", &["black"]));
+        assert!(engine.execute("genmove", &["white"]).is_err());
+        assert_eq!(Ok("B+13".to_string()), engine.execute("final_score", &[]));
+    }
+
+    #[test]
+    fn test_gtp_showboard() {
+        let mut engine = Engine::new(PlayerKind::Dumb);
+        let board = engine.execute("showboard", &[]).unwrap();
+        assert!(board.starts_with('\n'));
+        assert!(!board.contains("\n\n"));
+        assert!(board.ends_with("B 2 - 2 W\nBlack to move"));
+    }
+}
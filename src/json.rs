@@ -0,0 +1,331 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// 配列とオブジェクトを入れ子にできる深さ。再帰でスタックを使い切らないように制限する
+const MAX_DEPTH: usize = 64;
+
+/// HTTP API などでやり取りする JSON の値
+///
+/// オブジェクトはキーの順序を保つために組の列で持つ。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// キーと値の組からオブジェクトを作る
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    /// 文字列を JSON として読む。前後の空白以外の余分な文字があればエラーになる
+    pub fn parse(s: &str) -> Result<Json, String> {
+        let mut chars = s.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(c) => Err(format!("unexpected {:?} after JSON value", c)),
+            None => Ok(value),
+        }
+    }
+
+    /// オブジェクトのキーに対応する値を返す。オブジェクトでない場合やキーがない場合は None
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref fields) => fields.iter().find(|field| field.0 == key).map(|field| &field.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(s: &'a str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+impl From<f64> for Json {
+    fn from(n: f64) -> Json {
+        Json::Number(n)
+    }
+}
+impl From<i32> for Json {
+    fn from(n: i32) -> Json {
+        Json::Number(n as f64)
+    }
+}
+impl From<u64> for Json {
+    fn from(n: u64) -> Json {
+        Json::Number(n as f64)
+    }
+}
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+impl From<u8> for Json {
+    fn from(n: u8) -> Json {
+        Json::Number(n as f64)
+    }
+}
+impl From<i8> for Json {
+    fn from(n: i8) -> Json {
+        Json::Number(n as f64)
+    }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Json {
+        Json::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// 空白を含まない1行の JSON として書く
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(ref s) => write_string(f, s),
+            Json::Array(ref items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(ref fields) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: &str) -> Result<(), String> {
+    for c in expected.chars() {
+        if chars.next() != Some(c) {
+            return Err(format!("expected {:?}", expected));
+        }
+    }
+    Ok(())
+}
+
+/// `depth` はこの値を囲む配列とオブジェクトの数
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, String> {
+    skip_whitespace(chars);
+    if depth >= MAX_DEPTH && (chars.peek() == Some(&'[') || chars.peek() == Some(&'{')) {
+        return Err("too deeply nested".to_string());
+    }
+    match chars.peek().cloned() {
+        Some('n') => expect(chars, "null").map(|_| Json::Null),
+        Some('t') => expect(chars, "true").map(|_| Json::Bool(true)),
+        Some('f') => expect(chars, "false").map(|_| Json::Bool(false)),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        },
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ":")?;
+                fields.push((key, parse_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return Err("expected ',' or '}' in object".to_string()),
+                }
+            }
+        },
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while chars.peek().is_some_and(|&c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+                number.push(chars.next().unwrap());
+            }
+            number.parse().map(Json::Number).map_err(|_| format!("invalid number: {:?}", number))
+        },
+        Some(c) => Err(format!("unexpected {:?}", c)),
+        None => Err("unexpected end of JSON".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, "\"")?;
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('"') => s.push('"'),
+                Some('\\') => s.push('\\'),
+                Some('/') => s.push('/'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('n') => s.push('\n'),
+                Some('r') => s.push('\r'),
+                Some('t') => s.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape: \\u{}", hex))?;
+                    // サロゲートペアには対応せず、置換文字にする
+                    s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                },
+                _ => return Err("invalid escape in string".to_string()),
+            },
+            Some(c) => s.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_parse() {
+        let json = Json::parse(r#" {"move": "d3", "time": 1.5, "list": [1, -2e1, true, null], "nested": {}} "#).unwrap();
+        assert_eq!(Some("d3"), json.get("move").and_then(Json::as_str));
+        assert_eq!(Some(1.5), json.get("time").and_then(Json::as_f64));
+        let list = json.get("list").and_then(Json::as_array).unwrap();
+        assert_eq!(&[Json::Number(1.0), Json::Number(-20.0), Json::Bool(true), Json::Null], list);
+        assert_eq!(Some(&Json::Object(Vec::new())), json.get("nested"));
+        assert_eq!(Ok(Json::String("a\"\\\n\u{e9}".to_string())), Json::parse(r#""a\"\\\né""#));
+    }
+
+    #[test]
+    fn test_json_parse_errors() {
+        for s in &["", "{", "[1,]", "{\"a\" 1}", "tru", "\"abc", "1 2", "{1: 2}"] {
+            assert!(Json::parse(s).is_err(), "{:?} is invalid", s);
+        }
+    }
+
+    #[test]
+    fn test_json_parse_nesting() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(Err("too deeply nested".to_string()), Json::parse(&nested(MAX_DEPTH + 1)));
+        assert_eq!(Err("too deeply nested".to_string()), Json::parse(&"[".repeat(100_000)));
+        assert_eq!(Err("too deeply nested".to_string()), Json::parse(&"{\"a\":".repeat(100_000)));
+    }
+
+    #[test]
+    fn test_json_display() {
+        let json = Json::object(vec![
+            ("name", "a \"b\"\n".into()),
+            ("score", (-3i8).into()),
+            ("moves", vec!["d3", "c5"].into()),
+            ("winner", Json::from(None::<&str>)),
+        ]);
+        let s = json.to_string();
+        assert_eq!(r#"{"name":"a \"b\"\n","score":-3,"moves":["d3","c5"],"winner":null}"#, s);
+        assert_eq!(Ok(json), Json::parse(&s));
+    }
+}
@@ -0,0 +1,84 @@
+//! リバーシの盤面、対局の進行、コンピュータプレイヤーをまとめたライブラリ
+//!
+//! よく使う型はクレートの直下から使える。
+//!
+//! - [`Board`]、[`Move`]、[`Coord`]、[`Piece`]: 盤面と手
+//! - [`Game`] と [`Play`]: 対局の進行と、対局者が実装するトレイト
+//! - [`players`]: 組み込みのプレイヤーと、局面の評価関数 [`players::evaluate`]
+//!
+//! `serde` フィーチャーを有効にすると、これらの型と [`History`] をシリアライズできる。手は局面がないと読めないので `MoveSeed` で読む。
+//! C から使うための関数は [`ffi`] に、Python の拡張モジュールは `python` フィーチャーの `python` に、
+//! JavaScript から使う WebAssembly の API は `wasm` フィーチャーの `wasm` にまとめてある。
+//!
+//! ```
+//! use reversi::{Board, Game, Piece};
+//! use reversi::players::{AlphaBetaPlayer, RandomPlayer};
+//!
+//! let board = Board::new();
+//! assert_eq!(4, board.moves(Piece::Black).len());
+//!
+//! let mut game = Game::new(AlphaBetaPlayer::new(2), RandomPlayer::new(1));
+//! while game.step() {}
+//! println!("{}", game.outcome().unwrap());
+//! ```
+//!
+//! コマンドラインの `reversi` は、このクレートの [`cli`] などを薄く包んだものである。
+
+extern crate smallvec;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate rayon;
+#[cfg(target_arch = "wasm32")]
+extern crate web_time;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate js_sys;
+// pyo3 と wasm-bindgen のマクロが展開するコードは `::core` を参照する
+#[cfg(any(feature = "python", feature = "wasm"))]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate numpy;
+
+pub mod piece;
+pub mod coord;
+pub mod clock;
+mod rng;
+pub mod board;
+pub mod game;
+pub mod ggf;
+pub mod players;
+pub mod coach;
+pub mod analysis;
+pub mod openings;
+pub mod tournament;
+pub mod nboard;
+pub mod gtp;
+mod json;
+mod websocket;
+pub mod server;
+pub mod remote;
+pub mod online;
+pub mod tui;
+pub mod perft;
+pub mod bench;
+pub mod cli;
+pub mod ffi;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use piece::Piece;
+pub use coord::Coord;
+pub use board::{Board, Matrix, Move};
+pub use clock::{Clock, TimeControl};
+pub use game::{Context, Game, History, MoveError, Outcome, Play, Ply};
+pub use players::PlayerKind;
+#[cfg(feature = "serde")]
+pub use serialize::MoveSeed;
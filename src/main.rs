@@ -1,21 +1,118 @@
-extern crate smallvec;
-extern crate rayon;
+// コマンドラインの `reversi`。引数を読んで、ライブラリの各モジュールに処理を任せる
+extern crate reversi;
 
-mod piece;
-mod coord;
-mod board;
-mod game;
-mod players;
-
-use game::Game;
-use players::*;
+use std::env;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use reversi::{bench, cli, gtp, nboard, online, openings, remote, server, tournament};
+use reversi::cli::Command;
 
 fn main() {
-    let p1 = AlphaBetaPlayer::new(7);
-    let p2 = Human;
-    let mut game = Game::new(p1, p2);
-    while {
-        game.print();
-        game.step()
-    } {}
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Play(options)) => options,
+        Ok(Command::Tournament(entrants, settings)) => {
+            match tournament::run(&entrants, &settings) {
+                Ok(report) => print!("{}", report),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                },
+            }
+            return;
+        },
+        Ok(Command::Openings { count, plies, depth, threshold, seed }) => {
+            let openings = openings::generate(count, plies, depth, threshold, seed);
+            for (moves, score) in &openings {
+                let moves: String = moves.iter().map(|pos| pos.to_string()).collect();
+                println!("{} # {:+}", moves, score);
+            }
+            if openings.len() < count {
+                eprintln!("warning: only {} of {} openings found before running out of new positions", openings.len(), count);
+            }
+            return;
+        },
+        Ok(Command::NBoard(kind)) => {
+            let stdin = io::stdin();
+            if let Err(err) = nboard::run(kind, stdin.lock(), io::stdout()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Gtp(kind)) => {
+            let stdin = io::stdin();
+            if let Err(err) = gtp::run(kind, stdin.lock(), io::stdout()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Serve(settings)) => {
+            let result = server::Server::bind(&settings).and_then(|server| {
+                println!("Listening on http://{}", server.local_addr()?);
+                server.run()
+            });
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Host(port, settings)) => {
+            let result = TcpListener::bind(("0.0.0.0", port)).and_then(|listener| {
+                println!("Waiting for an opponent on port {}", listener.local_addr()?.port());
+                remote::host(&settings, &listener, io::stdout())
+            });
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Join(address, kind)) => {
+            let result = TcpStream::connect(&address[..]).and_then(|stream| {
+                remote::join(stream, kind.build(cli::default_seed()), io::stdout())
+            });
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Online(settings)) => {
+            if let Err(err) = online::run(&settings) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Analyze(settings)) => {
+            if let Err(err) = cli::analyze(&settings) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            return;
+        },
+        Ok(Command::Perft(settings)) => {
+            cli::perft(&settings);
+            return;
+        },
+        Ok(Command::Bench(settings)) => {
+            print!("{}", bench::run(&settings));
+            return;
+        },
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        },
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        },
+    };
+    if let Err(err) = cli::run(&options) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
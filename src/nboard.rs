@@ -0,0 +1,249 @@
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+use board::Move;
+use coord::Coord;
+use game::{Game, Manual, Play};
+use ggf::Record;
+use players::{self, AlphaBetaPlayer, PlayerKind};
+
+/// `set myname` で名乗るエンジンの名前
+const NAME: &str = "reversi";
+
+/// 深さを持たないプレイヤーで `hint` に答えるときの探索の深さ
+const DEFAULT_HINT_DEPTH: usize = 7;
+
+/// NBoard プロトコルで GUI とやり取りするエンジン
+///
+/// 局面は GUI から送られてくる `set game` と `move` だけで進め、
+/// `go` では手を返すだけで自分では打たない。
+pub struct Engine {
+    kind: PlayerKind,
+    player: Box<dyn Play + Send>,
+    game: Game<Manual, Manual>,
+}
+
+impl Engine {
+    pub fn new(kind: PlayerKind) -> Self {
+        Engine {
+            kind,
+            player: kind.build(0),
+            game: Game::new(Manual, Manual),
+        }
+    }
+
+    pub fn game(&self) -> &Game<Manual, Manual> {
+        &self.game
+    }
+
+    /// 1行分のコマンドを処理し、GUI に返す行を返す
+    ///
+    /// 知らないコマンドや不正な手はエラーになる。`quit` はここでは扱わない。
+    pub fn execute(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let line = line.trim();
+        let (command, args) = match line.find(' ') {
+            Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+            None => (line, ""),
+        };
+        match command {
+            "" => Ok(Vec::new()),
+            "nboard" => Ok(vec![format!("set myname {}", NAME)]),
+            "set" => self.set(args),
+            "move" => {
+                let pos = args.split('/').next().unwrap_or("");
+                self.play(parse_move(pos)?)?;
+                Ok(Vec::new())
+            },
+            "hint" => {
+                let count = args.parse().map_err(|_| format!("invalid hint count: {:?}", args))?;
+                Ok(self.hint(count))
+            },
+            "go" => Ok(vec![self.go()]),
+            "ping" => Ok(vec![format!("pong {}", args)]),
+            // 定石の学習には対応していないが、GUI を待たせないように応答だけ返す
+            "learn" => Ok(vec!["learned".to_string()]),
+            _ => Err(format!("unknown command: {:?}", line)),
+        }
+    }
+
+    /// `set depth`、`set game`、`set contempt` を処理する
+    fn set(&mut self, args: &str) -> Result<Vec<String>, String> {
+        let (name, value) = match args.find(' ') {
+            Some(idx) => (&args[..idx], args[idx + 1..].trim()),
+            None => (args, ""),
+        };
+        match name {
+            "depth" => {
+                let depth = match value.parse() {
+                    Ok(depth) if depth > 0 => depth,
+                    _ => return Err(format!("invalid depth: {:?}", value)),
+                };
+                self.kind = self.kind.with_depth(depth);
+                self.player = self.kind.build(0);
+            },
+            "game" => {
+                self.game = Record::parse(value)?.game()?;
+                self.player.new_game(self.game.turn(), self.game.board());
+            },
+            // 引き分けの評価は変えないので読み捨てる
+            "contempt" => {},
+            _ => return Err(format!("unknown setting: {:?}", name)),
+        }
+        Ok(Vec::new())
+    }
+
+    /// 手番の側の手を打つ。パスは None
+    fn play(&mut self, pos: Option<Coord>) -> Result<(), String> {
+        match pos {
+            Some(pos) => self.game.apply_at(pos),
+            None => self.game.apply(None),
+        }.map_err(|err| err.to_string())
+    }
+
+    /// 評価値の高い順に `count` 手までの候補を `search` 行で返す
+    ///
+    /// 手ごとの評価値を出せるのはアルファベータ法だけなので、設定したプレイヤーの種類にかかわらず
+    /// その深さのアルファベータ法で評価する。
+    fn hint(&mut self, count: usize) -> Vec<String> {
+        let depth = self.kind.depth().unwrap_or(DEFAULT_HINT_DEPTH);
+        let turn = self.game.turn();
+        let board = self.game.board();
+        let mut lines = vec!["status Analyzing".to_string()];
+        if self.game.outcome().is_none() {
+            let scores = AlphaBetaPlayer::new(depth).analyze(turn, board);
+            if scores.is_empty() {
+                // パスしかできない場合は、相手から見た評価値を反転して返す
+                let score = AlphaBetaPlayer::new(depth).score(turn.opponent(), board);
+                lines.push(format!("search PA {} 0 {}", -score, depth));
+            }
+            for (mov, score) in scores.into_iter().take(count) {
+                lines.push(format!("search {} {} 0 {}", format_move(Some(&mov)), score, depth));
+            }
+        }
+        lines.push("status".to_string());
+        lines
+    }
+
+    /// 手番の側の手を考えて `===` 行で返す
+    fn go(&mut self) -> String {
+        let started = Instant::now();
+        let stop = Arc::new(AtomicBool::new(false));
+        let turn = self.game.turn();
+        let (mov, _) = players::search(&mut self.player, turn, self.game.board(), self.game.history(), None, &stop, None);
+        format!("=== {}//{:.2}", format_move(mov.as_ref()), started.elapsed().as_secs_f64())
+    }
+}
+
+/// `F5` や `PA` を読む
+fn parse_move(s: &str) -> Result<Option<Coord>, String> {
+    if s.eq_ignore_ascii_case("pa") {
+        Ok(None)
+    } else {
+        s.parse().map(Some)
+    }
+}
+
+/// NBoard の表記に合わせて大文字で書く
+fn format_move(mov: Option<&Move>) -> String {
+    match mov {
+        Some(mov) => mov.pos.to_string().to_uppercase(),
+        None => "PA".to_string(),
+    }
+}
+
+/// `quit` か入力の終わりまで、1行ずつコマンドを読んで応答する
+///
+/// プロトコル上のエラーは GUI には返さず、標準エラー出力に書く。
+pub fn run<R: BufRead, W: Write>(kind: PlayerKind, input: R, mut output: W) -> io::Result<()> {
+    let mut engine = Engine::new(kind);
+    for line in input.lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+        match engine.execute(&line) {
+            Ok(lines) => for line in lines {
+                writeln!(output, "{}", line)?;
+            },
+            Err(err) => eprintln!("error: {}", err),
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use piece::Piece;
+    use board::Board;
+    use super::*;
+
+    const START: &str = "(;GM[Othello]PC[NBoard]PB[Human]PW[Engine]TI[5:00]TY[8]\
+        BO[8 ---------------------------O*------*O--------------------------- *];)";
+
+    fn session(kind: PlayerKind, input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        run(kind, Cursor::new(input), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_nboard_session() {
+        let input = format!("nboard 2\nset depth 3\nset game {}\nmove F5/0.00/1.0\nping 1\ngo\nlearn\nquit\nping 2\n", START);
+        let output = session(PlayerKind::AlphaBeta(5), &input);
+        assert_eq!("set myname reversi", output[0]);
+        assert_eq!("pong 1", output[1]);
+        assert!(output[2].starts_with("=== "));
+        let pos = &output[2][4..6];
+        let moves = ["D6", "F4", "F6"];
+        assert!(moves.contains(&pos), "{} is a legal move for white", pos);
+        assert_eq!("learned", output[3]);
+        assert_eq!(4, output.len());
+    }
+
+    #[test]
+    fn test_nboard_hint() {
+        let mut engine = Engine::new(PlayerKind::AlphaBeta(3));
+        engine.execute(&format!("set game {}", START)).unwrap();
+        let lines = engine.execute("hint 2").unwrap();
+        assert_eq!("status Analyzing", lines[0]);
+        assert_eq!("status", lines[3]);
+        assert_eq!(4, lines.len());
+        let scores: Vec<i8> = lines[1..3].iter()
+            .map(|line| line.split(' ').nth(2).unwrap().parse().unwrap())
+            .collect();
+        assert!(scores[0] >= scores[1]);
+    }
+
+    #[test]
+    fn test_nboard_set_game_with_moves() {
+        let mut engine = Engine::new(PlayerKind::Dumb);
+        let game = START.replace(";)", "B[F5]W[D6];)");
+        engine.execute(&format!("set game {}", game)).unwrap();
+        assert_eq!(Piece::Black, engine.game().turn());
+        assert_eq!(2, engine.game().history().len());
+        assert!(engine.execute("move A1").is_err());
+        assert!(engine.execute("move PA").is_err());
+        assert!(engine.execute("set game (;GM[Othello];)").is_err());
+        assert!(engine.execute("set depth 0").is_err());
+        assert!(engine.execute("frobnicate").is_err());
+        assert_eq!(2, engine.game().history().len());
+    }
+
+    #[test]
+    fn test_nboard_pass() {
+        // 黒は打てず、白だけが a1 に打てる局面
+        let position = format!("-XXXXXXO{} X", "X".repeat(56));
+        let (board, turn) = Board::from_position(&position).unwrap();
+        let mut record = Record::new("", "");
+        record.start = (board, turn);
+        let mut engine = Engine::new(PlayerKind::AlphaBeta(3));
+        engine.execute(&format!("set game {}", record)).unwrap();
+        assert!(engine.execute("go").unwrap()[0].starts_with("=== PA"));
+        assert_eq!("search PA", &engine.execute("hint 1").unwrap()[1][..9]);
+        engine.execute("move PA").unwrap();
+        assert_eq!(Piece::White, engine.game().turn());
+    }
+}
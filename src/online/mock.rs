@@ -0,0 +1,156 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use piece::Piece;
+use clock::{Clock, TimeControl};
+use game::{Game, Manual, Outcome};
+use ggf::Record;
+use super::Message;
+
+/// ログインに使えるパスワード
+const PASSWORD: &str = "secret";
+
+/// ログインしたクライアントに知らせる、サーバー側からの募集
+const SEEK_ID: u64 = 7;
+
+/// テストのために手元で動かす対局サーバー
+///
+/// 1つの接続だけを受け付け、自分では最初に見つかった合法手を打つ `mock` として1局を相手する。
+/// 相手の持ち時間は `seek` の内容によらず、`start` に渡した時間の切れ負けとする。
+pub struct MockServer {
+    addr: SocketAddr,
+    handle: JoinHandle<Record>,
+}
+
+impl MockServer {
+    pub fn start(time: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream, time)
+        });
+        MockServer { addr, handle }
+    }
+
+    pub fn addr(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// 対局が終わるのを待ち、最後の棋譜を返す
+    pub fn finish(self) -> Record {
+        self.handle.join().unwrap()
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn send(&mut self, message: Message) {
+        writeln!(self.writer, "{}", message).unwrap();
+    }
+
+    /// 1行を単語に分けて返す。接続が切れていれば None
+    fn receive(&mut self) -> Option<Vec<String>> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line).unwrap() {
+            0 => None,
+            _ => Some(line.split_whitespace().map(String::from).collect()),
+        }
+    }
+}
+
+fn ok(words: &[&str]) -> Message {
+    Message::Ok(words.iter().map(|word| word.to_string()).collect())
+}
+
+fn serve(stream: TcpStream, time: Duration) -> Record {
+    let mut conn = Connection {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+    };
+    let mut name = None;
+    let (id, color, time_control) = loop {
+        let words = conn.receive().expect("the client disconnected before a match");
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match (&words[..], name.is_some()) {
+            (["login", user, PASSWORD], _) => {
+                // 募集中の対局を知らせてからログインの完了を返す
+                name = Some(user.to_string());
+                conn.send(Message::Seek { id: SEEK_ID, name: "mock".to_string(), time: "1:00".to_string() });
+                conn.send(ok(&["login", user]));
+            },
+            (["login", ..], _) => conn.send(Message::Error("wrong password".to_string())),
+            (["seek", time], true) => {
+                conn.send(ok(&["seek", "1"]));
+                break (1, Piece::Black, time.to_string());
+            },
+            (["accept", seek], true) if *seek == SEEK_ID.to_string() => {
+                conn.send(ok(&["accept", seek]));
+                break (SEEK_ID, Piece::White, "1:00".to_string());
+            },
+            (["accept", _], true) => conn.send(Message::Error("no such seek".to_string())),
+            _ => conn.send(Message::Error(format!("unexpected command: {:?}", words.join(" ")))),
+        }
+    };
+    let name = name.expect("logged in");
+    let mut record = match color {
+        Piece::Black => Record::new(&name, "mock"),
+        Piece::White => Record::new("mock", &name),
+    };
+    record.time = Some(time_control);
+    conn.send(Message::Match { id, black: record.black_name.clone(), white: record.white_name.clone() });
+    let mut game = Game::new(Manual, Manual);
+    let mut clock = Clock::new(TimeControl::SuddenDeath(time));
+    let outcome = loop {
+        if let Some(outcome) = game.outcome() {
+            break outcome;
+        }
+        // 双方とも打てなくなったら、パスを待たずに終局させる
+        let board = game.board();
+        let is_over = board.moves(Piece::Black).is_empty() && board.moves(Piece::White).is_empty();
+        if game.turn() != color || is_over {
+            let mov = game.board().moves(game.turn()).into_iter().next();
+            game.apply(mov).unwrap();
+            continue;
+        }
+        let (black, white) = match color {
+            Piece::Black => (clock.remaining(), Duration::from_secs(60)),
+            Piece::White => (Duration::from_secs(60), clock.remaining()),
+        };
+        conn.send(Message::Clock { id, black, white });
+        record.moves = game.history().iter().map(|ply| ply.mov.as_ref().map(|mov| mov.pos)).collect();
+        conn.send(Message::Update { id, record: record.clone() });
+        let started = Instant::now();
+        let words = conn.receive().expect("the client disconnected during a match");
+        if !clock.consume(started.elapsed()) {
+            break Outcome::Timeout(color);
+        }
+        let result = match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["move", game_id, "PA"] if game_id == id.to_string() => game.apply(None).map_err(|err| err.to_string()),
+            ["move", game_id, pos] if game_id == id.to_string() => match pos.parse() {
+                Ok(pos) => game.apply_at(pos).map_err(|err| err.to_string()),
+                Err(err) => Err(err),
+            },
+            _ => Err(format!("unexpected command: {:?}", words.join(" "))),
+        };
+        if let Err(err) = result {
+            conn.send(Message::Error(err));
+        }
+    };
+    record.moves = game.history().iter().map(|ply| ply.mov.as_ref().map(|mov| mov.pos)).collect();
+    record.result = Some(outcome.to_string());
+    conn.send(Message::Update { id, record: record.clone() });
+    conn.send(Message::End { id, result: outcome.to_string() });
+    // クライアントが `quit` するか切断するまで待つ
+    while let Some(words) = conn.receive() {
+        if words.first().map(String::as_str) == Some("quit") {
+            break;
+        }
+    }
+    record
+}
@@ -0,0 +1,387 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use piece::Piece;
+use clock::TimeControl;
+use game::Play;
+use ggf::Record;
+use players::{self, PlayerKind};
+
+#[cfg(test)]
+mod mock;
+
+/// 対局サーバーから送られてくる1行
+///
+/// GGS に似た行単位のプロトコルで、局面は GGF で送られてくる。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// 直前の要求が受け付けられた。`ok seek 3` のように要求の名前と結果が続く
+    Ok(Vec<String>),
+    /// 直前の要求が受け付けられなかった
+    Error(String),
+    /// 誰かが対局相手を募集している
+    Seek { id: u64, name: String, time: String },
+    /// 対局が始まった
+    Match { id: u64, black: String, white: String },
+    /// 対局の局面が変わった。これまでの手をすべて含む棋譜が送られてくる
+    Update { id: u64, record: Record },
+    /// 双方の残り時間
+    Clock { id: u64, black: Duration, white: Duration },
+    /// 対局が終わった
+    End { id: u64, result: String },
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Result<Message, String> {
+        let line = line.trim();
+        let (kind, rest) = match line.find(' ') {
+            Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+            None => (line, ""),
+        };
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let id = |s: &str| s.parse::<u64>().map_err(|_| format!("invalid id: {:?}", s));
+        let secs = |s: &str| match s.parse::<f64>() {
+            Ok(secs) if secs >= 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+            _ => Err(format!("invalid time: {:?}", s)),
+        };
+        let invalid = || format!("invalid message: {:?}", line);
+        match (kind, &words[..]) {
+            ("ok", _) => Ok(Message::Ok(words.iter().map(|word| word.to_string()).collect())),
+            ("error", _) => Ok(Message::Error(rest.to_string())),
+            ("seek", [seek, name, time]) => Ok(Message::Seek {
+                id: id(seek)?,
+                name: name.to_string(),
+                time: time.to_string(),
+            }),
+            ("match", [game, black, white]) => Ok(Message::Match {
+                id: id(game)?,
+                black: black.to_string(),
+                white: white.to_string(),
+            }),
+            ("update", [game, ..]) => Ok(Message::Update {
+                id: id(game)?,
+                record: Record::parse(&rest[game.len()..])?,
+            }),
+            ("clock", [game, black, white]) => Ok(Message::Clock {
+                id: id(game)?,
+                black: secs(black)?,
+                white: secs(white)?,
+            }),
+            ("end", [game, ..]) => Ok(Message::End {
+                id: id(game)?,
+                result: rest[game.len()..].trim().to_string(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Ok(ref words) if words.is_empty() => write!(f, "ok"),
+            Message::Ok(ref words) => write!(f, "ok {}", words.join(" ")),
+            Message::Error(ref message) => write!(f, "error {}", message),
+            Message::Seek { id, ref name, ref time } => write!(f, "seek {} {} {}", id, name, time),
+            Message::Match { id, ref black, ref white } => write!(f, "match {} {} {}", id, black, white),
+            Message::Update { id, ref record } => write!(f, "update {} {}", id, record),
+            Message::Clock { id, black, white } => {
+                write!(f, "clock {} {:.3} {:.3}", id, black.as_secs_f64(), white.as_secs_f64())
+            },
+            Message::End { id, ref result } => write!(f, "end {} {}", id, result),
+        }
+    }
+}
+
+/// 対局サーバーに接続してエンジンを対局させるクライアント
+///
+/// クライアントからは次の行を送る。
+///
+/// * `login <名前> <パスワード>`
+/// * `seek <持ち時間>` - 対局相手を募集する。持ち時間は GGF の `TI` と同じ `5:00` の形式
+/// * `accept <ID>` - `seek` で募集されている対局を受ける
+/// * `move <対局の ID> <F5|PA>`
+/// * `quit`
+pub struct Client<R, W> {
+    reader: R,
+    writer: W,
+    name: String,
+    /// 要求の応答を待つ間に届いた、対局相手の募集
+    seeks: Vec<Message>,
+}
+
+impl Client<BufReader<TcpStream>, TcpStream> {
+    /// `host:port` のサーバーに接続する
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Client::new(BufReader::new(stream.try_clone()?), stream))
+    }
+}
+
+impl<R: BufRead, W: Write> Client<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Client {
+            reader,
+            writer,
+            name: String::new(),
+            seeks: Vec::new(),
+        }
+    }
+
+    /// これまでに届いた対局相手の募集を返す
+    pub fn seeks(&self) -> &[Message] {
+        &self.seeks
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.writer, "{}", line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| format!("failed to send: {}", err))
+    }
+
+    fn receive(&mut self) -> Result<Message, String> {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Err("the server closed the connection".to_string()),
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => return Message::parse(&line),
+                Err(err) => return Err(format!("failed to receive: {}", err)),
+            }
+        }
+    }
+
+    /// 要求を送り、`ok <name> ...` の応答を待って、その続きを返す
+    fn request(&mut self, name: &str, line: &str) -> Result<Vec<String>, String> {
+        self.send(line)?;
+        loop {
+            match self.receive()? {
+                Message::Ok(ref words) if words.first().map(String::as_str) == Some(name) => {
+                    return Ok(words[1..].to_vec());
+                },
+                Message::Error(message) => return Err(message),
+                seek @ Message::Seek { .. } => self.seeks.push(seek),
+                _ => {},
+            }
+        }
+    }
+
+    pub fn login(&mut self, name: &str, password: &str) -> Result<(), String> {
+        self.request("login", &format!("login {} {}", name, password))?;
+        self.name = name.to_string();
+        Ok(())
+    }
+
+    /// 対局相手を募集し、募集の ID を返す
+    pub fn seek(&mut self, time: &str) -> Result<u64, String> {
+        let words = self.request("seek", &format!("seek {}", time))?;
+        match words.first().map(|id| id.parse()) {
+            Some(Ok(id)) => Ok(id),
+            _ => Err(format!("invalid seek response: {:?}", words)),
+        }
+    }
+
+    /// 募集されている対局を受ける
+    pub fn accept(&mut self, id: u64) -> Result<(), String> {
+        self.request("accept", &format!("accept {}", id))?;
+        Ok(())
+    }
+
+    /// 自分の対局が始まるのを待って1局打ち、サーバーから送られた結果を返す
+    ///
+    /// 局面が届くたびに棋譜から打ち直し、自分の手番であれば `player` に考えさせて手を送る。
+    /// 不正な手を送った場合は、サーバーが同じ局面を送り直すのを待つ。経過は `output` に書く。
+    pub fn play<P: Play, O: Write>(&mut self, player: &mut P, mut output: O) -> Result<String, String> {
+        let mut current = None;
+        let mut remaining = None;
+        let mut is_started = false;
+        let log = |output: &mut O, line: String| writeln!(output, "{}", line).map_err(|err| err.to_string());
+        loop {
+            match self.receive()? {
+                Message::Match { id, ref black, ref white } if current.is_none() => {
+                    let color = if *black == self.name {
+                        Piece::Black
+                    } else if *white == self.name {
+                        Piece::White
+                    } else {
+                        continue;
+                    };
+                    log(&mut output, format!("Match {}: {} vs {}", id, black, white))?;
+                    current = Some((id, color));
+                },
+                Message::Clock { id, black, white } if current.is_some_and(|(current, _)| current == id) => {
+                    remaining = Some(match current {
+                        Some((_, Piece::Black)) => black,
+                        _ => white,
+                    });
+                },
+                Message::Update { id, ref record } => {
+                    let color = match current {
+                        Some((current, color)) if current == id => color,
+                        _ => continue,
+                    };
+                    let game = record.game()?;
+                    if !is_started {
+                        let (board, _) = game.start();
+                        player.new_game(color, board);
+                        is_started = true;
+                    }
+                    if let Some(ply) = game.history().last() {
+                        if ply.piece != color {
+                            player.opponent_moved(ply, game.board());
+                        }
+                    }
+                    log(&mut output, format!("{}", game.board()))?;
+                    // 時間切れなどで終わった対局は、結果だけが棋譜に書かれている
+                    if record.result.is_some() || game.outcome().is_some() || game.turn() != color {
+                        continue;
+                    }
+                    let stop = Arc::new(AtomicBool::new(false));
+                    let time_control = remaining.map(TimeControl::SuddenDeath);
+                    let (mov, _) = players::search(&mut *player, color, game.board(), game.history(), time_control, &stop, None);
+                    let pos = match mov {
+                        Some(ref mov) => mov.pos.to_string().to_uppercase(),
+                        None => "PA".to_string(),
+                    };
+                    log(&mut output, format!("Move: {}", pos))?;
+                    self.send(&format!("move {} {}", id, pos))?;
+                },
+                Message::Error(message) => log(&mut output, format!("Server: {}", message))?,
+                Message::End { id, result } if current.is_some_and(|(current, _)| current == id) => {
+                    log(&mut output, result.clone())?;
+                    return Ok(result);
+                },
+                seek @ Message::Seek { .. } => self.seeks.push(seek),
+                _ => {},
+            }
+        }
+    }
+
+    /// サーバーとの接続を終える
+    pub fn quit(&mut self) -> Result<(), String> {
+        self.send("quit")
+    }
+}
+
+/// 対局の始め方
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// 指定の持ち時間で対局相手を募集する
+    Seek(String),
+    /// 指定の ID の募集を受ける
+    Accept(u64),
+}
+
+/// `online` サブコマンドの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// `host:port` の形式のサーバーのアドレス
+    pub address: String,
+    pub name: String,
+    pub password: String,
+    pub request: Request,
+    pub player: PlayerKind,
+}
+
+/// サーバーに接続してログインし、1局打って結果を返す。経過は標準出力に書く
+pub fn run(settings: &Settings) -> Result<String, String> {
+    let mut client = Client::connect(&settings.address).map_err(|err| format!("{}: {}", settings.address, err))?;
+    client.login(&settings.name, &settings.password)?;
+    match settings.request {
+        Request::Seek(ref time) => {
+            let id = client.seek(time)?;
+            println!("Seeking an opponent (seek {})", id);
+        },
+        Request::Accept(id) => client.accept(id)?,
+    }
+    let mut player = settings.player.build(0);
+    let result = client.play(&mut player, io::stdout())?;
+    client.quit()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use coord::Coord;
+    use players::{AlphaBetaPlayer, DumbPlayer};
+    use super::mock::MockServer;
+    use super::*;
+
+    #[test]
+    fn test_online_message_roundtrip() {
+        let mut record = Record::new("alice", "bob");
+        record.moves = vec![Some(Coord(5, 4)), None];
+        let messages = [
+            Message::Ok(vec!["seek".to_string(), "3".to_string()]),
+            Message::Error("no such seek".to_string()),
+            Message::Seek { id: 3, name: "alice".to_string(), time: "5:00".to_string() },
+            Message::Match { id: 4, black: "alice".to_string(), white: "bob".to_string() },
+            Message::Update { id: 4, record },
+            Message::Clock { id: 4, black: Duration::from_millis(1500), white: Duration::from_secs(300) },
+            Message::End { id: 4, result: "Black wins (B 40 - 24 W)".to_string() },
+        ];
+        for message in &messages {
+            assert_eq!(Ok(message), Message::parse(&message.to_string()).as_ref());
+        }
+        assert!(Message::parse("seek x alice 5:00").is_err());
+        assert!(Message::parse("update 1 (;GM[Chess];)").is_err());
+        assert!(Message::parse("hello").is_err());
+    }
+
+    #[test]
+    fn test_online_seek_and_play() {
+        let server = MockServer::start(Duration::from_secs(3));
+        let mut client = Client::connect(&server.addr()).unwrap();
+        assert!(client.login("alice", "wrong").is_err());
+        client.login("alice", "secret").unwrap();
+        let id = client.seek("1:00").unwrap();
+        let result = client.play(&mut AlphaBetaPlayer::new(2), io::sink()).unwrap();
+        client.quit().unwrap();
+        let record = server.finish();
+        assert_eq!("alice", record.black_name);
+        assert_eq!("mock", record.white_name);
+        let outcome = record.game().unwrap().outcome().expect("the game is over");
+        assert_eq!(outcome.to_string(), result);
+        assert_eq!(1, id);
+    }
+
+    #[test]
+    fn test_online_accept() {
+        let server = MockServer::start(Duration::from_secs(60));
+        let mut client = Client::connect(&server.addr()).unwrap();
+        client.login("bob", "secret").unwrap();
+        let id = match client.seeks().first() {
+            Some(&Message::Seek { id, .. }) => id,
+            seek => panic!("unexpected seek: {:?}", seek),
+        };
+        assert!(client.accept(id + 1).is_err());
+        client.accept(id).unwrap();
+        client.play(&mut DumbPlayer, io::sink()).unwrap();
+        client.quit().unwrap();
+        assert_eq!("bob", server.finish().white_name);
+    }
+
+    #[test]
+    fn test_online_clock() {
+        struct Slow;
+        impl Play for Slow {
+            fn play(&mut self, piece: Piece, board: &::board::Board) -> Option<::board::Move> {
+                ::std::thread::sleep(Duration::from_millis(200));
+                board.moves(piece).into_iter().next()
+            }
+        }
+        let server = MockServer::start(Duration::from_millis(300));
+        let mut client = Client::connect(&server.addr()).unwrap();
+        client.login("carol", "secret").unwrap();
+        client.seek("0:01").unwrap();
+        let result = client.play(&mut Slow, io::sink()).unwrap();
+        assert_eq!("White wins on time", result);
+        drop(client);
+        server.finish();
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+use game::{self, Game, Manual};
+use players::AlphaBetaPlayer;
+use rng::Rng;
+
+/// 同梱している互角に近い序盤の一覧 (XOT 形式の 8 手)
+///
+/// `generate(64, 8, 6, 2, 789)` で作ったもの。コメントは8手後に手番となる側から見た評価値。
+pub const SUITE: [&str; 64] = [
+    "f5f4g3g4h3f6f3c5", // -2
+    "c4c5f6f5f4f3e6b4", // -2
+    "f5f4c3d6d7g5f3c4", // +0
+    "e6f6c4d6e7d8e8d3", // -2
+    "e6f4e3f6c4e2g4d6", // -2
+    "e6f6d3e3e2d6c7c3", // -2
+    "d3c5e6f3b6f5g4c4", // +0
+    "c4e3f3c5d3g2c6c3", // -2
+    "e6f6g6e3c3c4b3e7", // -2
+    "e6f6g6e7d3e3f4g5", // -2
+    "c4e3f6e6f2g6f4b3", // -2
+    "e6f6c4e7f7e3d7f8", // -2
+    "f5d6c5b4d3e3e2d2", // -2
+    "f5f6d3e3f2f4g7c5", // -2
+    "c4e3f2c5f4b3e6f3", // +0
+    "c4e3f2e2f5g2d3c5", // -2
+    "f5f4e3f2f3d6f1g4", // +0
+    "e6f4c3d6d7c6g3g4", // +2
+    "d3c5d6c3b4c7d2f4", // -2
+    "f5f6d3f4g5c4g3g4", // -2
+    "d3c3b3d6f6f5f4g3", // +2
+    "f5f4g3d6c4b3c7g5", // -2
+    "f5f4f3d6c3d3c7b2", // +2
+    "d3c3f5d6b2f4g4d2", // -2
+    "c4c3c2f4f3b4b3e3", // +0
+    "e6f6c4c3d3e7g7b3", // +0
+    "d3c3f5f6b3c5c6g5", // +2
+    "e6f6d3e3f2d6d7e2", // +2
+    "c4e3f2e2f3g1e6g3", // -2
+    "d3e3f3e2f4c5e1g4", // -2
+    "d3c3c4c5b5f3e6e3", // -2
+    "e6d6c3f3c6e7f4b6", // -2
+    "f5f6d3c3b3c5g7e3", // +0
+    "e6d6c3f7c5c4d7f4", // -2
+    "d3c3f5d6b2g5g6f3", // -2
+    "d3c3e6f6b3c5c4b2", // -2
+    "e6d6c7f4c4d7c6f5", // -2
+    "f5f4f3d6c3g5e6g2", // -2
+    "d3c5f6d2c2f5e6f4", // -2
+    "f5f4g3e6f7e7c4g4", // +0
+    "f5f6f7f4f3g3h3e3", // +0
+    "d3e3f4c5e2d2c1d1", // +0
+    "d3e3f3c3b3f2f5e6", // -2
+    "e6f4f3d6c6c7c8f2", // -2
+    "f5f4d3f6g5c4b5d2", // +0
+    "c4e3f6e6f2b3d6c3", // +0
+    "f5f6c4c3e6c5b2f7", // +0
+    "f5d6c3f4c5c4g3b2", // +0
+    "e6d6c4f4g4e7f7d3", // -2
+    "d3c3e6f4b2c6g3f7", // +0
+    "c4c3e6f6g6f4c2c5", // -2
+    "f5f4g3f6f3g5d3c2", // -2
+    "e6d6c6f6g6g7f4g5", // +0
+    "e6f6g6d6c6f4d3g7", // +0
+    "c4e3f5b4f3f6e2c5", // +0
+    "d3e3f6e6f2e2f7g2", // +2
+    "d3c5d6c7e6f4f6e3", // -2
+    "d3c5c6e3c4c3f5b5", // -2
+    "f5d6c3g5d7e3f2b2", // +0
+    "e6d6c3f3c7d7c8d3", // +2
+    "d3c5c6c3e6d2b2b4", // +0
+    "d3c5c6c7b6c3c8e3", // -2
+    "d3c5f6f3d6f5g6d2", // +2
+    "c4e3f6e6f3g6e7c3", // +0
+];
+
+/// 同梱の序盤を座標の列として返す
+pub fn suite() -> Vec<Vec<Coord>> {
+    SUITE.iter()
+        .map(|opening| game::parse_transcript(opening).expect("bundled openings are valid"))
+        .collect()
+}
+
+/// 1行に1つ序盤の棋譜が書かれたテキストを読む
+///
+/// 空行と、`#` 以降のコメントは無視する。各序盤は初期局面から打ち直して確かめ、
+/// 読めない行や不正な手を含む行があれば、その行番号とともにエラーを返す。
+pub fn parse(text: &str) -> Result<Vec<Vec<Coord>>, String> {
+    let openings = text.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.split('#').next().unwrap_or("").trim()))
+        .filter(|&(_, line)| !line.is_empty())
+        .map(|(number, line)| {
+            let moves = game::parse_transcript(line).map_err(|err| format!("line {}: {}", number, err))?;
+            Game::new(Manual, Manual).replay(&moves).map_err(|err| format!("line {}: {}", number, err))?;
+            Ok(moves)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if openings.is_empty() {
+        return Err("no openings found".to_string());
+    }
+    Ok(openings)
+}
+
+/// 新しい局面がこの回数続けて出なければ、もう残っていないとみなして諦める
+const GIVE_UP_ATTEMPTS: usize = 10_000;
+
+/// 初期局面から無作為に `plies` 手打った序盤を、`count` 個になるまで作る
+///
+/// 打った後の局面を深さ `depth` で探索し、評価値の絶対値が `threshold` を超えるものは捨てる。
+/// 戻り値は序盤の手順と、その後に手番となる側から見た評価値の組。
+/// `plies` が小さく異なる局面がそれだけ存在しない場合は、見つかった分だけを返すので `count` 個より少なくなる。
+pub fn generate(count: usize, plies: usize, depth: usize, threshold: i8, seed: u64) -> Vec<(Vec<Coord>, i8)> {
+    let mut rng = Rng::new(seed);
+    let engine = AlphaBetaPlayer::new(depth);
+    let mut seen = HashSet::new();
+    let mut openings = Vec::new();
+    let mut attempts = 0;
+    while openings.len() < count && attempts < GIVE_UP_ATTEMPTS {
+        attempts += 1;
+        let mut board = Board::new();
+        let mut piece = Piece::Black;
+        let mut moves = Vec::new();
+        while moves.len() < plies {
+            let candidates = board.moves(piece);
+            if candidates.is_empty() {
+                break;
+            }
+            let mov = &candidates[rng.below(candidates.len())];
+            board.do_move(piece, mov);
+            moves.push(mov.pos);
+            piece = piece.opponent();
+        }
+        if moves.len() < plies || board.moves(piece).is_empty() || !seen.insert(board.clone()) {
+            continue;
+        }
+        attempts = 0;
+        let score = engine.score(piece, &board);
+        if score.abs() <= threshold {
+            openings.push((moves, score));
+        }
+    }
+    openings
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Play;
+    use board::Move;
+    use super::*;
+
+    struct Idle;
+    impl Play for Idle {
+        fn play(&mut self, _piece: Piece, _board: &Board) -> Option<Move> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_suite_is_legal() {
+        let suite = suite();
+        assert_eq!(SUITE.len(), suite.len());
+        for opening in suite {
+            assert_eq!(8, opening.len());
+            let mut game = Game::new(Idle, Idle);
+            assert_eq!(Ok(()), game.replay(&opening));
+        }
+    }
+
+    #[test]
+    fn test_openings_parse() {
+        let openings = parse("# comment\nf5d6c3\n\nf5f6 # diagonal\n").unwrap();
+        assert_eq!(2, openings.len());
+        assert_eq!(vec![Coord(5, 4), Coord(5, 5)], openings[1]);
+        assert!(parse("# nothing\n").is_err());
+        assert!(parse("f5d6z9\n").is_err());
+        assert_eq!(Err("line 3: Black cannot play a1".to_string()), parse("f5d6c3\n\na1a2\n"));
+    }
+
+    #[test]
+    fn test_openings_generate() {
+        let openings = generate(3, 4, 2, 4, 1);
+        assert_eq!(3, openings.len());
+        for (moves, score) in openings {
+            assert_eq!(4, moves.len());
+            assert!(score.abs() <= 4);
+            let mut game = Game::new(Idle, Idle);
+            assert_eq!(Ok(()), game.replay(&moves));
+        }
+    }
+
+    #[test]
+    fn test_openings_generate_exhausted() {
+        // 1手目は4通りしかない
+        let openings = generate(5, 1, 1, 64, 1);
+        assert_eq!(4, openings.len());
+        let mut first: Vec<Coord> = openings.iter().map(|(moves, _)| moves[0]).collect();
+        first.sort_by_key(|pos| pos.index());
+        first.dedup();
+        assert_eq!(4, first.len());
+    }
+}
@@ -0,0 +1,134 @@
+//! 合法手の生成を検証するための perft（指定の深さまでの末端局面の数え上げ）
+//!
+//! 手番に打てる手がなく相手には打てる手があるときは、パスを1手として数える。
+//! 両者とも打てない終局の局面は、指定の深さに届いていなくても末端として1つと数える。
+
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use piece::Piece;
+use coord::Coord;
+use board::{Board, Move};
+
+/// 根に近いこの深さ以上の局面では、子の局面をスレッドに分けて数える
+const PARALLEL_DEPTH: usize = 6;
+
+/// `board` で `turn` の手番から `depth` 手先までの末端局面の数
+///
+/// ```
+/// use reversi::{Board, Piece};
+/// use reversi::perft::perft;
+///
+/// assert_eq!(244, perft(&Board::new(), Piece::Black, 4));
+/// ```
+pub fn perft(board: &Board, turn: Piece, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.moves(turn);
+    if moves.is_empty() {
+        if board.moves(turn.opponent()).is_empty() {
+            return 1;
+        }
+        return perft(board, turn.opponent(), depth - 1);
+    }
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let child = |mov: &Move| {
+        let mut board = board.clone();
+        board.do_move(turn, mov);
+        perft(&board, turn.opponent(), depth - 1)
+    };
+    if depth < PARALLEL_DEPTH {
+        return moves.iter().map(child).sum();
+    }
+    // rayon のない wasm32 では1スレッドで数える
+    #[cfg(not(target_arch = "wasm32"))]
+    let moves = moves.par_iter();
+    #[cfg(target_arch = "wasm32")]
+    let moves = moves.iter();
+    moves.map(child).sum()
+}
+
+/// 根の手ごとに、その手を打ったあとの `depth - 1` 手先までの末端局面の数を返す
+///
+/// 他の実装と数が食い違ったときに、どの手の下で違うのかを絞り込むのに使う。
+/// 根でパスするときは `None` の1要素、終局していれば空を返す。`depth` が 0 のときも空を返す。
+pub fn divide(board: &Board, turn: Piece, depth: usize) -> Vec<(Option<Coord>, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let moves = board.moves(turn);
+    if moves.is_empty() {
+        if board.moves(turn.opponent()).is_empty() {
+            return Vec::new();
+        }
+        return vec![(None, perft(board, turn.opponent(), depth - 1))];
+    }
+    moves.iter().map(|mov| {
+        let mut board = board.clone();
+        board.do_move(turn, mov);
+        (Some(mov.pos), perft(&board, turn.opponent(), depth - 1))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 初期局面からの深さ 1 から 11 までの末端局面の数
+    const INITIAL: [u64; 11] = [4, 12, 56, 244, 1396, 8200, 55092, 390216, 3005288, 24571284, 212258800];
+
+    fn check_initial(depths: ::std::ops::RangeInclusive<usize>) {
+        let board = Board::new();
+        for depth in depths {
+            assert_eq!(INITIAL[depth - 1], perft(&board, Piece::Black, depth), "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn test_perft_initial() {
+        check_initial(1..=8);
+    }
+
+    /// 深さ 9 以降はデバッグビルドでは時間がかかるので、
+    /// `cargo test --release perft -- --ignored` で確かめる
+    #[test]
+    #[ignore]
+    fn test_perft_initial_deep() {
+        check_initial(9..=11);
+    }
+
+    #[test]
+    fn test_perft_endgame_passes() {
+        // 空き 10 の終盤。深さ 9 と 10 の木のいたるところでパスが起き、途中で終局する枝もある
+        let position = "-O-XXXX-OOOOOXOOXOXOXXOXXOXXXOO-XOXXOXO-XOXOOO--XXOOOOOOXO-OOO-- X";
+        let (board, turn) = Board::from_position(position).unwrap();
+        assert_eq!(63508, perft(&board, turn, 9));
+        assert_eq!(66257, perft(&board, turn, 10));
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::new();
+        let divided = divide(&board, Piece::Black, 5);
+        assert_eq!(4, divided.len());
+        // 初期局面は対称なので、どの手の下も同じ数になる
+        assert!(divided.iter().all(|&(pos, nodes)| pos.is_some() && nodes == INITIAL[4] / 4));
+        assert_eq!(INITIAL[4], divided.iter().map(|&(_, nodes)| nodes).sum::<u64>());
+    }
+
+    #[test]
+    fn test_perft_pass_and_end() {
+        // 白には打てる手がなく、黒は c1 に打てる
+        let position = "XO-------------------------------------------------------------- O";
+        let (board, turn) = Board::from_position(position).unwrap();
+        assert_eq!(vec![(None, 1)], divide(&board, turn, 2));
+        // パスのあと黒が c1 に打つと、盤上は黒だけになり終局する
+        assert_eq!(1, perft(&board, turn, 1));
+        assert_eq!(1, perft(&board, turn, 10));
+        let (board, turn) = Board::from_position("XX-------------------------------------------------------------- O").unwrap();
+        assert_eq!(1, perft(&board, turn, 5));
+        assert!(divide(&board, turn, 5).is_empty());
+    }
+}
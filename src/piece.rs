@@ -1,5 +1,5 @@
 /// 石の色を表す列挙型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Piece {
     Black,
     White,
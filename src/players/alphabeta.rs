@@ -1,36 +1,78 @@
+use std::cell::Cell;
 use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
 use piece::Piece;
 use board::{Board, Move};
-use game::Play;
+use clock::Instant;
+use game::{Context, Play, SearchInfo};
+use players::evaluate;
+use players::table::{self, Bound, Entry, Table};
 
-pub struct AlphaBetaPlayer {
-    depth: usize,
+/// 探索を打ち切る条件と、それまでに探索した局面の数
+struct Limit<'a> {
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    nodes: Cell<u64>,
 }
-impl AlphaBetaPlayer {
-    pub fn new(depth: usize) -> Self {
-        AlphaBetaPlayer { depth }
+impl<'a> Limit<'a> {
+    fn new(deadline: Option<Instant>, stop: Option<&'a AtomicBool>) -> Self {
+        Limit { deadline, stop, nodes: Cell::new(0) }
     }
 
-    fn evaluate(&self, piece: Piece, board: &Board) -> i8 {
-        match piece {
-            Piece::Black => board.black as i8 - board.white as i8,
-            Piece::White => board.white as i8 - board.black as i8,
+    fn is_reached(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+}
+
+/// 置換表を持ち、アルファベータ法で探索する
+///
+/// 置換表はスレッド間で共有されるので、先読みのスレッドにも複製して渡すことができる。
+#[derive(Clone)]
+struct Searcher {
+    table: Arc<Table>,
+}
+impl Searcher {
+    fn new(table_size: usize) -> Self {
+        Searcher {
+            table: Arc::new(Table::new(table_size)),
         }
     }
 
-    fn alphabeta(&self, piece: Piece, board: &Board, mut al: i8, be: i8, depth: usize) -> (i8, Option<Move>) {
+    /// `limit` に達した場合は探索を打ち切って None を返す
+    fn alphabeta(&self, piece: Piece, board: &Board, mut al: i8, be: i8, depth: usize, limit: &Limit) -> Option<(i8, Option<Move>)> {
+        if limit.is_reached() {
+            return None;
+        }
+        limit.nodes.set(limit.nodes.get() + 1);
         if depth == 0 {
-            return (self.evaluate(piece, &board), None);
+            return Some((evaluate(piece, board), None));
+        }
+        let mut moves = board.moves(piece);
+        if moves.is_empty() {
+            return Some((evaluate(piece, board), None));
         }
-        let moves = board.moves(piece);
-        if moves.len() == 0 {
-            return (self.evaluate(piece, &board), None);
+        if let Some(entry) = self.table.get(piece, board) {
+            let is_cut = entry.depth >= depth && match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= be,
+                Bound::Upper => entry.score <= al,
+            };
+            let best = moves.iter().find(|mov| Some(mov.pos) == entry.best).cloned();
+            if is_cut && (best.is_some() || entry.best.is_none()) {
+                return Some((entry.score, best));
+            }
+            // 前回の最善手から調べると枝刈りが起きやすい
+            moves.sort_by_key(|mov| Some(mov.pos) != entry.best);
         }
+        let original_al = al;
         let mut best = (-127, None);
         for mov in moves {
             let mut board = board.clone();
             board.do_move(piece, &mov);
-            let (score, _) = self.alphabeta(piece.opponent(), &board, -be, -al, depth - 1);
+            let (score, _) = self.alphabeta(piece.opponent(), &board, -be, -al, depth - 1, limit)?;
             if -score > best.0 {
                 best = (-score, Some(mov));
             }
@@ -39,13 +81,227 @@ impl AlphaBetaPlayer {
                 break;
             }
         }
-        best
+        let bound = if best.0 <= original_al {
+            Bound::Upper
+        } else if best.0 >= be {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(piece, board, Entry {
+            depth,
+            score: best.0,
+            bound,
+            best: best.1.as_ref().map(|mov| mov.pos),
+        });
+        Some(best)
+    }
+
+    /// `max_depth` まで反復深化で探索し、`limit` に達したら完了している最も深い探索の結果を返す
+    ///
+    /// 1段の探索を終えるたびに、その結果を `report` に渡す。
+    fn search(&self, piece: Piece, board: &Board, max_depth: usize, limit: &Limit, report: &dyn Fn(&SearchInfo)) -> Option<Move> {
+        self.table.new_search();
+        let started = Instant::now();
+        let mut best = None;
+        for depth in 1..=max_depth {
+            match self.alphabeta(piece, board, -127, 127, depth, limit) {
+                Some((score, mov)) => {
+                    report(&SearchInfo {
+                        piece,
+                        depth,
+                        score,
+                        best: mov.as_ref().map(|mov| mov.pos),
+                        nodes: limit.nodes.get(),
+                    });
+                    best = mov;
+                },
+                None => break,
+            }
+            // 1段深い探索には今までよりずっと時間がかかるので、残り時間が経過時間より短ければ諦める
+            if let Some(deadline) = limit.deadline {
+                if started + started.elapsed() * 2 > deadline {
+                    break;
+                }
+            }
+        }
+        best.or_else(|| board.moves(piece).into_iter().next())
+    }
+
+    /// 相手の手番の間、相手の手を予想してその後の局面を探索し続ける
+    ///
+    /// 結果は置換表に残り、予想が当たれば次の探索で使われる。
+    fn ponder(&self, piece: Piece, board: &Board, stop: &AtomicBool) {
+        let limit = Limit::new(None, Some(stop));
+        let opponent = piece.opponent();
+        let mut board = board.clone();
+        if let Some(predicted) = self.search(opponent, &board, PREDICTION_DEPTH, &limit, &|_| {}) {
+            board.do_move(opponent, &predicted);
+        }
+        self.search(piece, &board, board.empties() as usize, &limit, &|_| {});
+    }
+}
+
+/// 先読みで相手の手を予想するときの探索の深さ
+const PREDICTION_DEPTH: usize = 4;
+
+/// 先読みをしているスレッド
+struct Pondering {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+pub struct AlphaBetaPlayer {
+    depth: usize,
+    searcher: Searcher,
+    pondering: Option<Pondering>,
+}
+impl AlphaBetaPlayer {
+    pub fn new(depth: usize) -> Self {
+        AlphaBetaPlayer {
+            depth,
+            searcher: Searcher::new(table::DEFAULT_SIZE),
+            pondering: None,
+        }
+    }
+
+    /// 置換表のエントリ数を変える
+    ///
+    /// 2の冪に切り下げて確保する。1エントリは 16 バイトで、既定では `1 << 16` エントリ。
+    pub fn with_table_size(mut self, size: usize) -> Self {
+        self.stop_pondering();
+        self.searcher = Searcher::new(size);
+        self
+    }
+
+    /// 指定の深さで探索した、`piece` から見た局面の評価値を返す
+    pub fn score(&self, piece: Piece, board: &Board) -> i8 {
+        self.searcher.table.new_search();
+        let limit = Limit::new(None, None);
+        let (score, _) = self.searcher.alphabeta(piece, board, -127, 127, self.depth, &limit)
+            .expect("search without limit always completes");
+        score
+    }
+
+    /// 合法手それぞれを指定の深さで探索し、評価値の高い順に返す
+    ///
+    /// 評価値は `piece` から見た石の差。
+    pub fn analyze(&self, piece: Piece, board: &Board) -> Vec<(Move, i8)> {
+        self.searcher.table.new_search();
+        let limit = Limit::new(None, None);
+        let depth = self.depth.saturating_sub(1);
+        let mut scores: Vec<_> = board.moves(piece).into_iter()
+            .map(|mov| {
+                let mut board = board.clone();
+                board.do_move(piece, &mov);
+                let (score, _) = self.searcher.alphabeta(piece.opponent(), &board, -127, 127, depth, &limit)
+                    .expect("search without limit always completes");
+                (mov, -score)
+            })
+            .collect();
+        scores.sort_by_key(|&(_, score)| -score);
+        scores
     }
 }
 impl Play for AlphaBetaPlayer {
     fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
         let depth = self.depth;
-        let (_, mov) = self.alphabeta(piece, &board, -127, 127, depth);
+        self.searcher.table.new_search();
+        let limit = Limit::new(None, None);
+        let (_, mov) = self.searcher.alphabeta(piece, board, -127, 127, depth, &limit)?;
         mov
     }
+
+    /// 持ち時間のある対局では、深さの代わりに時間を予算として探索する
+    ///
+    /// 中断を求められた場合は、それまでに完了した探索の結果を返す。
+    fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+        let (max_depth, deadline) = match ctx.clock {
+            Some(clock) => {
+                let moves_left = (board.empties() as u32).div_ceil(2);
+                (board.empties() as usize, Some(Instant::now() + clock.allot(moves_left)))
+            },
+            None => (self.depth, None),
+        };
+        let limit = Limit::new(deadline, Some(ctx.stop));
+        self.searcher.search(piece, board, max_depth, &limit, &|info| ctx.report(info))
+    }
+
+    fn new_game(&mut self, _piece: Piece, _board: &Board) {
+        self.stop_pondering();
+        self.searcher.table.clear();
+    }
+
+    fn ponder(&mut self, piece: Piece, board: &Board) {
+        self.stop_pondering();
+        let stop = Arc::new(AtomicBool::new(false));
+        let searcher = self.searcher.clone();
+        let board = board.clone();
+        let flag = stop.clone();
+        let handle = thread::spawn(move || searcher.ponder(piece, &board, &flag));
+        self.pondering = Some(Pondering { stop, handle });
+    }
+
+    fn stop_pondering(&mut self) {
+        if let Some(pondering) = self.pondering.take() {
+            pondering.stop.store(true, Ordering::SeqCst);
+            pondering.handle.join().unwrap();
+        }
+    }
+}
+impl Drop for AlphaBetaPlayer {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn test_alphabeta_ponder_fills_table() {
+        let mut player = AlphaBetaPlayer::new(5);
+        let mut board = Board::new();
+        let mov = player.play(Piece::Black, &board).unwrap();
+        board.do_move(Piece::Black, &mov);
+        player.searcher.table.clear();
+
+        player.ponder(Piece::Black, &board);
+        thread::sleep(Duration::from_millis(200));
+        player.stop_pondering();
+        assert!(player.pondering.is_none());
+
+        let limit = Limit::new(None, None);
+        let predicted = player.searcher.search(Piece::White, &board, PREDICTION_DEPTH, &limit, &|_| {}).unwrap();
+        board.do_move(Piece::White, &predicted);
+        assert!(player.searcher.table.get(Piece::Black, &board).is_some(), "predicted position is searched");
+    }
+
+    #[test]
+    fn test_alphabeta_table_keeps_result() {
+        let mut player = AlphaBetaPlayer::new(5);
+        let board = Board::new();
+        let first = player.play(Piece::Black, &board);
+        assert!(!player.searcher.table.is_empty());
+        assert_eq!(first, player.play(Piece::Black, &board));
+    }
+
+    #[test]
+    fn test_alphabeta_small_table() {
+        let (board, turn) = Board::from_position("---X-------X-------X-OX---OOOOOO--OXXXOO-O-X-X-OO----X-------X-- X").unwrap();
+        let small = AlphaBetaPlayer::new(5).with_table_size(1);
+        assert_eq!(AlphaBetaPlayer::new(5).score(turn, &board), small.score(turn, &board));
+    }
+
+    #[test]
+    fn test_alphabeta_analyze() {
+        let player = AlphaBetaPlayer::new(4);
+        let board = Board::new();
+        let scores = player.analyze(Piece::Black, &board);
+        assert_eq!(4, scores.len());
+        assert!(scores.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(player.score(Piece::Black, &board), scores[0].1);
+    }
 }
@@ -1,27 +1,381 @@
-use std::io;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
 use piece::Piece;
 use board::{Board, Move};
-use game::Play;
+use coord::Coord;
+use game::{Context, Outcome, Play, Resignation};
+use coach::Coach;
+use players::AlphaBetaPlayer;
+
+/// ヒントのために探索する深さ
+const HINT_DEPTH: usize = 6;
+
+const HELP: &str = "\
+Commands:
+  d3            Play a move
+  pass          Pass (only when you have no legal moves)
+  undo          Take back your last move
+  hint          Suggest a move
+  board         Show the board and your legal moves
+  save <FILE>   Write the moves so far to FILE
+  resign        Resign this game
+  quit          Resign and stop playing
+  help          Show this help";
+
+/// 人間が入力する1行のコマンド
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Move(Coord),
+    Pass,
+    Undo,
+    Hint,
+    Board,
+    Save(String),
+    Resign,
+    Quit,
+    Help,
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or("").to_ascii_lowercase();
+    let arg = words.next();
+    if words.next().is_some() {
+        return Err(format!("too many arguments: {:?}", line));
+    }
+    let command = match (&name[..], arg) {
+        ("pass", None) => Command::Pass,
+        ("undo", None) => Command::Undo,
+        ("hint", None) => Command::Hint,
+        ("board", None) => Command::Board,
+        ("save", Some(path)) => Command::Save(path.to_string()),
+        ("save", None) => return Err("save needs a file name, e.g. save game.txt".to_string()),
+        ("resign", None) => Command::Resign,
+        ("quit", None) | ("exit", None) => Command::Quit,
+        ("help", None) | ("?", None) => Command::Help,
+        (_, None) if name.len() == 2 => Command::Move(name.parse()?),
+        _ => return Err(format!("unknown command: {:?}", line)),
+    };
+    Ok(command)
+}
+
+fn format_moves(moves: &[Move]) -> String {
+    let moves: Vec<String> = moves.iter().map(|mov| mov.pos.to_string()).collect();
+    moves.join(" ")
+}
+
+/// 標準入力から `d3` のような座標やコマンドを読んで手を打つ人間のプレイヤー
+///
+/// 入力が終わった場合や `quit` と入力された場合は、`Resignation::Quit` で対局を放棄する。
+pub struct Human {
+    input: Box<dyn BufRead + Send>,
+    output: Box<dyn Write + Send>,
+    resignation: Option<Resignation>,
+    takeback: bool,
+    coach: Option<Coach>,
+}
+
+impl Human {
+    pub fn new() -> Self {
+        Human::with_io(BufReader::new(io::stdin()), io::stdout())
+    }
+
+    /// 指定の入出力でやり取りするプレイヤーを作る
+    pub fn with_io<R, W>(input: R, output: W) -> Self
+    where
+        R: BufRead + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Human {
+            input: Box::new(input),
+            output: Box::new(output),
+            resignation: None,
+            takeback: false,
+            coach: None,
+        }
+    }
+
+    /// 手番ごとに候補手を示し、悪手の前に警告するコーチをつける
+    pub fn with_coach(mut self, coach: Coach) -> Self {
+        self.coach = Some(coach);
+        self
+    }
+
+    fn say(&mut self, message: &str) {
+        let _ = writeln!(self.output, "{}", message);
+        let _ = self.output.flush();
+    }
+
+    fn show_moves(&mut self, piece: Piece, moves: &[Move]) {
+        let message = if moves.is_empty() {
+            format!("{:?} has no legal moves. Type 'pass' to pass.", piece)
+        } else {
+            format!("Legal moves: {}", format_moves(moves))
+        };
+        self.say(&message);
+    }
+
+    /// 1行を読む。入力が終わっていれば None
+    fn read_line(&mut self, piece: Piece) -> Option<String> {
+        let _ = write!(self.output, "{:?}> ", piece);
+        let _ = self.output.flush();
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.say("");
+                None
+            },
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+impl Default for Human {
+    fn default() -> Self {
+        Human::new()
+    }
+}
 
-pub struct Human;
 impl Play for Human {
     fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+        let stop = Default::default();
+        let ctx = Context {
+            clock: None,
+            history: &[],
+            stop: &stop,
+            listener: None,
+            takebacks: false,
+        };
+        self.think(piece, board, &ctx)
+    }
+
+    fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+        self.takeback = false;
         let moves = board.moves(piece);
-        if moves.len() == 0 {
-            return None;
-        }
-        for (idx, mov) in moves.iter().enumerate() {
-            println!("{}) {}", idx, mov.pos);
+        self.show_moves(piece, &moves);
+        let analysis = match self.coach {
+            Some(ref coach) if !moves.is_empty() => coach.analyze(piece, board),
+            _ => Vec::new(),
+        };
+        if !analysis.is_empty() {
+            self.say(&format!("Coach: {}", Coach::advice(&analysis)));
         }
+        // 警告を受けたあとに同じ手をもう一度入力すると、そのまま打つ
+        let mut warned = None;
         loop {
-            let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-            let idx: Result<usize, _> = input.trim().parse();
-            if let Ok(idx) = idx {
-                if idx < moves.len() {
-                    return Some(moves[idx].clone());
-                }
+            let line = match self.read_line(piece) {
+                Some(line) => line,
+                None => {
+                    self.resignation = Some(Resignation::Quit);
+                    return None;
+                },
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let command = match parse_command(&line) {
+                Ok(command) => command,
+                Err(err) => {
+                    self.say(&format!("error: {}. Type 'help' for the commands.", err));
+                    continue;
+                },
+            };
+            match command {
+                Command::Move(pos) => match moves.iter().find(|mov| mov.pos == pos) {
+                    Some(mov) => {
+                        let warning = self.coach.as_ref().and_then(|coach| coach.warning(&analysis, pos));
+                        if let (Some(warning), false) = (warning, warned == Some(pos)) {
+                            self.say(&format!("Coach: {}. Enter {} again to play it anyway.", warning, pos));
+                            warned = Some(pos);
+                            continue;
+                        }
+                        if let Some(ref mut coach) = self.coach {
+                            coach.record(ctx.history.len() + 1, &analysis, pos);
+                        }
+                        return Some(mov.clone());
+                    },
+                    None if moves.is_empty() => self.say(&format!("error: {} is not a legal move. You have to pass.", pos)),
+                    None => self.say(&format!("error: {} is not a legal move. Legal moves: {}", pos, format_moves(&moves))),
+                },
+                Command::Pass if moves.is_empty() => return None,
+                Command::Pass => self.say(&format!("error: you cannot pass. Legal moves: {}", format_moves(&moves))),
+                Command::Undo if !ctx.takebacks => self.say("error: undo is not allowed in this game"),
+                Command::Undo if !ctx.history.iter().any(|ply| ply.piece == piece && ply.mov.is_some()) => {
+                    self.say("error: you have no move to undo");
+                },
+                Command::Undo => {
+                    self.takeback = true;
+                    return None;
+                },
+                Command::Hint if moves.is_empty() => self.say("Hint: pass"),
+                Command::Hint => {
+                    let best = AlphaBetaPlayer::new(HINT_DEPTH).analyze(piece, board).into_iter().next();
+                    if let Some((mov, score)) = best {
+                        self.say(&format!("Hint: {} ({:+})", mov.pos, score));
+                    }
+                },
+                Command::Board => {
+                    self.say(&board.to_string());
+                    self.show_moves(piece, &moves);
+                },
+                Command::Save(path) => {
+                    let transcript: String = ctx.history.iter()
+                        .filter_map(|ply| ply.mov.as_ref())
+                        .map(|mov| mov.pos.to_string())
+                        .collect();
+                    match fs::write(&path, format!("{}\n", transcript)) {
+                        Ok(()) => self.say(&format!("Saved {} moves to {}", transcript.len() / 2, path)),
+                        Err(err) => self.say(&format!("error: {}: {}", path, err)),
+                    }
+                },
+                Command::Resign => {
+                    self.resignation = Some(Resignation::Resigned);
+                    return None;
+                },
+                Command::Quit => {
+                    self.resignation = Some(Resignation::Quit);
+                    return None;
+                },
+                Command::Help => self.say(HELP),
             }
         }
     }
+
+    fn game_over(&mut self, _outcome: &Outcome) {
+        if let Some(report) = self.coach.as_ref().map(Coach::report) {
+            self.say(&report);
+        }
+    }
+
+    fn resignation(&mut self) -> Option<Resignation> {
+        self.resignation
+    }
+
+    fn takeback(&mut self) -> bool {
+        self.takeback
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use game::Game;
+    use super::*;
+
+    /// テストで出力を覗くための、複製しても同じ中身を共有するバッファ
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+    impl Output {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn human(input: &str) -> (Human, Output) {
+        let output = Output::default();
+        (Human::with_io(Cursor::new(input.to_string()), output.clone()), output)
+    }
+
+    struct First;
+    impl Play for First {
+        fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+            board.moves(piece).into_iter().next()
+        }
+    }
+
+    #[test]
+    fn test_human_parse_command() {
+        assert_eq!(Ok(Command::Move(Coord(3, 2))), parse_command("d3\n"));
+        assert_eq!(Ok(Command::Move(Coord(3, 2))), parse_command(" D3 "));
+        assert_eq!(Ok(Command::Pass), parse_command("PASS"));
+        assert_eq!(Ok(Command::Save("a.txt".to_string())), parse_command("save a.txt"));
+        assert_eq!(Ok(Command::Quit), parse_command("exit"));
+        assert!(parse_command("save").is_err());
+        assert!(parse_command("z9").is_err());
+        assert!(parse_command("undo now").is_err());
+        assert!(parse_command("0").is_err());
+    }
+
+    #[test]
+    fn test_human_reports_errors() {
+        let (mut player, output) = human("3\ne4\npass\nundo\nd3\n");
+        let mov = player.play(Piece::Black, &Board::new());
+        assert_eq!(Some(Coord(3, 2)), mov.map(|mov| mov.pos));
+        assert_eq!(None, player.resignation());
+        let output = output.text();
+        assert!(output.contains("Legal moves: "));
+        assert!(output.contains("error: unknown command: \"3\""));
+        assert!(output.contains("error: e4 is not a legal move"));
+        assert!(output.contains("error: you cannot pass"));
+        assert!(output.contains("error: undo is not allowed"));
+    }
+
+    #[test]
+    fn test_human_quits_on_eof() {
+        let (mut player, _) = human("hint\n");
+        assert_eq!(None, player.play(Piece::Black, &Board::new()));
+        assert_eq!(Some(Resignation::Quit), player.resignation());
+
+        let (player, _) = human("f5\nresign\n");
+        let mut game = Game::new(player, First);
+        assert!(game.step());
+        assert!(game.step());
+        assert!(!game.step());
+        assert_eq!(Some(Outcome::Resigned(Piece::Black, Resignation::Resigned)), game.outcome());
+    }
+
+    #[test]
+    fn test_human_undo_and_save() {
+        let path = env::temp_dir().join(format!("reversi-human-{}.txt", ::std::process::id()));
+        let input = format!("undo\nf5\nundo\nd3\nsave {}\nquit\n", path.display());
+        let (player, output) = human(&input);
+        let mut game = Game::new(player, First);
+        game.set_takebacks(true);
+        while game.step() {}
+        assert_eq!(Some(Outcome::Resigned(Piece::Black, Resignation::Quit)), game.outcome());
+        assert_eq!("d3", game.history()[0].mov.as_ref().unwrap().pos.to_string());
+        let transcript = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(format!("{}\n", game.transcript()), transcript);
+        assert!(output.text().contains("error: you have no move to undo"));
+        assert!(output.text().contains("Saved 2 moves"));
+    }
+
+    #[test]
+    fn test_human_coach() {
+        // d3 と、それに対する白の手の後で、黒の最も損をする手を打つ
+        let mut board = Board::new();
+        for &piece in &[Piece::Black, Piece::White] {
+            let mov = match piece {
+                Piece::Black => board.moves(piece).into_iter().find(|mov| mov.pos == Coord(3, 2)),
+                Piece::White => First.play(piece, &board),
+            };
+            board.do_move(piece, &mov.unwrap());
+        }
+        let coach = Coach::new(4, 0);
+        let analysis = coach.analyze(Piece::Black, &board);
+        let &(ref worst, loss) = analysis.last().unwrap();
+        assert!(Coach::loss(&analysis, worst.pos).unwrap() > 0, "{:?}", loss);
+        let (player, output) = human(&format!("d3\n{0}\n{0}\nquit\n", worst.pos));
+        let mut game = Game::new(player.with_coach(coach), First);
+        while game.step() {}
+        assert_eq!(4, game.history().len());
+        let output = output.text();
+        assert!(output.contains(&format!("Coach: {}", Coach::advice(&analysis))));
+        assert!(output.contains(&format!("Enter {} again to play it anyway.", worst.pos)));
+        assert!(output.contains(&format!("Coach: your biggest mistakes\n  3. {}", worst.pos)));
+    }
 }
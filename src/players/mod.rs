@@ -1,9 +1,159 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use piece::Piece;
+use board::{Board, Move};
+use clock::{Clock, TimeControl};
+use game::{Context, Play, Ply, SearchInfo, SearchListener};
+
 mod dumb;
+mod random;
 mod negamax;
 mod alphabeta;
+mod table;
 mod human;
 
 pub use self::dumb::DumbPlayer;
+pub use self::random::RandomPlayer;
 pub use self::negamax::NegaMaxPlayer;
 pub use self::alphabeta::AlphaBetaPlayer;
 pub use self::human::Human;
+
+/// 探索するプレイヤーが使う評価関数。`piece` から見た石の差を返す
+///
+/// ```
+/// use reversi::{Board, Piece};
+/// use reversi::players::evaluate;
+///
+/// assert_eq!(0, evaluate(Piece::Black, &Board::new()));
+/// ```
+pub fn evaluate(piece: Piece, board: &Board) -> i8 {
+    match piece {
+        Piece::Black => board.black as i8 - board.white as i8,
+        Piece::White => board.white as i8 - board.black as i8,
+    }
+}
+
+/// 対局の外から、プレイヤーに1手考えさせる
+///
+/// `time_control` を与えるとその持ち時間の時計を渡して考えさせ、None なら持ち時間なしで考えさせる。
+/// 探索の途中経過は `listener` があればそこにも報告する。
+/// 選んだ手と、探索が最後に報告した途中経過を返す。途中経過を報告しないプレイヤーでは None になる。
+pub fn search<P: Play + ?Sized>(
+    player: &mut P,
+    piece: Piece,
+    board: &Board,
+    history: &[Ply],
+    time_control: Option<TimeControl>,
+    stop: &Arc<AtomicBool>,
+    listener: Option<Arc<SearchListener>>,
+) -> (Option<Move>, Option<SearchInfo>) {
+    let clock = time_control.map(Clock::new);
+    let last = Arc::new(Mutex::new(None));
+    let reported = last.clone();
+    let record = move |info: &SearchInfo| {
+        if let Some(ref listener) = listener {
+            listener(info);
+        }
+        *reported.lock().unwrap() = Some(info.clone());
+    };
+    let ctx = Context {
+        clock: clock.as_ref(),
+        history,
+        stop,
+        listener: Some(&record),
+        takebacks: false,
+    };
+    let mov = player.think(piece, board, &ctx);
+    let info = last.lock().unwrap().take();
+    (mov, info)
+}
+
+/// 実行時に選べるプレイヤーの種類
+///
+/// `alphabeta:7` のように、種類の後にコロン区切りで探索の深さを指定できる。
+///
+/// ```
+/// use reversi::{Game, PlayerKind};
+///
+/// let kind: PlayerKind = "alphabeta:3".parse().unwrap();
+/// assert_eq!(PlayerKind::AlphaBeta(3), kind);
+/// let mut game = Game::new(kind.build(1), "random".parse::<PlayerKind>().unwrap().build(2));
+/// while game.step() {}
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerKind {
+    Human,
+    Dumb,
+    Random,
+    NegaMax(usize),
+    AlphaBeta(usize),
+}
+impl PlayerKind {
+    /// この種類のプレイヤーを生成する
+    ///
+    /// `seed` は無作為に手を選ぶプレイヤーのみが使う。
+    pub fn build(&self, seed: u64) -> Box<dyn Play + Send> {
+        match *self {
+            PlayerKind::Human => Box::new(Human::new()),
+            PlayerKind::Dumb => Box::new(DumbPlayer),
+            PlayerKind::Random => Box::new(RandomPlayer::new(seed)),
+            PlayerKind::NegaMax(depth) => Box::new(NegaMaxPlayer::new(depth)),
+            PlayerKind::AlphaBeta(depth) => Box::new(AlphaBetaPlayer::new(depth)),
+        }
+    }
+
+    /// 探索の深さを変えたものを返す。深さを持たない種類はそのまま
+    pub fn with_depth(&self, depth: usize) -> Self {
+        match *self {
+            PlayerKind::NegaMax(_) => PlayerKind::NegaMax(depth),
+            PlayerKind::AlphaBeta(_) => PlayerKind::AlphaBeta(depth),
+            kind => kind,
+        }
+    }
+
+    /// 探索の深さを返す。深さを持たない種類は None
+    pub fn depth(&self) -> Option<usize> {
+        match *self {
+            PlayerKind::NegaMax(depth) | PlayerKind::AlphaBeta(depth) => Some(depth),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for PlayerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlayerKind::Human => write!(f, "human"),
+            PlayerKind::Dumb => write!(f, "dumb"),
+            PlayerKind::Random => write!(f, "random"),
+            PlayerKind::NegaMax(depth) => write!(f, "negamax:{}", depth),
+            PlayerKind::AlphaBeta(depth) => write!(f, "alphabeta:{}", depth),
+        }
+    }
+}
+impl FromStr for PlayerKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let depth = match parts.next() {
+            Some(depth) => match depth.parse() {
+                Ok(depth) if depth > 0 => Some(depth),
+                _ => return Err(format!("invalid depth: {:?}", depth)),
+            },
+            None => None,
+        };
+        match (name, depth) {
+            ("human", None) => Ok(PlayerKind::Human),
+            ("dumb", None) => Ok(PlayerKind::Dumb),
+            ("random", None) => Ok(PlayerKind::Random),
+            ("negamax", depth) => Ok(PlayerKind::NegaMax(depth.unwrap_or(5))),
+            ("alphabeta", depth) => Ok(PlayerKind::AlphaBeta(depth.unwrap_or(7))),
+            ("human", Some(_)) | ("dumb", Some(_)) | ("random", Some(_)) => {
+                Err(format!("player {:?} does not take a depth", name))
+            },
+            _ => Err(format!("unknown player: {:?}", s)),
+        }
+    }
+}
@@ -1,7 +1,28 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
 use rayon::prelude::*;
 use piece::Piece;
 use board::{Board, Move};
-use game::Play;
+use clock::Instant;
+use game::{Context, Play, SearchInfo};
+use players::evaluate;
+
+/// 探索を打ち切る条件と、それまでに探索した局面の数。スレッドに分けた探索からも使う
+struct Limit<'a> {
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    nodes: AtomicU64,
+}
+impl<'a> Limit<'a> {
+    fn new(deadline: Option<Instant>, stop: Option<&'a AtomicBool>) -> Self {
+        Limit { deadline, stop, nodes: AtomicU64::new(0) }
+    }
+
+    fn is_reached(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+}
 
 pub struct NegaMaxPlayer {
     depth: usize,
@@ -11,44 +32,116 @@ impl NegaMaxPlayer {
         NegaMaxPlayer { depth }
     }
 
-    fn evaluate(&self, piece: Piece, board: &Board) -> i8 {
-        match piece {
-            Piece::Black => board.black as i8 - board.white as i8,
-            Piece::White => board.white as i8 - board.black as i8,
+    /// `limit` に達した場合は探索を打ち切って None を返す
+    fn negamax(&self, piece: Piece, board: &Board, depth: usize, limit: &Limit) -> Option<(i8, Option<Move>)> {
+        if limit.is_reached() {
+            return None;
         }
-    }
-
-    fn negamax(&self, piece: Piece, board: &Board, depth: usize) -> (i8, Option<Move>) {
+        limit.nodes.fetch_add(1, Ordering::Relaxed);
         if depth == 0 {
-            return (self.evaluate(piece, &board), None);
+            return Some((evaluate(piece, board), None));
         }
-        let moves = board.moves(piece);
-        moves.into_iter().map(|mov| {
+        let mut best = (-127, None);
+        for mov in board.moves(piece) {
             let mut board = board.clone();
             board.do_move(piece, &mov);
-            let (score, _) = self.negamax(piece.opponent(), &board, depth - 1);
-            (-score, Some(mov))
-        }).max_by_key(|&(score, _)| score).unwrap_or((-127, None))
+            let (score, _) = self.negamax(piece.opponent(), &board, depth - 1, limit)?;
+            if -score >= best.0 {
+                best = (-score, Some(mov));
+            }
+        }
+        Some(best)
     }
 
-    fn negamax_mt(&self, piece: Piece, board: &Board, depth: usize) -> (i8, Option<Move>) {
+    fn negamax_mt(&self, piece: Piece, board: &Board, depth: usize, limit: &Limit) -> Option<(i8, Option<Move>)> {
+        limit.nodes.fetch_add(1, Ordering::Relaxed);
         if depth == 0 {
-            return (self.evaluate(piece, &board), None);
+            return Some((evaluate(piece, board), None));
         }
         let moves = board.moves(piece);
-        let (score, mov) = moves.into_par_iter().map(|mov| {
+        // rayon のない wasm32 では1スレッドで探索する
+        #[cfg(not(target_arch = "wasm32"))]
+        let moves = moves.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let moves = moves.iter();
+        let scores: Vec<(i8, &Move)> = moves.map(|mov| {
             let mut board = board.clone();
-            board.do_move(piece, &mov);
-            let (score, _) = self.negamax(piece.opponent(), &board, depth - 1);
-            (-score, Some(mov))
-        }).max_by_key(|&(score, _)| score).unwrap_or((-127, None));
-        (score, mov.map(Clone::clone))
+            board.do_move(piece, mov);
+            let (score, _) = self.negamax(piece.opponent(), &board, depth - 1, limit)?;
+            Some((-score, mov))
+        }).collect::<Option<_>>()?;
+        let (score, mov) = scores.into_iter().max_by_key(|&(score, _)| score).map_or((-127, None), |(score, mov)| (score, Some(mov)));
+        Some((score, mov.cloned()))
     }
 }
 impl Play for NegaMaxPlayer {
     fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
         let depth = self.depth;
-        let (_, mov) = self.negamax_mt(piece, &board, depth);
-        mov
+        let limit = Limit::new(None, None);
+        self.negamax_mt(piece, board, depth, &limit)?.1
+    }
+
+    /// 持ち時間のある対局や中断を求められたときに備えて、1 から指定の深さまで順に探索する
+    ///
+    /// 1段の探索を終えるたびに、その結果を報告する。
+    /// 時間切れや中断で打ち切った場合は、完了している最も深い探索の結果を返す。
+    fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+        let deadline = ctx.clock.map(|clock| {
+            let moves_left = (board.empties() as u32).div_ceil(2);
+            Instant::now() + clock.allot(moves_left)
+        });
+        let limit = Limit::new(deadline, Some(ctx.stop));
+        let mut best = None;
+        for depth in 1..=self.depth {
+            match self.negamax_mt(piece, board, depth, &limit) {
+                Some((score, mov)) => {
+                    ctx.report(&SearchInfo {
+                        piece,
+                        depth,
+                        score,
+                        best: mov.as_ref().map(|mov| mov.pos),
+                        nodes: limit.nodes.load(Ordering::Relaxed),
+                    });
+                    best = mov;
+                },
+                None => break,
+            }
+        }
+        best.or_else(|| board.moves(piece).into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use clock::{Clock, TimeControl};
+    use super::*;
+
+    fn context<'a>(clock: Option<&'a Clock>, stop: &'a Arc<AtomicBool>) -> Context<'a> {
+        Context { clock, history: &[], stop, listener: None, takebacks: false }
+    }
+
+    #[test]
+    fn test_negamax_think_matches_play() {
+        let board = Board::new();
+        let mut player = NegaMaxPlayer::new(3);
+        let stop = Arc::new(AtomicBool::new(false));
+        let played = player.play(Piece::Black, &board).map(|mov| mov.pos);
+        assert_eq!(played, player.think(Piece::Black, &board, &context(None, &stop)).map(|mov| mov.pos));
+    }
+
+    #[test]
+    fn test_negamax_think_honours_time_and_stop() {
+        let board = Board::new();
+        // 深さ 20 の全幅探索は終わらないので、時間切れか中断で打ち切られなければ戻らない
+        let mut player = NegaMaxPlayer::new(20);
+        let stop = Arc::new(AtomicBool::new(false));
+        let clock = Clock::new(TimeControl::Byoyomi(Duration::from_millis(100)));
+        let started = Instant::now();
+        assert!(player.think(Piece::Black, &board, &context(Some(&clock), &stop)).is_some());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        stop.store(true, Ordering::SeqCst);
+        assert!(player.think(Piece::Black, &board, &context(None, &stop)).is_some());
     }
 }
@@ -0,0 +1,24 @@
+use piece::Piece;
+use board::{Board, Move};
+use game::Play;
+use rng::Rng;
+
+/// 合法手の中から無作為に選んで打つプレイヤー
+pub struct RandomPlayer {
+    rng: Rng,
+}
+impl RandomPlayer {
+    pub fn new(seed: u64) -> Self {
+        RandomPlayer { rng: Rng::new(seed) }
+    }
+}
+impl Play for RandomPlayer {
+    fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+        let moves = board.moves(piece);
+        if moves.is_empty() {
+            return None;
+        }
+        let idx = self.rng.below(moves.len());
+        Some(moves[idx].clone())
+    }
+}
@@ -0,0 +1,208 @@
+//! アルファベータ法の探索で使う、固定長の置換表
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+
+/// 置換表に保存する評価値がどのような値か
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    /// 正確な評価値
+    Exact,
+    /// 真の評価値はこれ以上
+    Lower,
+    /// 真の評価値はこれ以下
+    Upper,
+}
+
+/// 置換表の1エントリ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub depth: usize,
+    pub score: i8,
+    pub bound: Bound,
+    /// その局面での最善手の座標
+    pub best: Option<Coord>,
+}
+
+/// 既定のエントリ数。1エントリは 16 バイトなので 1 MiB になる
+pub const DEFAULT_SIZE: usize = 1 << 16;
+
+/// エントリが使われていることを表すビット
+const OCCUPIED: u64 = 1 << 63;
+
+/// 1つの位置。鍵はハッシュ値とデータの排他的論理和で保存する
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// 局面のハッシュ値で決まる位置に1つずつエントリを置く、固定長の置換表
+///
+/// 別々のスレッドが同じ位置に同時に書いて鍵とデータが食い違っても、読むときに鍵が合わずに
+/// 見つからないだけになるので、ロックを取らずに先読みのスレッドと共有できる。
+/// 位置がふさがっているときは、前の探索で書かれたものか、新しいエントリのほうが深く探索したものなら置き換える。
+pub struct Table {
+    slots: Vec<Slot>,
+    /// 探索のたびに進める世代
+    generation: AtomicU8,
+}
+impl Table {
+    /// `size` 以下で最大の2の冪の数のエントリを持つ表を作る
+    pub fn new(size: usize) -> Self {
+        let size = match size {
+            0 => 1,
+            size => 1 << (usize::BITS - 1 - size.leading_zeros()),
+        };
+        Table {
+            slots: (0..size).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// 新しい探索を始める。これより前に書かれたエントリは深さにかかわらず置き換えられる
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, piece: Piece, board: &Board) -> Option<Entry> {
+        let key = hash(piece, board);
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data & OCCUPIED == 0 || slot.key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        Some(unpack(data).0)
+    }
+
+    pub fn insert(&self, piece: Piece, board: &Board, entry: Entry) {
+        let key = hash(piece, board);
+        let slot = self.slot(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let old = slot.data.load(Ordering::Relaxed);
+        if old & OCCUPIED != 0 && slot.key.load(Ordering::Relaxed) ^ old != key {
+            let (stored, stored_generation) = unpack(old);
+            if stored_generation == generation && stored.depth > entry.depth {
+                return;
+            }
+        }
+        let data = pack(&entry, generation);
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(key ^ data, Ordering::Relaxed);
+    }
+
+    /// すべてのエントリを消す
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.data.store(0, Ordering::Relaxed);
+            slot.key.store(0, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.data.load(Ordering::Relaxed) & OCCUPIED == 0)
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[key as usize & (self.slots.len() - 1)]
+    }
+}
+
+/// splitmix64 の仕上げの混ぜ合わせ
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// 手番と盤面のハッシュ値。石のあるマスごとの乱数の排他的論理和（Zobrist ハッシュ）
+fn hash(piece: Piece, board: &Board) -> u64 {
+    let mut hash = if piece == Piece::White { mix(128) } else { 0 };
+    for idx in 0..64 {
+        let pos = Coord::from_index(idx).expect("index is on the board");
+        match board.matrix()[pos] {
+            Some(Piece::Black) => hash ^= mix(idx as u64 * 2),
+            Some(Piece::White) => hash ^= mix(idx as u64 * 2 + 1),
+            None => {},
+        }
+    }
+    hash
+}
+
+/// 評価値 8 ビット、深さ 8 ビット、種類 2 ビット、最善手 7 ビット、世代 8 ビットに詰める
+fn pack(entry: &Entry, generation: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let best = entry.best.map_or(0, |pos| pos.index() as u64 + 1);
+    OCCUPIED
+        | entry.score as u8 as u64
+        | (entry.depth.min(u8::MAX as usize) as u64) << 8
+        | bound << 16
+        | best << 18
+        | (generation as u64) << 25
+}
+
+fn unpack(data: u64) -> (Entry, u8) {
+    let bound = match (data >> 16) & 0b11 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let best = match (data >> 18) & 0x7f {
+        0 => None,
+        idx => Coord::from_index(idx as i32 - 1),
+    };
+    let entry = Entry {
+        depth: ((data >> 8) & 0xff) as usize,
+        score: data as u8 as i8,
+        bound,
+        best,
+    };
+    (entry, (data >> 25) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(depth: usize, score: i8) -> Entry {
+        Entry { depth, score, bound: Bound::Lower, best: Some(Coord(7, 7)) }
+    }
+
+    #[test]
+    fn test_table_get_insert() {
+        let table = Table::new(1000);
+        assert_eq!(512, table.slots.len());
+        let board = Board::new();
+        assert!(table.is_empty());
+        table.insert(Piece::Black, &board, entry(5, -64));
+        assert_eq!(Some(entry(5, -64)), table.get(Piece::Black, &board));
+        assert_eq!(None, table.get(Piece::White, &board));
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(None, table.get(Piece::Black, &board));
+    }
+
+    #[test]
+    fn test_table_replacement() {
+        // 1エントリしかないので、別の局面は同じ位置を取り合う
+        let table = Table::new(1);
+        let board = Board::new();
+        table.insert(Piece::Black, &board, entry(6, 1));
+        table.insert(Piece::White, &board, entry(3, 2));
+        assert_eq!(Some(entry(6, 1)), table.get(Piece::Black, &board), "deeper entries are kept");
+        table.insert(Piece::Black, &board, entry(2, 3));
+        assert_eq!(Some(entry(2, 3)), table.get(Piece::Black, &board), "the same position is overwritten");
+        table.new_search();
+        table.insert(Piece::White, &board, entry(1, 4));
+        assert_eq!(Some(entry(1, 4)), table.get(Piece::White, &board), "old entries are replaced");
+        assert_eq!(None, table.get(Piece::Black, &board));
+    }
+}
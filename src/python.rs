@@ -0,0 +1,399 @@
+//! `python` フィーチャーで有効になる、Python から使うための拡張モジュール `reversi`
+//!
+//! maturin でビルドする。ルールを Python で書き直さずに、棋譜の生成や学習したモデルの評価ができるよう、
+//! 盤面は numpy の配列として取り出せる。
+//!
+//! ```python
+//! import reversi
+//!
+//! board = reversi.Board()
+//! engine = reversi.Engine("alphabeta:5")
+//! while not board.is_game_over:
+//!     board.do_move(engine.best_move(board, time=0.1))
+//! x = board.features()  # float32, shape (4, 8, 8)
+//! ```
+//!
+//! 手は `"d3"` のような座標か、`a1` を 0、`h8` を 63 とするマスの番号で渡す。パスは None か `"pass"`。
+//! 色は `"black"` と `"white"` の文字列で表す。
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use numpy::{PyArray1, PyArray3, PyArrayMethods};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use piece::Piece;
+use coord::Coord;
+use board::Board;
+use clock::TimeControl;
+use game::{Game, Manual, Play, SearchInfo};
+use players::{self, AlphaBetaPlayer, PlayerKind};
+
+fn piece_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Black => "black",
+        Piece::White => "white",
+    }
+}
+
+/// 座標の文字列かマスの番号を座標として読む。パスは None
+fn to_coord(mov: Option<&Bound<'_, PyAny>>) -> PyResult<Option<Coord>> {
+    let mov = match mov {
+        Some(mov) if !mov.is_none() => mov,
+        _ => return Ok(None),
+    };
+    if let Ok(index) = mov.extract::<i32>() {
+        return match Coord::from_index(index) {
+            Some(pos) => Ok(Some(pos)),
+            None => Err(PyValueError::new_err(format!("invalid square: {}", index))),
+        };
+    }
+    let s: String = mov.extract()?;
+    if s == "pass" {
+        return Ok(None);
+    }
+    s.parse().map(Some).map_err(PyValueError::new_err)
+}
+
+/// 手番と、取り消せるように打った手の履歴を持つ盤面
+#[pyclass(name = "Board", module = "reversi")]
+pub struct PyBoard {
+    game: Game<Manual, Manual>,
+}
+
+#[pymethods]
+impl PyBoard {
+    /// `Board::to_position` の形式の局面から始める。省略すると初期局面から黒の手番で始める
+    #[new]
+    #[pyo3(signature = (position=None))]
+    fn new(position: Option<&str>) -> PyResult<Self> {
+        let game = match position {
+            Some(position) => {
+                let (board, turn) = Board::from_position(position).map_err(PyValueError::new_err)?;
+                Game::from_position(board, turn, Manual, Manual)
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?
+            },
+            None => Game::new(Manual, Manual),
+        };
+        Ok(PyBoard { game })
+    }
+
+    /// 手番の色。終局していれば None
+    #[getter]
+    fn turn(&self) -> Option<&'static str> {
+        match self.game.outcome() {
+            Some(_) => None,
+            None => Some(piece_name(self.game.turn())),
+        }
+    }
+
+    #[getter]
+    fn black(&self) -> u8 {
+        self.game.board().black
+    }
+
+    #[getter]
+    fn white(&self) -> u8 {
+        self.game.board().white
+    }
+
+    #[getter]
+    fn empties(&self) -> u8 {
+        self.game.board().empties()
+    }
+
+    #[getter]
+    fn is_game_over(&self) -> bool {
+        self.game.outcome().is_some()
+    }
+
+    /// 勝った色。引き分けか対局中なら None
+    #[getter]
+    fn winner(&self) -> Option<&'static str> {
+        self.game.outcome().and_then(|outcome| outcome.winner()).map(piece_name)
+    }
+
+    /// 手番の合法手を座標の文字列で返す。打てる手がなければ空になるので、`do_move(None)` でパスする
+    fn moves(&self) -> Vec<String> {
+        if self.game.outcome().is_some() {
+            return Vec::new();
+        }
+        self.game.board().moves(self.game.turn()).into_iter().map(|mov| mov.pos.to_string()).collect()
+    }
+
+    /// 手番の手を打つ。不正な手なら ValueError
+    #[pyo3(signature = (mov=None))]
+    fn do_move(&mut self, mov: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        match to_coord(mov)? {
+            Some(pos) => self.game.apply_at(pos),
+            None => self.game.apply(None),
+        }.map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// 最後の1手を取り消し、その手を返す。パスを取り消した場合は None。取り消す手がなければ IndexError
+    fn undo(&mut self) -> PyResult<Option<String>> {
+        match self.game.undo() {
+            Some(ply) => Ok(ply.mov.map(|mov| mov.pos.to_string())),
+            None => Err(PyIndexError::new_err("no move to undo")),
+        }
+    }
+
+    /// マスの石の色。空きなら None
+    fn __getitem__(&self, square: &Bound<'_, PyAny>) -> PyResult<Option<&'static str>> {
+        match to_coord(Some(square))? {
+            Some(pos) => Ok(self.game.board().matrix()[pos].map(piece_name)),
+            None => Err(PyValueError::new_err("pass is not a square")),
+        }
+    }
+
+    fn to_position(&self) -> String {
+        self.game.board().to_position(self.game.turn())
+    }
+
+    /// パスを省略した `f5d6c3` のような棋譜
+    fn transcript(&self) -> String {
+        self.game.transcript()
+    }
+
+    /// 履歴ごと複製する
+    fn copy(&self) -> Self {
+        let game = self.game.to_history().replay().expect("a game without clocks can always be replayed");
+        PyBoard { game }
+    }
+
+    fn __copy__(&self) -> Self {
+        self.copy()
+    }
+
+    fn __str__(&self) -> String {
+        self.game.board().to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Board({:?})", self.to_position())
+    }
+
+    /// 黒と白の石の配置。uint8 で shape は (2, 8, 8)、添字は (色, 行, 列)
+    fn planes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let matrix = self.game.board().matrix();
+        let planes: Vec<u8> = [Piece::Black, Piece::White].iter()
+            .flat_map(|&piece| (0..64).map(move |index| (matrix[Coord::from_index(index).unwrap()] == Some(piece)) as u8))
+            .collect();
+        PyArray1::from_vec(py, planes).reshape([2, 8, 8])
+    }
+
+    /// 手番から見た特徴量。float32 で shape は (4, 8, 8)
+    ///
+    /// 面はそれぞれ手番の石、相手の石、空きマス、手番の合法手。
+    fn features<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f32>>> {
+        let board = self.game.board();
+        let turn = self.game.turn();
+        let mut features = vec![0.0; 4 * 64];
+        for index in 0..64 {
+            let plane = match board.matrix()[Coord::from_index(index).unwrap()] {
+                Some(piece) if piece == turn => 0,
+                Some(_) => 1,
+                None => 2,
+            };
+            features[plane * 64 + index as usize] = 1.0;
+        }
+        if self.game.outcome().is_none() {
+            for mov in board.moves(turn) {
+                features[3 * 64 + mov.pos.index() as usize] = 1.0;
+            }
+        }
+        PyArray1::from_vec(py, features).reshape([4, 8, 8])
+    }
+}
+
+/// `alphabeta:7` のような `PlayerKind` の表記で選ぶコンピュータプレイヤー
+#[pyclass(name = "Engine", module = "reversi")]
+pub struct PyEngine {
+    kind: PlayerKind,
+    player: Mutex<Box<dyn Play + Send>>,
+    stop: Arc<AtomicBool>,
+    info: Mutex<Option<SearchInfo>>,
+}
+
+#[pymethods]
+impl PyEngine {
+    /// `seed` は無作為に手を選ぶエンジンのみが使う
+    #[new]
+    #[pyo3(signature = (kind="alphabeta", seed=0))]
+    fn new(kind: &str, seed: u64) -> PyResult<Self> {
+        let kind: PlayerKind = kind.parse().map_err(PyValueError::new_err)?;
+        if kind == PlayerKind::Human {
+            return Err(PyValueError::new_err("a human player cannot be an engine"));
+        }
+        Ok(PyEngine {
+            kind,
+            player: Mutex::new(kind.build(seed)),
+            stop: Arc::new(AtomicBool::new(false)),
+            info: Mutex::new(None),
+        })
+    }
+
+    /// 手番の手を考えて座標の文字列で返す。打てる手がなければ None
+    ///
+    /// `time` を秒で与えるとおよそその時間で探索を打ち切り、省略するとエンジンの深さまで探索する。
+    /// 探索の間は GIL を手放すので、ほかのスレッドから `stop` を呼べる。
+    #[pyo3(signature = (board, time=None))]
+    fn best_move(&self, py: Python<'_>, board: &PyBoard, time: Option<f64>) -> PyResult<Option<String>> {
+        let time = match time {
+            Some(secs) if secs > 0.0 && secs.is_finite() => Some(Duration::from_millis((secs * 1000.0) as u64)),
+            Some(secs) => return Err(PyValueError::new_err(format!("invalid time: {}", secs))),
+            None => None,
+        };
+        let game = &board.game;
+        if game.outcome().is_some() {
+            return Ok(None);
+        }
+        let (mov, info) = py.detach(|| {
+            self.stop.store(false, Ordering::SeqCst);
+            let mut player = self.player.lock().unwrap();
+            players::search(&mut **player, game.turn(), game.board(), game.history(), time.map(TimeControl::Byoyomi), &self.stop, None)
+        });
+        *self.info.lock().unwrap() = info;
+        Ok(mov.map(|mov| mov.pos.to_string()))
+    }
+
+    /// 探索中の `best_move` をすぐに戻らせる
+    fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// 最後の探索が報告した `{"depth": 5, "score": 2, "best": "d3"}`。報告がなければ None
+    fn search_info<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let info = match *self.info.lock().unwrap() {
+            Some(ref info) => info.clone(),
+            None => return Ok(None),
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("depth", info.depth)?;
+        dict.set_item("score", info.score)?;
+        dict.set_item("best", info.best.map(|pos| pos.to_string()))?;
+        Ok(Some(dict))
+    }
+
+    /// 合法手それぞれの評価値（手番から見た石の差）を高い順に返す。`alphabeta` のエンジンのみ
+    fn analyze(&self, py: Python<'_>, board: &PyBoard) -> PyResult<Vec<(String, i8)>> {
+        let depth = match self.kind {
+            PlayerKind::AlphaBeta(depth) => depth,
+            kind => return Err(PyValueError::new_err(format!("{} cannot analyze positions", kind))),
+        };
+        let game = &board.game;
+        if game.outcome().is_some() {
+            return Ok(Vec::new());
+        }
+        let analysis = py.detach(|| AlphaBetaPlayer::new(depth).analyze(game.turn(), game.board()));
+        Ok(analysis.into_iter().map(|(mov, score)| (mov.pos.to_string(), score)).collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Engine({:?})", self.kind.to_string())
+    }
+}
+
+/// 座標の文字列をマスの番号にする
+#[pyfunction]
+fn square(coord: &str) -> PyResult<i32> {
+    coord.parse::<Coord>().map(|pos| pos.index()).map_err(PyValueError::new_err)
+}
+
+/// マスの番号を座標の文字列にする
+#[pyfunction]
+fn coord(square: i32) -> PyResult<String> {
+    match Coord::from_index(square) {
+        Some(pos) => Ok(pos.to_string()),
+        None => Err(PyValueError::new_err(format!("invalid square: {}", square))),
+    }
+}
+
+#[pymodule]
+fn reversi(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBoard>()?;
+    m.add_class::<PyEngine>()?;
+    m.add_function(wrap_pyfunction!(self::square, m)?)?;
+    m.add_function(wrap_pyfunction!(self::coord, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_python<F: FnOnce(Python)>(f: F) {
+        Python::initialize();
+        Python::attach(f);
+    }
+
+    #[test]
+    fn test_python_board() {
+        with_python(|py| {
+            let mut board = PyBoard::new(None).unwrap();
+            assert_eq!(Some("black"), board.turn());
+            assert_eq!(vec!["d3", "c4", "f5", "e6"], board.moves());
+            let f5 = "f5".into_pyobject(py).unwrap().into_any();
+            board.do_move(Some(&f5)).unwrap();
+            let d6 = 43i32.into_pyobject(py).unwrap().into_any();
+            board.do_move(Some(&d6)).unwrap();
+            assert!(board.do_move(Some(&d6)).is_err());
+            assert!(board.do_move(None).is_err());
+            assert_eq!("f5d6", board.transcript());
+            assert_eq!(Some("white"), board.__getitem__(&d6).unwrap());
+            let copy = board.copy();
+            assert_eq!(Some("d6".to_string()), board.undo().unwrap());
+            assert_eq!("f5d6", copy.transcript());
+            assert_eq!(Some("white"), board.turn());
+            assert_eq!((4, 1), (board.black(), board.white()));
+            board.undo().unwrap();
+            assert!(board.undo().is_err());
+            assert!(PyBoard::new(Some("XO")).is_err());
+        });
+    }
+
+    #[test]
+    fn test_python_engine() {
+        with_python(|py| {
+            assert!(PyEngine::new("human", 0).is_err());
+            assert!(PyEngine::new("chess", 0).is_err());
+            let engine = PyEngine::new("alphabeta:3", 0).unwrap();
+            let mut board = PyBoard::new(None).unwrap();
+            assert!(engine.search_info(py).unwrap().is_none());
+            let best = engine.best_move(py, &board, None).unwrap().unwrap();
+            let info = engine.search_info(py).unwrap().unwrap();
+            assert_eq!(3, info.get_item("depth").unwrap().unwrap().extract::<usize>().unwrap());
+            let analysis = engine.analyze(py, &board).unwrap();
+            assert_eq!(4, analysis.len());
+            let best = best.into_pyobject(py).unwrap().into_any();
+            board.do_move(Some(&best)).unwrap();
+            assert!(PyEngine::new("random", 0).unwrap().analyze(py, &board).is_err());
+            assert_eq!(19, square("d3").unwrap());
+            assert_eq!("h8", coord(63).unwrap());
+            assert!(coord(64).is_err());
+        });
+    }
+
+    #[test]
+    fn test_python_engine_budget() {
+        with_python(|py| {
+            // 深さ 20 の negamax は、時間の予算か中断がなければ戻らない
+            let engine = PyEngine::new("negamax:20", 0).unwrap();
+            let board = PyBoard::new(None).unwrap();
+            let started = ::std::time::Instant::now();
+            assert!(engine.best_move(py, &board, Some(0.1)).unwrap().is_some());
+            assert!(started.elapsed() < Duration::from_secs(5));
+            let done = AtomicBool::new(false);
+            ::std::thread::scope(|scope| {
+                // 探索を始めるときに中断のフラグが戻されるので、戻るまで繰り返し止める
+                scope.spawn(|| while !done.load(Ordering::SeqCst) {
+                    ::std::thread::sleep(Duration::from_millis(50));
+                    engine.stop();
+                });
+                assert!(engine.best_move(py, &board, None).unwrap().is_some());
+                done.store(true, Ordering::SeqCst);
+            });
+        });
+    }
+}
@@ -0,0 +1,468 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use piece::Piece;
+use board::{Board, Matrix, Move};
+use coord::Coord;
+use clock::TimeControl;
+use game::{Context, Game, IllegalMovePolicy, MoveError, Outcome, Play, Ply, Resignation};
+use players::{self, PlayerKind};
+
+/// 接続して最初にやり取りする挨拶。プロトコルを変えたら番号を上げる
+const HELLO: &str = "hello reversi 1";
+
+/// 挨拶の応答を待つ時間
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 持ち時間がない対局で、相手の手を待つ時間
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 持ち時間のある対局で、残り時間に加えて待つ時間。通信の遅れを見込む
+const GRACE: Duration = Duration::from_secs(2);
+
+/// TCP でつながった相手に手を考えさせるプレイヤー
+///
+/// 行単位のテキストで、相手に盤面と手番を送って手を待つ。ホストから相手へは次の行を送る。
+///
+/// * `new <black|white> <盤面>` - 対局の開始と相手の色
+/// * `turn <局面> [<残り秒数>]` - 相手の手番。局面は `Board::to_position` の形式
+/// * `moved <d3|pass>` - こちらが打った手
+/// * `rejected <理由>` - 相手の手が不正だった。続けて `turn` を送り直す
+/// * `over <結果>` - 対局の終わり
+///
+/// 相手は `turn` に対して `move d3`、`move pass` か `resign` を返す。
+/// 接続が切れたり、時間内に応答がなかったりした場合は、相手が対局を放棄したものとする。
+pub struct RemotePlayer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    timeout: Duration,
+    resignation: Option<Resignation>,
+}
+
+impl RemotePlayer {
+    /// 相手と挨拶を交わして接続を確かめる
+    ///
+    /// `timeout` は持ち時間のない対局で1手を待つ時間。
+    pub fn new(stream: TcpStream, timeout: Duration) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut player = RemotePlayer {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            timeout,
+            resignation: None,
+        };
+        writeln!(player.writer, "{}", HELLO)?;
+        let line = player.receive(Instant::now() + HANDSHAKE_TIMEOUT).map_err(|reason| {
+            io::Error::new(io::ErrorKind::ConnectionAborted, format!("the peer {}", reason))
+        })?;
+        if line != HELLO {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected greeting: {:?}", line)));
+        }
+        Ok(player)
+    }
+
+    /// 1行を送る。送れなければ接続が切れたものとする
+    fn send(&mut self, line: &str) {
+        if self.resignation == Some(Resignation::Disconnected) {
+            return;
+        }
+        if writeln!(self.writer, "{}", line).is_err() {
+            self.resignation = Some(Resignation::Disconnected);
+        }
+    }
+
+    /// `deadline` までに空でない1行を受け取る
+    fn receive(&mut self, deadline: Instant) -> Result<String, Resignation> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout == Duration::from_secs(0) {
+                return Err(Resignation::NoResponse);
+            }
+            self.reader.get_ref().set_read_timeout(Some(timeout)).map_err(|_| Resignation::Disconnected)?;
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Err(Resignation::Disconnected),
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => return Ok(line.trim().to_string()),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut => {
+                    return Err(Resignation::NoResponse);
+                },
+                Err(_) => return Err(Resignation::Disconnected),
+            }
+        }
+    }
+}
+
+impl Play for RemotePlayer {
+    fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+        let stop = Arc::new(AtomicBool::new(false));
+        players::search(self, piece, board, &[], None, &stop, None).0
+    }
+
+    fn think(&mut self, piece: Piece, board: &Board, ctx: &Context) -> Option<Move> {
+        let (limit, turn) = match ctx.clock {
+            Some(clock) => (
+                clock.remaining() + GRACE,
+                format!("turn {} {:.3}", board.to_position(piece), clock.remaining().as_secs_f64()),
+            ),
+            None => (self.timeout, format!("turn {}", board.to_position(piece))),
+        };
+        let deadline = Instant::now() + limit;
+        self.send(&turn);
+        while self.resignation.is_none() {
+            let line = match self.receive(deadline) {
+                Ok(line) => line,
+                // 持ち時間を使い切った場合は、時間切れとして `Game` に負けを判定させる
+                Err(Resignation::NoResponse) if ctx.clock.is_some() => return None,
+                Err(reason) => {
+                    self.resignation = Some(reason);
+                    return None;
+                },
+            };
+            let reply: Vec<&str> = line.split_whitespace().collect();
+            let pos = match reply[..] {
+                ["resign"] => {
+                    self.resignation = Some(Resignation::Resigned);
+                    return None;
+                },
+                ["move", "pass"] => return None,
+                ["move", pos] => pos.parse::<Coord>(),
+                _ => Err(format!("unknown reply: {:?}", line)),
+            };
+            // 座標を合法手に直せない場合は、`Game` に渡さずにこの場で打ち直させる
+            let err = match pos {
+                Ok(pos) => match board.moves(piece).into_iter().find(|mov| mov.pos == pos) {
+                    Some(mov) => return Some(mov),
+                    None => MoveError::IllegalMove(piece, pos).to_string(),
+                },
+                Err(err) => err,
+            };
+            self.send(&format!("rejected {}", err));
+            self.send(&turn);
+        }
+        None
+    }
+
+    fn new_game(&mut self, piece: Piece, board: &Board) {
+        let color = match piece {
+            Piece::Black => "black",
+            Piece::White => "white",
+        };
+        self.send(&format!("new {} {}", color, board.matrix().to_position_string()));
+    }
+
+    fn opponent_moved(&mut self, ply: &Ply, _board: &Board) {
+        self.send(&format!("moved {}", format_move(ply.mov.as_ref())));
+    }
+
+    fn move_rejected(&mut self, err: &MoveError) {
+        self.send(&format!("rejected {}", err));
+    }
+
+    fn game_over(&mut self, outcome: &Outcome) {
+        self.send(&format!("over {}", outcome));
+    }
+
+    fn resignation(&mut self) -> Option<Resignation> {
+        self.resignation
+    }
+}
+
+fn format_move(mov: Option<&Move>) -> String {
+    match mov {
+        Some(mov) => mov.pos.to_string(),
+        None => "pass".to_string(),
+    }
+}
+
+/// `host` で待ち受ける対局の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// こちらが持つ色
+    pub color: Piece,
+    /// こちらで手を考えるプレイヤー
+    pub player: PlayerKind,
+    pub time_control: Option<TimeControl>,
+    /// 持ち時間がない場合に、相手の1手を待つ時間
+    pub timeout: Duration,
+}
+
+/// 接続してきた相手と1局を打ち、結果を返す
+///
+/// 対局はこちらの `Game` で進め、盤面と結果を `output` に書く。
+pub fn host<W: Write>(settings: &Settings, listener: &TcpListener, mut output: W) -> io::Result<Outcome> {
+    let (stream, addr) = listener.accept()?;
+    let remote: Box<dyn Play + Send> = Box::new(RemotePlayer::new(stream, settings.timeout)?);
+    writeln!(output, "{} joined", addr)?;
+    let local = settings.player.build(0);
+    let (black, white) = match settings.color {
+        Piece::Black => (local, remote),
+        Piece::White => (remote, local),
+    };
+    let mut game = Game::new(black, white);
+    if let Some(control) = settings.time_control {
+        game.set_time_control(control);
+    }
+    // 打ち直しは `RemotePlayer` が相手とやり取りするが、不正なパスはここで数回まで許す
+    game.set_illegal_move_policy(IllegalMovePolicy::Retry(3));
+    loop {
+        writeln!(output, "{}", game.board())?;
+        writeln!(output, "Turn: {:?}", game.turn())?;
+        if !game.step() {
+            break;
+        }
+    }
+    let outcome = game.outcome().expect("the game is over");
+    writeln!(output, "{}", game.board())?;
+    writeln!(output, "{}", outcome)?;
+    Ok(outcome)
+}
+
+/// `RemotePlayer` の相手として、ホストに手を送る側
+///
+/// 盤面はホストから送られる局面で合わせ、その間の手は自分でも打って対局者に知らせる。
+pub struct Peer<P> {
+    player: P,
+    color: Piece,
+    board: Board,
+    history: Vec<Ply>,
+}
+
+impl<P: Play> Peer<P> {
+    pub fn new(player: P) -> Self {
+        Peer {
+            player,
+            color: Piece::Black,
+            board: Board::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// ホストからの1行を処理し、ホストに返す行があれば返す
+    ///
+    /// 盤面や相手の手など、人に見せる内容は `output` に書く。
+    pub fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> Result<Option<String>, String> {
+        let (command, args) = match line.find(' ') {
+            Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+            None => (line, ""),
+        };
+        match command {
+            "hello" if line == HELLO => Ok(Some(HELLO.to_string())),
+            "hello" => Err(format!("unsupported protocol: {:?}", line)),
+            "new" => {
+                let (color, matrix) = match args.find(' ') {
+                    Some(idx) => (&args[..idx], &args[idx + 1..]),
+                    None => return Err(format!("invalid new game: {:?}", args)),
+                };
+                self.color = match color {
+                    "black" => Piece::Black,
+                    "white" => Piece::White,
+                    _ => return Err(format!("invalid color: {:?}", color)),
+                };
+                self.board = Board::from_matrix(matrix.parse::<Matrix>()?);
+                self.history.clear();
+                self.player.new_game(self.color, &self.board);
+                writeln!(output, "You play {:?}", self.color).map_err(|err| err.to_string())?;
+                Ok(None)
+            },
+            "turn" => {
+                let words: Vec<&str> = args.split_whitespace().collect();
+                let (position, secs) = match words[..] {
+                    [matrix, turn] => (format!("{} {}", matrix, turn), None),
+                    [matrix, turn, secs] => (format!("{} {}", matrix, turn), Some(secs)),
+                    _ => return Err(format!("invalid turn: {:?}", args)),
+                };
+                let (board, turn) = Board::from_position(&position)?;
+                if turn != self.color {
+                    return Err(format!("asked to move for {:?}", turn));
+                }
+                let time_control = match secs {
+                    Some(secs) => match secs.parse::<f64>() {
+                        Ok(secs) if secs >= 0.0 && secs.is_finite() => {
+                            Some(TimeControl::Byoyomi(Duration::from_secs_f64(secs)))
+                        },
+                        _ => return Err(format!("invalid remaining time: {:?}", secs)),
+                    },
+                    None => None,
+                };
+                self.board = board;
+                writeln!(output, "{}", self.board).map_err(|err| err.to_string())?;
+                let stop = Arc::new(AtomicBool::new(false));
+                let (mov, _) = players::search(&mut self.player, self.color, &self.board, &self.history, time_control, &stop, None);
+                if self.player.resignation().is_some() {
+                    return Ok(Some("resign".to_string()));
+                }
+                let reply = format!("move {}", format_move(mov.as_ref()));
+                if let Some(ref mov) = mov {
+                    self.board.do_move(self.color, mov);
+                }
+                self.history.push(Ply { piece: self.color, mov });
+                Ok(Some(reply))
+            },
+            "moved" => {
+                let piece = self.color.opponent();
+                let mov = match args {
+                    "pass" => None,
+                    pos => {
+                        let pos: Coord = pos.parse()?;
+                        let mov = self.board.moves(piece).into_iter().find(|mov| mov.pos == pos);
+                        Some(mov.ok_or_else(|| format!("the opponent cannot play {}", pos))?)
+                    },
+                };
+                if let Some(ref mov) = mov {
+                    self.board.do_move(piece, mov);
+                }
+                let ply = Ply { piece, mov };
+                writeln!(output, "{}", ply).map_err(|err| err.to_string())?;
+                self.player.opponent_moved(&ply, &self.board);
+                self.history.push(ply);
+                Ok(None)
+            },
+            "rejected" => {
+                // 自分の手を取り消す。続けて `turn` が送られてくる
+                if self.history.last().is_some_and(|ply| ply.piece == self.color) {
+                    self.history.pop();
+                }
+                writeln!(output, "Rejected: {}", args).map_err(|err| err.to_string())?;
+                Ok(None)
+            },
+            "over" => {
+                writeln!(output, "{}", args).map_err(|err| err.to_string())?;
+                Ok(None)
+            },
+            _ => Err(format!("unknown message: {:?}", line)),
+        }
+    }
+}
+
+/// ホストに接続して対局し、ホストから送られた結果の文字列を返す
+///
+/// 対局が終わる前に接続が切れた場合はエラーになる。
+pub fn join<P: Play, W: Write>(stream: TcpStream, player: P, mut output: W) -> io::Result<String> {
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    let mut peer = Peer::new(player);
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(reply) = peer.execute(line, &mut output).map_err(invalid_data)? {
+            writeln!(writer, "{}", reply)?;
+            writer.flush()?;
+        }
+        if let Some(outcome) = line.strip_prefix("over ") {
+            return Ok(outcome.to_string());
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the host disconnected"))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::thread;
+    use players::DumbPlayer;
+    use super::*;
+
+    fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        (listener, addr)
+    }
+
+    /// 挨拶だけ済ませた生の接続を返す
+    fn connect(addr: &str) -> (BufReader<TcpStream>, TcpStream) {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(HELLO, line.trim());
+        let mut writer = stream;
+        writeln!(writer, "{}", HELLO).unwrap();
+        (reader, writer)
+    }
+
+    fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.trim().to_string()
+    }
+
+    #[test]
+    fn test_remote_game() {
+        let (listener, addr) = listen();
+        let peer = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            join(stream, DumbPlayer, io::sink()).unwrap()
+        });
+        let settings = Settings {
+            color: Piece::White,
+            player: PlayerKind::Dumb,
+            time_control: None,
+            timeout: Duration::from_secs(10),
+        };
+        let outcome = host(&settings, &listener, io::sink()).unwrap();
+        match outcome {
+            Outcome::Finished { .. } => {},
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+        assert_eq!(outcome.to_string(), peer.join().unwrap());
+    }
+
+    #[test]
+    fn test_remote_rejects_illegal_moves() {
+        let (listener, addr) = listen();
+        let peer = thread::spawn(move || {
+            let (mut reader, mut writer) = connect(&addr);
+            assert_eq!(format!("new black {}", Board::new().matrix().to_position_string()), read_line(&mut reader));
+            let turn = read_line(&mut reader);
+            assert_eq!(format!("turn {}", Board::new().to_position(Piece::Black)), turn);
+            writeln!(writer, "move a1").unwrap();
+            assert_eq!("rejected Black cannot play a1", read_line(&mut reader));
+            assert_eq!(turn, read_line(&mut reader));
+            writeln!(writer, "move d3").unwrap();
+            assert!(read_line(&mut reader).starts_with("moved "));
+            read_line(&mut reader);
+            writeln!(writer, "resign").unwrap();
+            read_line(&mut reader)
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let remote = RemotePlayer::new(stream, Duration::from_secs(10)).unwrap();
+        let mut game = Game::new(remote, DumbPlayer);
+        while game.step() {}
+        assert_eq!(Some(Outcome::Resigned(Piece::Black, Resignation::Resigned)), game.outcome());
+        assert_eq!(2, game.history().len());
+        assert_eq!("over White wins (Black resigned)", peer.join().unwrap());
+    }
+
+    #[test]
+    fn test_remote_disconnection_and_timeout() {
+        let (listener, addr) = listen();
+        let peer = thread::spawn(move || {
+            // 1人目は挨拶の後すぐに切断し、2人目は手番が来ても答えない
+            drop(connect(&addr));
+            let (mut reader, _writer) = connect(&addr);
+            while !read_line(&mut reader).starts_with("over") {}
+        });
+        for &(timeout, reason) in &[
+            (Duration::from_secs(10), Resignation::Disconnected),
+            (Duration::from_millis(100), Resignation::NoResponse),
+        ] {
+            let (stream, _) = listener.accept().unwrap();
+            let remote = RemotePlayer::new(stream, timeout).unwrap();
+            let mut game = Game::new(DumbPlayer, remote);
+            while game.step() {}
+            assert_eq!(Some(Outcome::Resigned(Piece::White, reason)), game.outcome());
+        }
+        peer.join().unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+//! `serde` フィーチャーで有効になる、主な型のシリアライズとデシリアライズ
+//!
+//! 座標は `d3`、盤面は `Board::to_position` と同じ 64 文字のように、入れ子の配列ではなく短い文字列で表す。
+//! デシリアライズでは値を検証し、盤面からはみ出す座標や不正な棋譜はエラーにする。
+//! 手は打つ局面がなければ正しいか確かめられないので、座標だけを書き、読むときは [`MoveSeed`] で局面の合法手から作り直す。
+
+use std::fmt::Display;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{DeserializeSeed, Error};
+use piece::Piece;
+use coord::Coord;
+use board::{Board, Matrix, Move};
+use game::{History, Ply};
+
+/// 文字列から読む型をデシリアライズする
+fn parse<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(D::Error::custom)
+}
+
+/// 黒は `"black"`、白は `"white"`
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match *self {
+            Piece::Black => "black",
+            Piece::White => "white",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match &String::deserialize(deserializer)?[..] {
+            "black" => Ok(Piece::Black),
+            "white" => Ok(Piece::White),
+            other => Err(D::Error::custom(format!("invalid piece: {:?}", other))),
+        }
+    }
+}
+
+/// `d3` のような文字列
+impl Serialize for Coord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Coord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        parse(deserializer)
+    }
+}
+
+/// `Matrix::to_position_string` の 64 文字
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_position_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        parse(deserializer)
+    }
+}
+
+/// 盤面の 64 文字。石の数は読むときに数え直す
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.matrix().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Matrix::deserialize(deserializer).map(Board::from_matrix)
+    }
+}
+
+/// 打つ座標の `d3` だけを書く
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.pos.serialize(serializer)
+    }
+}
+
+/// `board` で `piece` が打つ手として、座標から `Move` を読む
+///
+/// 座標が `board.moves(piece)` のどれとも合わなければエラーになる。
+///
+/// ```
+/// # extern crate reversi;
+/// # extern crate serde;
+/// # extern crate serde_json;
+/// use serde::de::DeserializeSeed;
+/// use reversi::{Board, MoveSeed, Piece};
+///
+/// # fn main() {
+/// let board = Board::new();
+/// let seed = MoveSeed { board: &board, piece: Piece::Black };
+/// let mov = seed.deserialize(&mut serde_json::Deserializer::from_str("\"d3\"")).unwrap();
+/// assert_eq!("d3", mov.pos.to_string());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MoveSeed<'a> {
+    pub board: &'a Board,
+    pub piece: Piece,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for MoveSeed<'a> {
+    type Value = Move;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Move, D::Error> {
+        let pos = Coord::deserialize(deserializer)?;
+        self.board.moves(self.piece).into_iter()
+            .find(|mov| mov.pos == pos)
+            .ok_or_else(|| D::Error::custom(format!("{:?} cannot play {}", self.piece, pos)))
+    }
+}
+
+#[derive(Serialize)]
+struct PlyRepr {
+    piece: Piece,
+    /// パスは null
+    #[serde(rename = "move")]
+    mov: Option<Move>,
+}
+
+/// `{"piece": "black", "move": "d3"}`。手と同じく、読むには局面が要るので書くだけにする
+impl Serialize for Ply {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PlyRepr { piece: self.piece, mov: self.mov.clone() }.serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryRepr {
+    /// `Board::to_position` の形式
+    start: String,
+    /// `f5` のような座標か `pass`
+    moves: Vec<String>,
+}
+
+/// `{"start": "---...--- X", "moves": ["f5", "d6", "pass"]}`
+///
+/// 読むときは最初の局面から打ち直し、不正な手があればエラーにする。
+impl Serialize for History {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (ref board, turn) = self.start;
+        let moves = self.moves.iter()
+            .map(|mov| mov.map_or("pass".to_string(), |pos| pos.to_string()))
+            .collect();
+        HistoryRepr { start: board.to_position(turn), moves }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for History {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = HistoryRepr::deserialize(deserializer)?;
+        let start = Board::from_position(&repr.start).map_err(D::Error::custom)?;
+        let moves = repr.moves.iter()
+            .map(|mov| match &mov[..] {
+                "pass" => Ok(None),
+                pos => pos.parse().map(Some),
+            })
+            .collect::<Result<_, _>>()
+            .map_err(D::Error::custom)?;
+        let history = History { start, moves };
+        history.replay().map_err(D::Error::custom)?;
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use game::{Game, Manual};
+    use super::*;
+
+    #[test]
+    fn test_serialize_compact() {
+        assert_eq!("\"black\"", serde_json::to_string(&Piece::Black).unwrap());
+        assert_eq!("\"d3\"", serde_json::to_string(&Coord(3, 2)).unwrap());
+        let position = "---------------------------OX------XO---------------------------";
+        assert_eq!(format!("\"{}\"", position), serde_json::to_string(&Board::new()).unwrap());
+        assert_eq!(format!("\"{}\"", position), serde_json::to_string(&Matrix::new()).unwrap());
+    }
+
+    fn read_move(board: &Board, piece: Piece, json: &str) -> Result<Move, serde_json::Error> {
+        MoveSeed { board, piece }.deserialize(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let board = Board::new();
+        let mov = board.moves(Piece::Black)[0].clone();
+        let ply = Ply { piece: Piece::Black, mov: Some(mov.clone()) };
+        assert_eq!(format!(r#"{{"piece":"black","move":"{}"}}"#, mov.pos), serde_json::to_string(&ply).unwrap());
+        assert_eq!(mov, read_move(&board, Piece::Black, &serde_json::to_string(&mov).unwrap()).unwrap());
+        assert_eq!(board, serde_json::from_str::<Board>(&serde_json::to_string(&board).unwrap()).unwrap());
+        assert_eq!(Coord(7, 7), serde_json::from_str::<Coord>("\"H8\"").unwrap());
+        assert_eq!(Piece::White, serde_json::from_str::<Piece>("\"white\"").unwrap());
+    }
+
+    #[test]
+    fn test_serialize_history() {
+        let mut game = Game::new(Manual, Manual);
+        game.replay(&::game::parse_transcript("f5d6c3").unwrap()).unwrap();
+        let history = game.to_history();
+        let json = serde_json::to_string(&history).unwrap();
+        assert!(json.ends_with(",\"moves\":[\"f5\",\"d6\",\"c3\"]}"));
+        let restored: History = serde_json::from_str(&json).unwrap();
+        assert_eq!(history, restored);
+        assert_eq!("f5d6c3", restored.replay().unwrap().transcript());
+    }
+
+    #[test]
+    fn test_serialize_validates() {
+        assert!(serde_json::from_str::<Coord>("\"i9\"").is_err());
+        assert!(serde_json::from_str::<Piece>("\"red\"").is_err());
+        assert!(serde_json::from_str::<Board>("\"XO\"").is_err());
+        // 手は局面の合法手のときだけ読める
+        let board = Board::new();
+        assert!(read_move(&board, Piece::Black, "\"d3\"").is_ok());
+        assert!(read_move(&board, Piece::White, "\"d3\"").is_err());
+        assert!(read_move(&board, Piece::Black, "\"a1\"").is_err());
+        assert!(read_move(&board, Piece::Black, r#"{"pos":"a1","flips":[0,0,0,0,0,0,0,6]}"#).is_err());
+        let start = Board::new().to_position(Piece::Black);
+        let history = |moves: &str| format!(r#"{{"start":"{}","moves":{}}}"#, start, moves);
+        assert!(serde_json::from_str::<History>(&history(r#"["f5","d6"]"#)).is_ok());
+        assert!(serde_json::from_str::<History>(&history(r#"["f5","f5"]"#)).is_err());
+        assert!(serde_json::from_str::<History>(&history(r#"["pass"]"#)).is_err());
+        assert!(serde_json::from_str::<History>(&history(r#"["z0"]"#)).is_err());
+    }
+}
@@ -0,0 +1,1170 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+use clock::{Clock, TimeControl};
+use game::{self, Game, Manual, MoveError, Outcome, Play, Ply, SearchInfo, SearchListener};
+use json::Json;
+use players::{self, PlayerKind};
+use websocket::{self, Message};
+
+/// これより大きなリクエストの本文は受け付けない
+const MAX_BODY: usize = 1 << 20;
+
+/// 接続してから読み終わるまでに待つ時間
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// エンジンに考えさせるときの既定の時間（秒）
+const DEFAULT_ENGINE_TIME: f64 = 1.0;
+
+/// リクエストで指定できるエンジンの探索の深さの上限
+const MAX_ENGINE_DEPTH: usize = 20;
+
+/// 思考中に時計のイベントを送る間隔
+const TICK: Duration = Duration::from_millis(200);
+
+/// WebSocket の接続が閉じられたかを確かめる間隔
+const POLL: Duration = Duration::from_millis(500);
+
+/// 終わった対局を一覧に残しておく時間
+const FINISHED_MATCH_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// リクエストの来ない `/games` の対局を一覧に残しておく時間
+const IDLE_GAME_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// HTTP リクエストのうち、API で使う部分
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// クエリ文字列を除いたパス
+    pub path: String,
+    /// ヘッダーの名前と値。名前は小文字にそろえてある
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// 指定の名前（小文字）のヘッダーの値を返す
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|header| header.0 == name).map(|header| header.1.as_str())
+    }
+
+    /// WebSocket への切り替えを求めるリクエストかどうか
+    fn is_upgrade(&self) -> bool {
+        self.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+    }
+}
+
+/// API の応答。本文は常に JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: Json::object(vec![("error", message.into())]),
+        }
+    }
+}
+
+/// 対局の様子を WebSocket の購読者に配る
+///
+/// 最新の `state` イベントを覚えておき、途中から購読を始めた相手にもまず盤面を送る。
+#[derive(Clone)]
+struct Channel {
+    inner: Arc<Mutex<Subscribers>>,
+}
+
+struct Subscribers {
+    latest: Option<Json>,
+    senders: Vec<Sender<String>>,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Channel {
+            inner: Arc::new(Mutex::new(Subscribers { latest: None, senders: Vec::new() })),
+        }
+    }
+
+    fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(ref state) = inner.latest {
+            // 受け取り側はまだ手元にあるので、送信は失敗しない
+            sender.send(state.to_string()).unwrap();
+        }
+        inner.senders.push(sender);
+        receiver
+    }
+
+    /// 最新の `state` イベントを返す
+    fn latest(&self) -> Option<Json> {
+        self.inner.lock().unwrap().latest.clone()
+    }
+
+    /// イベントをすべての購読者に送る。切断した購読者は取り除く
+    fn publish(&self, event: Json) {
+        let message = event.to_string();
+        let mut inner = self.inner.lock().unwrap();
+        if event.get("type").and_then(Json::as_str) == Some("state") {
+            inner.latest = Some(event);
+        }
+        inner.senders.retain(|sender| sender.send(message.clone()).is_ok());
+    }
+}
+
+/// `type` を先頭に付けたイベントを作る
+fn event(kind: &str, body: Json) -> Json {
+    let mut fields = vec![("type".to_string(), kind.into())];
+    if let Json::Object(rest) = body {
+        fields.extend(rest);
+    }
+    Json::Object(fields)
+}
+
+fn move_event(ply: &Ply, elapsed: Duration) -> Json {
+    event("move", Json::object(vec![
+        ("piece", piece_name(ply.piece).into()),
+        ("move", ply.mov.as_ref().map(|mov| mov.pos.to_string()).into()),
+        ("elapsed", elapsed.as_secs_f64().into()),
+    ]))
+}
+
+fn search_event(info: &SearchInfo) -> Json {
+    event("search", Json::object(vec![
+        ("piece", piece_name(info.piece).into()),
+        ("depth", info.depth.into()),
+        ("score", info.score.into()),
+        ("move", info.best.map(|pos| pos.to_string()).into()),
+    ]))
+}
+
+/// 手番の側の残り時間を、考え始めてからの経過時間だけ減らして送る
+fn clock_event(turn: Piece, remaining: [Option<Duration>; 2], elapsed: Duration) -> Json {
+    let seconds = |piece: Piece, remaining: Option<Duration>| -> Json {
+        remaining
+            .map(|remaining| if piece == turn { remaining.saturating_sub(elapsed) } else { remaining })
+            .map(|remaining| remaining.as_secs_f64())
+            .into()
+    };
+    event("clock", Json::object(vec![
+        ("turn", piece_name(turn).into()),
+        ("elapsed", elapsed.as_secs_f64().into()),
+        ("black", seconds(Piece::Black, remaining[0])),
+        ("white", seconds(Piece::White, remaining[1])),
+    ]))
+}
+
+/// 手を外部から与える対局と、その様子の配信先
+struct Session {
+    game: Game<Manual, Manual>,
+    channel: Channel,
+}
+
+/// 一覧に置いた対局と、最後にその対局へのリクエストを受けた時刻
+struct StoredGame {
+    session: Arc<Mutex<Session>>,
+    used: Instant,
+}
+
+/// サーバー上でエンジン同士が戦っている対局
+struct Match {
+    channel: Channel,
+    /// 対局を打ち切るよう求めるフラグ
+    abort: Arc<AtomicBool>,
+    /// 思考中のエンジンの探索を止めるフラグ
+    stop: Arc<AtomicBool>,
+    /// 対局が終わった時刻。進行中なら None
+    finished: Arc<Mutex<Option<Instant>>>,
+}
+impl Match {
+    fn is_running(&self) -> bool {
+        self.finished.lock().unwrap().is_none()
+    }
+
+    /// 終わってから `FINISHED_MATCH_TTL` が過ぎたか
+    fn is_expired(&self, now: Instant) -> bool {
+        self.finished.lock().unwrap().is_some_and(|finished| now.duration_since(finished) >= FINISHED_MATCH_TTL)
+    }
+}
+
+type EngineGame = Game<Box<dyn Play + Send>, Box<dyn Play + Send>>;
+
+/// 対局の一覧を持ち、リクエストを処理する
+///
+/// 複製してもすべての複製が同じ対局を共有する。
+/// 対局は `IDLE_GAME_TTL` の間リクエストが来ないと、
+/// エンジン同士の対局は終わってから `FINISHED_MATCH_TTL` が過ぎると一覧から消える。
+#[derive(Clone)]
+pub struct Api {
+    games: Arc<Mutex<HashMap<u64, StoredGame>>>,
+    matches: Arc<Mutex<HashMap<u64, Match>>>,
+    next_id: Arc<Mutex<u64>>,
+    max_games: usize,
+    max_matches: usize,
+}
+
+impl Api {
+    pub fn new() -> Self {
+        let settings = Settings::default();
+        Api {
+            games: Arc::new(Mutex::new(HashMap::new())),
+            matches: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(Mutex::new(1)),
+            max_games: settings.max_games,
+            max_matches: settings.max_matches,
+        }
+    }
+
+    /// 一覧に置ける `/games` の対局の数を変える。これを超える対局の作成は 503 で断る
+    pub fn with_max_games(mut self, max_games: usize) -> Self {
+        self.max_games = max_games;
+        self
+    }
+
+    /// 同時に進められるエンジン同士の対局の数を変える。これを超える対局の開始は 503 で断る
+    pub fn with_max_matches(mut self, max_matches: usize) -> Self {
+        self.max_matches = max_matches;
+        self
+    }
+
+    /// 終わってから時間の経った対局を一覧から消す
+    fn evict_matches(matches: &mut HashMap<u64, Match>) {
+        let now = Instant::now();
+        matches.retain(|_, found| !found.is_expired(now));
+    }
+
+    /// しばらくリクエストの来ていない対局を一覧から消す
+    fn evict_games(games: &mut HashMap<u64, StoredGame>) {
+        let now = Instant::now();
+        games.retain(|_, stored| now.duration_since(stored.used) < IDLE_GAME_TTL);
+    }
+
+    /// 対局を探し、使われた時刻を更新する
+    fn session(&self, id: u64) -> Option<Arc<Mutex<Session>>> {
+        let mut games = self.games.lock().unwrap();
+        Api::evict_games(&mut games);
+        let stored = games.get_mut(&id)?;
+        stored.used = Instant::now();
+        Some(stored.session.clone())
+    }
+
+    fn next_id(&self) -> u64 {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id - 1
+    }
+
+    /// リクエストを振り分けて処理する
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+        let body = if request.body.trim().is_empty() {
+            Json::Object(Vec::new())
+        } else {
+            match Json::parse(&request.body) {
+                Ok(body) => body,
+                Err(err) => return Response::error(400, &format!("invalid JSON: {}", err)),
+            }
+        };
+        match (request.method.as_str(), &segments[..]) {
+            ("GET", ["games"]) => {
+                let mut games = self.games.lock().unwrap();
+                Api::evict_games(&mut games);
+                let mut ids: Vec<u64> = games.keys().cloned().collect();
+                ids.sort();
+                Response::ok(Json::object(vec![("games", ids.into())]))
+            },
+            ("POST", ["games"]) => self.create(&body),
+            ("GET", ["matches"]) => {
+                let mut matches = self.matches.lock().unwrap();
+                Api::evict_matches(&mut matches);
+                let mut ids: Vec<u64> = matches.keys().cloned().collect();
+                ids.sort();
+                Response::ok(Json::object(vec![("matches", ids.into())]))
+            },
+            ("POST", ["matches"]) => self.start_match(&body),
+            (_, [_, _, "ws"]) => Response::error(400, "expected a WebSocket upgrade"),
+            (method, ["matches", id]) => {
+                let mut matches = self.matches.lock().unwrap();
+                Api::evict_matches(&mut matches);
+                let (id, found) = match id.parse().ok().and_then(|id| matches.get(&id).map(|found| (id, found))) {
+                    Some(found) => found,
+                    None => return Response::error(404, "no such match"),
+                };
+                match method {
+                    "GET" => Response::ok(found.channel.latest().unwrap_or(Json::Null)),
+                    // 対局は次の手が終わったところで打ち切られる
+                    "DELETE" => {
+                        found.abort.store(true, Ordering::SeqCst);
+                        found.stop.store(true, Ordering::SeqCst);
+                        Response::ok(Json::object(vec![("id", id.into())]))
+                    },
+                    _ => Response::error(404, "not found"),
+                }
+            },
+            (method, ["games", id, rest @ ..]) => {
+                let id = match id.parse() {
+                    Ok(id) => id,
+                    Err(_) => return Response::error(404, "no such game"),
+                };
+                if method == "DELETE" && rest.is_empty() {
+                    return match self.games.lock().unwrap().remove(&id) {
+                        Some(_) => Response::ok(Json::object(vec![("id", id.into())])),
+                        None => Response::error(404, "no such game"),
+                    };
+                }
+                let shared = match self.session(id) {
+                    Some(shared) => shared,
+                    None => return Response::error(404, "no such game"),
+                };
+                // 探索の間は対局をロックしないので、ここでは取らない
+                if method == "POST" && rest == ["engine"] {
+                    return think(id, &shared, &body);
+                }
+                let mut session = shared.lock().unwrap();
+                match (method, rest) {
+                    ("GET", []) => Response::ok(state(id, &session.game)),
+                    ("GET", ["moves"]) => Response::ok(Json::object(vec![("moves", legal_moves(&session.game).into())])),
+                    ("POST", ["moves"]) => submit(id, &mut session, &body),
+                    ("GET", ["history"]) => Response::ok(history(&session.game)),
+                    _ => Response::error(404, "not found"),
+                }
+            },
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    /// `/games/{id}/ws` や `/matches/{id}/ws` の配信を購読する。対局がなければ None
+    fn subscribe(&self, path: &str) -> Option<Receiver<String>> {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match segments[..] {
+            ["games", id, "ws"] => {
+                let session = self.session(id.parse().ok()?)?;
+                let channel = session.lock().unwrap().channel.clone();
+                Some(channel.subscribe())
+            },
+            ["matches", id, "ws"] => {
+                let matches = self.matches.lock().unwrap();
+                Some(matches.get(&id.parse().ok()?)?.channel.subscribe())
+            },
+            _ => None,
+        }
+    }
+
+    /// 新しい対局を作る
+    ///
+    /// 本文の `position` で始める局面を、`opening` でそこから打つ手を指定できる。
+    fn create(&self, body: &Json) -> Response {
+        let game = match new_game(body, Manual, Manual) {
+            Ok(game) => game,
+            Err(err) => return Response::error(400, &err),
+        };
+        let mut games = self.games.lock().unwrap();
+        Api::evict_games(&mut games);
+        if games.len() >= self.max_games {
+            return Response::error(503, "too many games are open");
+        }
+        let id = self.next_id();
+        let response = state(id, &game);
+        let channel = Channel::new();
+        channel.publish(event("state", response.clone()));
+        let session = Arc::new(Mutex::new(Session { game, channel }));
+        games.insert(id, StoredGame { session, used: Instant::now() });
+        Response { status: 201, body: response }
+    }
+
+    /// エンジン同士の対局を別のスレッドで始める
+    ///
+    /// 本文の `black` と `white` でエンジンの種類を、`time` で持ち時間を指定できる。
+    /// 局面の指定は対局を作るときと同じ。
+    fn start_match(&self, body: &Json) -> Response {
+        let engine = |key: &str| engine_kind(body.get(key));
+        let time_control = match body.get("time") {
+            None | Some(&Json::Null) => Ok(None),
+            Some(&Json::Number(secs)) => secs.to_string().parse().map(Some),
+            Some(Json::String(control)) => control.parse().map(Some),
+            Some(_) => Err("\"time\" must be a time control such as \"60+1\"".to_string()),
+        };
+        let seed = seed();
+        let game = engine("black").and_then(|black| {
+            let white = engine("white")?;
+            new_game(body, black.build(seed), white.build(seed.wrapping_add(1)))
+        });
+        let (mut game, time_control) = match (game, time_control) {
+            (Ok(game), Ok(time_control)) => (game, time_control),
+            (Err(err), _) | (_, Err(err)) => return Response::error(400, &err),
+        };
+        if let Some(control) = time_control {
+            game.set_time_control(control);
+        }
+        let mut matches = self.matches.lock().unwrap();
+        Api::evict_matches(&mut matches);
+        if matches.values().filter(|found| found.is_running()).count() >= self.max_matches {
+            return Response::error(503, "too many matches are running");
+        }
+        let id = self.next_id();
+        let channel = Channel::new();
+        channel.publish(event("state", state(id, &game)));
+        let listener = channel.clone();
+        game.set_search_listener(Arc::new(move |info: &SearchInfo| listener.publish(search_event(info))));
+        let abort = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(Mutex::new(None));
+        let found = Match {
+            channel: channel.clone(),
+            abort: abort.clone(),
+            stop: game.stop_handle(),
+            finished: finished.clone(),
+        };
+        matches.insert(id, found);
+        thread::spawn(move || {
+            run_match(id, game, &channel, &abort);
+            *finished.lock().unwrap() = Some(Instant::now());
+        });
+        Response { status: 201, body: Json::object(vec![("id", id.into())]) }
+    }
+}
+impl Default for Api {
+    fn default() -> Self {
+        Api::new()
+    }
+}
+
+/// エンジン同士の対局を最後まで進め、1手ごとの様子を配信する
+fn run_match(id: u64, mut game: EngineGame, channel: &Channel, abort: &AtomicBool) {
+    loop {
+        let turn = game.turn();
+        let remaining = [
+            game.clock(Piece::Black).map(Clock::remaining),
+            game.clock(Piece::White).map(Clock::remaining),
+        ];
+        let plies = game.history().len();
+        let started = Instant::now();
+        let done = AtomicBool::new(false);
+        let is_continued = thread::scope(|scope| {
+            let ticker = scope.spawn(|| loop {
+                thread::park_timeout(TICK);
+                if done.load(Ordering::SeqCst) {
+                    break;
+                }
+                channel.publish(clock_event(turn, remaining, started.elapsed()));
+            });
+            let is_continued = game.step();
+            done.store(true, Ordering::SeqCst);
+            ticker.thread().unpark();
+            is_continued
+        });
+        if let Some(ply) = game.history().get(plies) {
+            channel.publish(move_event(ply, started.elapsed()));
+        }
+        channel.publish(event("state", state(id, &game)));
+        if !is_continued {
+            break;
+        }
+        if abort.load(Ordering::SeqCst) {
+            channel.publish(event("aborted", Json::Object(Vec::new())));
+            break;
+        }
+    }
+}
+
+/// 本文の `position` と `opening` に従って対局を作る
+fn new_game<P1: Play, P2: Play>(body: &Json, black: P1, white: P2) -> Result<Game<P1, P2>, String> {
+    let (board, turn) = match body.get("position").and_then(Json::as_str) {
+        Some(position) => Board::from_position(position)?,
+        None => (Board::new(), Piece::Black),
+    };
+    let mut game = Game::from_position(board, turn, black, white)
+        .map_err(|err| format!("invalid position: {}", err))?;
+    if let Some(opening) = body.get("opening").and_then(Json::as_str) {
+        game::parse_transcript(opening)
+            .and_then(|moves| game.replay(&moves).map_err(|err| err.to_string()))
+            .map_err(|err| format!("invalid opening: {}", err))?;
+    }
+    Ok(game)
+}
+
+fn seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos() as u64).unwrap_or(0)
+}
+
+fn piece_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Black => "black",
+        Piece::White => "white",
+    }
+}
+
+fn legal_moves<P1: Play, P2: Play>(game: &Game<P1, P2>) -> Vec<String> {
+    if game.outcome().is_some() {
+        return Vec::new();
+    }
+    game.board().moves(game.turn()).iter().map(|mov| mov.pos.to_string()).collect()
+}
+
+fn outcome(outcome: &Outcome) -> Json {
+    let (black, white) = match *outcome {
+        Outcome::Finished { black, white } => (Json::from(black), Json::from(white)),
+        _ => (Json::Null, Json::Null),
+    };
+    Json::object(vec![
+        ("winner", outcome.winner().map(piece_name).into()),
+        ("black", black),
+        ("white", white),
+        ("description", outcome.to_string().into()),
+    ])
+}
+
+/// 盤面や手番など、対局の今の状態
+fn state<P1: Play, P2: Play>(id: u64, game: &Game<P1, P2>) -> Json {
+    let board = game.board();
+    let matrix = board.matrix();
+    let grid: Vec<Json> = (0..matrix.size() as i8)
+        .map(|y| {
+            let row: Vec<Json> = (0..matrix.size() as i8)
+                .map(|x| matrix[Coord(x, y)].map(piece_name).into())
+                .collect();
+            Json::Array(row)
+        })
+        .collect();
+    let is_over = game.outcome().is_some();
+    let clocks = match (game.clock(Piece::Black), game.clock(Piece::White)) {
+        (Some(black), Some(white)) => Json::object(vec![
+            ("black", black.remaining().as_secs_f64().into()),
+            ("white", white.remaining().as_secs_f64().into()),
+        ]),
+        _ => Json::Null,
+    };
+    Json::object(vec![
+        ("id", id.into()),
+        ("board", Json::Array(grid)),
+        ("position", board.to_position(game.turn()).into()),
+        ("turn", if is_over { Json::Null } else { piece_name(game.turn()).into() }),
+        ("black", board.black.into()),
+        ("white", board.white.into()),
+        ("moves", legal_moves(game).into()),
+        ("clocks", clocks),
+        ("outcome", game.outcome().as_ref().map_or(Json::Null, outcome)),
+    ])
+}
+
+fn history(game: &Game<Manual, Manual>) -> Json {
+    let plies: Vec<Json> = game.history().iter()
+        .map(|ply| Json::object(vec![
+            ("piece", piece_name(ply.piece).into()),
+            ("move", ply.mov.as_ref().map(|mov| mov.pos.to_string()).into()),
+        ]))
+        .collect();
+    let (board, turn) = game.start();
+    Json::object(vec![
+        ("start", board.to_position(turn).into()),
+        ("moves", Json::Array(plies)),
+        ("transcript", game.transcript().into()),
+    ])
+}
+
+fn move_error(err: &MoveError) -> Response {
+    match *err {
+        MoveError::GameOver => Response::error(409, &err.to_string()),
+        _ => Response::error(422, &err.to_string()),
+    }
+}
+
+/// 最後に打たれた手と打った後の盤面を配信する
+fn publish_move(id: u64, session: &Session, elapsed: Duration) {
+    if let Some(ply) = session.game.history().last() {
+        session.channel.publish(move_event(ply, elapsed));
+    }
+    session.channel.publish(event("state", state(id, &session.game)));
+}
+
+/// 本文の `move` の手を打つ。パスは `"pass"` か null
+fn submit(id: u64, session: &mut Session, body: &Json) -> Response {
+    let game = &mut session.game;
+    let result = match body.get("move") {
+        Some(&Json::Null) => game.apply(None),
+        Some(Json::String(s)) if s == "pass" => game.apply(None),
+        Some(Json::String(s)) => match s.parse() {
+            Ok(pos) => game.apply_at(pos),
+            Err(err) => return Response::error(400, &err),
+        },
+        _ => return Response::error(400, "\"move\" must be a coordinate such as \"d3\" or \"pass\""),
+    };
+    match result {
+        Ok(()) => {
+            publish_move(id, session, Duration::from_secs(0));
+            Response::ok(state(id, &session.game))
+        },
+        Err(err) => move_error(&err),
+    }
+}
+
+/// エンジンに手を考えさせ、`play` が false でなければその手を打つ
+///
+/// 本文の `player` でエンジンの種類を、`time` で考える時間を秒で指定できる。
+/// 探索の途中経過は購読者に配信する。
+///
+/// 探索の間は対局のロックを離し、その間に対局が進められていれば手を打たずに 409 を返す。
+fn think(id: u64, shared: &Mutex<Session>, body: &Json) -> Response {
+    let kind = match engine_kind(body.get("player")) {
+        Ok(kind) => kind,
+        Err(err) => return Response::error(400, &err),
+    };
+    let secs = body.get("time").and_then(Json::as_f64).unwrap_or(DEFAULT_ENGINE_TIME);
+    if !(secs > 0.0 && secs.is_finite()) {
+        return Response::error(400, "\"time\" must be a positive number of seconds");
+    }
+    let is_played = body.get("play").and_then(Json::as_bool).unwrap_or(true);
+    let (board, turn, history, channel) = {
+        let session = shared.lock().unwrap();
+        if session.game.outcome().is_some() {
+            return move_error(&MoveError::GameOver);
+        }
+        let game = &session.game;
+        (game.board().clone(), game.turn(), game.history().to_vec(), session.channel.clone())
+    };
+    let mut player = kind.build(seed());
+    // 1手ごとの秒読みの時計を渡すと、エンジンはその範囲で考える
+    let time_control = TimeControl::Byoyomi(Duration::from_millis((secs * 1000.0) as u64));
+    let stop = Arc::new(AtomicBool::new(false));
+    let listener: Arc<SearchListener> = Arc::new(move |info: &SearchInfo| channel.publish(search_event(info)));
+    let started = Instant::now();
+    let (mov, _) = players::search(&mut player, turn, &board, &history, Some(time_control), &stop, Some(listener));
+    let elapsed = started.elapsed();
+    let pos = mov.as_ref().map(|mov| mov.pos.to_string());
+    let mut session = shared.lock().unwrap();
+    if session.game.history() != &history[..] {
+        return Response::error(409, "the game changed while the engine was thinking");
+    }
+    if is_played {
+        if let Err(err) = session.game.apply(mov) {
+            return Response::error(500, &format!("engine failed: {}", err));
+        }
+        publish_move(id, &session, elapsed);
+    }
+    Response::ok(Json::object(vec![
+        ("move", pos.into()),
+        ("elapsed", elapsed.as_secs_f64().into()),
+        ("game", state(id, &session.game)),
+    ]))
+}
+
+/// 本文で指定されたエンジンの種類を読む。省略すると alphabeta
+///
+/// 人間や、深さが `MAX_ENGINE_DEPTH` を超えるものはエラーになる。
+fn engine_kind(value: Option<&Json>) -> Result<PlayerKind, String> {
+    match value.and_then(Json::as_str).unwrap_or("alphabeta").parse()? {
+        PlayerKind::Human => Err("the engine cannot be a human".to_string()),
+        kind if kind.depth().is_some_and(|depth| depth > MAX_ENGINE_DEPTH) => {
+            Err(format!("the depth must be at most {}", MAX_ENGINE_DEPTH))
+        },
+        kind => Ok(kind),
+    }
+}
+
+/// 1つの HTTP リクエストを読む
+fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|err| err.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target),
+        _ => return Err("malformed request line".to_string()),
+    };
+    let path = target.split('?').next().unwrap_or("").to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|err| err.to_string())? == 0 {
+            return Err("unexpected end of headers".to_string());
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(idx) = header.find(':') {
+            headers.push((header[..idx].trim().to_lowercase(), header[idx + 1..].trim().to_string()));
+        }
+    }
+    let length = match headers.iter().find(|header| header.0 == "content-length") {
+        Some(header) => header.1.parse().map_err(|_| "invalid Content-Length".to_string())?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err("request body is too large".to_string());
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|err| err.to_string())?;
+    let body = String::from_utf8(body).map_err(|_| "request body is not UTF-8".to_string())?;
+    Ok(Request { method, path, headers, body })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// 応答を書く。`cors` にオリジンが指定されていれば、そのオリジンのブラウザからのアクセスを許す
+fn write_response<W: Write>(writer: &mut W, status: u16, body: &str, cors: Option<&str>) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n",
+        status, reason(status), body.len(),
+    )?;
+    if let Some(origin) = cors {
+        write!(
+            writer,
+            "Access-Control-Allow-Origin: {}\r\n\
+             Access-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\n",
+            origin,
+        )?;
+    }
+    write!(writer, "Connection: close\r\n\r\n{}", body)?;
+    writer.flush()
+}
+
+/// WebSocket に切り替えた接続へ、配信されたイベントを送り続ける
+///
+/// クライアントが接続を閉じるか、送信に失敗した時点で終わる。
+fn stream_events(mut stream: TcpStream, key: &str, events: Receiver<String>) -> io::Result<()> {
+    websocket::write_handshake(&mut stream, key)?;
+    stream.set_read_timeout(None)?;
+    let closed = Arc::new(AtomicBool::new(false));
+    let mut writer = stream.try_clone()?;
+    let reader = {
+        let mut stream = stream.try_clone()?;
+        let closed = closed.clone();
+        thread::spawn(move || {
+            let mut reader = websocket::Reader::new(stream.try_clone().unwrap());
+            loop {
+                match reader.read_message() {
+                    Ok(Message::Ping(data)) => {
+                        let _ = websocket::write_message(&mut stream, &Message::Pong(data));
+                    },
+                    Ok(Message::Close) | Err(_) => break,
+                    Ok(_) => {},
+                }
+            }
+            closed.store(true, Ordering::SeqCst);
+        })
+    };
+    let result = loop {
+        if closed.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        match events.recv_timeout(POLL) {
+            Ok(event) => if let Err(err) = websocket::write_message(&mut writer, &Message::Text(event)) {
+                break Err(err);
+            },
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+        }
+    };
+    let _ = websocket::write_message(&mut writer, &Message::Close);
+    let _ = stream.shutdown(Shutdown::Both);
+    let _ = reader.join();
+    result
+}
+
+fn handle_connection(api: &Api, stream: TcpStream, cors: Option<&str>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(err) => {
+            let response = Response::error(400, &err);
+            return write_response(&mut writer, response.status, &response.body.to_string(), cors);
+        },
+    };
+    if request.is_upgrade() {
+        let key = request.header("sec-websocket-key").map(String::from);
+        return match (key, api.subscribe(&request.path)) {
+            (Some(key), Some(events)) => stream_events(writer, &key, events),
+            (None, _) => write_response(&mut writer, 400, &Response::error(400, "missing Sec-WebSocket-Key").body.to_string(), cors),
+            (_, None) => write_response(&mut writer, 404, &Response::error(404, "not found").body.to_string(), cors),
+        };
+    }
+    // CORS のプリフライトには本文なしで答える
+    if request.method == "OPTIONS" {
+        return write_response(&mut writer, 204, "", cors);
+    }
+    let response = api.handle(&request);
+    write_response(&mut writer, response.status, &response.body.to_string(), cors)
+}
+
+/// `serve` で起動するサーバーの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// 待ち受けるポート。0 なら空いているポートを使う
+    pub port: u16,
+    /// CORS でアクセスを許すオリジン (`*` ならすべて)。None なら CORS のヘッダーを付けない
+    pub cors: Option<String>,
+    /// 一覧に置ける `/games` の対局の数
+    pub max_games: usize,
+    /// 同時に進められるエンジン同士の対局の数
+    pub max_matches: usize,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: 8080,
+            cors: None,
+            max_games: 100,
+            max_matches: 4,
+        }
+    }
+}
+
+/// localhost で HTTP/JSON の API と WebSocket の配信を提供するサーバー
+pub struct Server {
+    listener: TcpListener,
+    api: Api,
+    cors: Option<String>,
+}
+
+impl Server {
+    /// 設定に従って待ち受ける
+    pub fn bind(settings: &Settings) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(("127.0.0.1", settings.port))?,
+            api: Api::new().with_max_games(settings.max_games).with_max_matches(settings.max_matches),
+            cors: settings.cors.clone(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 接続を待ち受け続け、1接続ごとにスレッドを立てて処理する
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let api = self.api.clone();
+            let cors = self.cors.clone();
+            thread::spawn(move || {
+                if let Err(err) = handle_connection(&api, stream, cors.as_deref()) {
+                    eprintln!("connection error: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+    use super::*;
+
+    /// テスト用の最小限の HTTP クライアント
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Json) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method, path, body.len(), body,
+        ).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+        (status, if body.is_empty() { Json::Null } else { Json::parse(body).unwrap() })
+    }
+
+    fn start() -> SocketAddr {
+        start_with(Settings { port: 0, ..Settings::default() })
+    }
+
+    fn start_with(settings: Settings) -> SocketAddr {
+        let server = Server::bind(&settings).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    #[test]
+    fn test_server_game() {
+        let addr = start();
+        let (status, game) = request(addr, "POST", "/games", "");
+        assert_eq!(201, status);
+        let id = game.get("id").and_then(Json::as_f64).unwrap() as u64;
+        assert_eq!(Some("black"), game.get("turn").and_then(Json::as_str));
+        let row = game.get("board").and_then(Json::as_array).unwrap()[3].clone();
+        assert_eq!(Json::parse(r#"[null,null,null,"white","black",null,null,null]"#), Ok(row));
+        assert_eq!(Some(format!("{} X", Board::new().matrix().to_position_string()).as_str()),
+                   game.get("position").and_then(Json::as_str));
+
+        let (status, moves) = request(addr, "GET", &format!("/games/{}/moves", id), "");
+        assert_eq!(200, status);
+        assert_eq!(Json::parse(r#"{"moves":["d3","c4","f5","e6"]}"#), Ok(moves));
+
+        let (status, game) = request(addr, "POST", &format!("/games/{}/moves", id), r#"{"move":"d3"}"#);
+        assert_eq!(200, status);
+        assert_eq!(Some(4.0), game.get("black").and_then(Json::as_f64));
+        let (status, _) = request(addr, "POST", &format!("/games/{}/moves", id), r#"{"move":"a1"}"#);
+        assert_eq!(422, status);
+
+        let (status, reply) = request(addr, "POST", &format!("/games/{}/engine", id), r#"{"player":"alphabeta:3","time":0.5}"#);
+        assert_eq!(200, status);
+        let mov = reply.get("move").and_then(Json::as_str).unwrap().to_string();
+        assert!(["c3", "c5", "e3"].contains(&mov.as_str()));
+
+        let (status, history) = request(addr, "GET", &format!("/games/{}/history", id), "");
+        assert_eq!(200, status);
+        assert_eq!(Some(format!("d3{}", mov).as_str()), history.get("transcript").and_then(Json::as_str));
+        assert_eq!(2, history.get("moves").and_then(Json::as_array).unwrap().len());
+
+        assert_eq!(200, request(addr, "DELETE", &format!("/games/{}", id), "").0);
+        assert_eq!(404, request(addr, "GET", &format!("/games/{}", id), "").0);
+    }
+
+    #[test]
+    fn test_server_errors() {
+        let addr = start();
+        assert_eq!(404, request(addr, "GET", "/nowhere", "").0);
+        assert_eq!(400, request(addr, "POST", "/games", "{").0);
+        assert_eq!(400, request(addr, "POST", "/games", &"[".repeat(100_000)).0);
+        assert_eq!(400, request(addr, "POST", "/games", r#"{"opening":"a1"}"#).0);
+        assert_eq!(204, request(addr, "OPTIONS", "/games", "").0);
+        let (status, game) = request(addr, "POST", "/games", r#"{"opening":"f5d6"}"#);
+        assert_eq!(201, status);
+        let path = format!("/games/{}", game.get("id").and_then(Json::as_f64).unwrap());
+        assert_eq!(400, request(addr, "POST", &format!("{}/moves", path), r#"{"move":"z9"}"#).0);
+        assert_eq!(400, request(addr, "POST", &format!("{}/engine", path), r#"{"player":"human"}"#).0);
+        assert_eq!(400, request(addr, "POST", &format!("{}/engine", path), r#"{"time":-1}"#).0);
+    }
+
+    #[test]
+    fn test_server_finished_game() {
+        let api = Api::new();
+        let post = |path: &str, body: &str| api.handle(&Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: body.to_string(),
+        });
+        let game = post("/games", r#"{"opening":"d3c3b3d2e1d6d7e3f4"}"#).body;
+        assert_eq!(Some(&Json::Array(Vec::new())), game.get("moves"));
+        assert_eq!(200, post("/games/1/moves", r#"{"move":"pass"}"#).status);
+        let game = post("/games/1/moves", r#"{"move":null}"#).body;
+        assert_eq!(Some(&Json::Null), game.get("turn"));
+        let outcome = game.get("outcome").unwrap();
+        assert_eq!(Some("black"), outcome.get("winner").and_then(Json::as_str));
+        assert_eq!(409, post("/games/1/moves", r#"{"move":"pass"}"#).status);
+        assert_eq!(409, post("/games/1/engine", "").status);
+    }
+
+    /// WebSocket で購読し、イベントを1つずつ読む関数を返す
+    fn subscribe(addr: SocketAddr, path: &str) -> impl FnMut() -> Json {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path,
+        ).unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut headers = String::new();
+        while !headers.ends_with("\r\n\r\n") {
+            reader.read_line(&mut headers).unwrap();
+        }
+        assert!(headers.starts_with("HTTP/1.1 101"));
+        assert!(headers.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        let mut reader = websocket::Reader::new(reader);
+        move || match reader.read_message().unwrap() {
+            Message::Text(text) => Json::parse(&text).unwrap(),
+            message => panic!("unexpected message: {:?}", message),
+        }
+    }
+
+    fn kind(event: &Json) -> &str {
+        event.get("type").and_then(Json::as_str).unwrap()
+    }
+
+    #[test]
+    fn test_server_websocket_game() {
+        let addr = start();
+        request(addr, "POST", "/games", "");
+        let mut next = subscribe(addr, "/games/1/ws");
+        assert_eq!("state", kind(&next()));
+
+        request(addr, "POST", "/games/1/moves", r#"{"move":"d3"}"#);
+        let event = next();
+        assert_eq!("move", kind(&event));
+        assert_eq!(Some("d3"), event.get("move").and_then(Json::as_str));
+        let event = next();
+        assert_eq!("state", kind(&event));
+        assert_eq!(Some("white"), event.get("turn").and_then(Json::as_str));
+
+        request(addr, "POST", "/games/1/engine", r#"{"player":"alphabeta:3"}"#);
+        let event = next();
+        assert_eq!("search", kind(&event));
+        assert_eq!(Some(1.0), event.get("depth").and_then(Json::as_f64));
+        let mut event = next();
+        while kind(&event) == "search" {
+            event = next();
+        }
+        assert_eq!("move", kind(&event));
+        assert_eq!("state", kind(&next()));
+
+        assert_eq!(400, request(addr, "GET", "/games/1/ws", "").0);
+    }
+
+    #[test]
+    fn test_server_websocket_match() {
+        let addr = start();
+        let (status, created) = request(addr, "POST", "/matches", r#"{"black":"alphabeta","white":"alphabeta:4","time":"byoyomi:1"}"#);
+        assert_eq!(201, status);
+        let path = format!("/matches/{}", created.get("id").and_then(Json::as_f64).unwrap());
+        let mut next = subscribe(addr, &format!("{}/ws", path));
+        assert_eq!("state", kind(&next()));
+        let mut seen = Vec::new();
+        while !(seen.contains(&"move".to_string()) && seen.contains(&"clock".to_string())) {
+            let event = next();
+            if kind(&event) == "clock" {
+                assert!(event.get("black").and_then(Json::as_f64).unwrap() <= 1.0);
+            }
+            seen.push(kind(&event).to_string());
+        }
+        assert!(seen.contains(&"search".to_string()));
+
+        assert_eq!(200, request(addr, "DELETE", &path, "").0);
+        while kind(&next()) != "aborted" {}
+        let (status, state) = request(addr, "GET", &path, "");
+        assert_eq!(200, status);
+        assert!(state.get("clocks").and_then(|clocks| clocks.get("white")).is_some());
+        assert_eq!(400, request(addr, "POST", "/matches", r#"{"black":"human"}"#).0);
+        assert_eq!(400, request(addr, "POST", "/matches", r#"{"time":"soon"}"#).0);
+        assert_eq!(404, request(addr, "DELETE", "/matches/99", "").0);
+    }
+
+    #[test]
+    fn test_server_engine_does_not_block() {
+        let addr = start();
+        request(addr, "POST", "/games", "");
+        // 深さ 12 の negamax は時間の予算がなければ終わらないので、秒読みで打ち切られる
+        let thinking = thread::spawn(move || {
+            request(addr, "POST", "/games/1/engine", r#"{"player":"negamax:12","time":1.5}"#)
+        });
+        thread::sleep(Duration::from_millis(300));
+        let started = Instant::now();
+        assert_eq!(200, request(addr, "GET", "/games/1", "").0);
+        assert!(started.elapsed() < Duration::from_millis(500), "the game is locked during the search");
+        assert_eq!(200, request(addr, "POST", "/games/1/moves", r#"{"move":"d3"}"#).0);
+        assert_eq!(409, thinking.join().unwrap().0);
+
+        let (status, reply) = request(addr, "POST", "/games/1/engine", r#"{"player":"negamax:12","time":0.2}"#);
+        assert_eq!(200, status);
+        assert!(reply.get("elapsed").and_then(Json::as_f64).unwrap() < 2.0);
+        assert_eq!(400, request(addr, "POST", "/games/1/engine", r#"{"player":"alphabeta:21"}"#).0);
+        assert_eq!(400, request(addr, "POST", "/matches", r#"{"black":"negamax:30"}"#).0);
+    }
+
+    #[test]
+    fn test_server_game_limit() {
+        let api = Api::new().with_max_games(2);
+        let call = |method: &str, path: &str| api.handle(&Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: String::new(),
+        });
+        assert_eq!(201, call("POST", "/games").status);
+        assert_eq!(201, call("POST", "/games").status);
+        assert_eq!(503, call("POST", "/games").status);
+        assert_eq!(200, call("DELETE", "/games/1").status);
+        assert_eq!(201, call("POST", "/games").status);
+
+        // しばらくリクエストの来ていない対局は一覧から消え、使われた対局は残る
+        let idle = Instant::now().checked_sub(IDLE_GAME_TTL).unwrap();
+        for stored in api.games.lock().unwrap().values_mut() {
+            stored.used = idle;
+        }
+        assert_eq!(404, call("GET", "/games/2").status);
+        assert_eq!(Json::parse(r#"{"games":[]}"#), Ok(call("GET", "/games").body));
+        assert_eq!(201, call("POST", "/games").status);
+        api.games.lock().unwrap().get_mut(&4).unwrap().used = Instant::now() - IDLE_GAME_TTL / 2;
+        assert_eq!(200, call("GET", "/games/4").status);
+        assert!(api.games.lock().unwrap()[&4].used.elapsed() < IDLE_GAME_TTL / 2);
+    }
+
+    #[test]
+    fn test_server_match_limit() {
+        let api = Api::new().with_max_matches(1);
+        let call = |method: &str, path: &str, body: &str| api.handle(&Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: body.to_string(),
+        });
+        let body = r#"{"black":"alphabeta:9","white":"alphabeta:9"}"#;
+        assert_eq!(201, call("POST", "/matches", body).status);
+        assert_eq!(503, call("POST", "/matches", body).status);
+        assert_eq!(200, call("DELETE", "/matches/1", "").status);
+        while api.matches.lock().unwrap()[&1].is_running() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(201, call("POST", "/matches", body).status);
+        assert_eq!(200, call("DELETE", "/matches/2", "").status);
+
+        // 終わってから時間の経った対局は一覧から消える
+        let expired = Instant::now().checked_sub(FINISHED_MATCH_TTL).unwrap();
+        *api.matches.lock().unwrap()[&1].finished.lock().unwrap() = Some(expired);
+        assert_eq!(Json::parse(r#"{"matches":[2]}"#), Ok(call("GET", "/matches", "").body));
+        assert_eq!(404, call("GET", "/matches/1", "").status);
+    }
+
+    #[test]
+    fn test_server_cors() {
+        let headers = |addr: SocketAddr| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "OPTIONS /games HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        assert!(!headers(start()).contains("Access-Control-Allow-Origin"));
+        let settings = Settings { port: 0, cors: Some("http://localhost:3000".to_string()), ..Settings::default() };
+        assert!(headers(start_with(settings)).contains("Access-Control-Allow-Origin: http://localhost:3000\r\n"));
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /games/1/moves?x=1 HTTP/1.1\r\ncontent-length: 13\r\n\r\n{\"move\":\"d3\"}";
+        let request = read_request(&mut Cursor::new(raw)).unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!("/games/1/moves", request.path);
+        assert_eq!("{\"move\":\"d3\"}", request.body);
+        assert_eq!(Some("13"), request.header("content-length"));
+        assert!(read_request(&mut Cursor::new("GET /\r\n")).is_err());
+    }
+}
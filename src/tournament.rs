@@ -0,0 +1,488 @@
+use std::cmp;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use piece::Piece;
+use coord::Coord;
+use clock::TimeControl;
+use game::{Game, Outcome};
+use players::PlayerKind;
+use rng::Rng;
+
+/// 対局の組み合わせ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// 全員が他の全員と対戦する
+    RoundRobin,
+    /// 最初の参加者だけが他の全員と対戦する
+    Gauntlet,
+}
+
+/// 逐次確率比検定 (SPRT) の設定
+///
+/// 2人の Elo レーティング差が `elo0` であるという帰無仮説と、`elo1` であるという対立仮説を比べる。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// 対数尤度比がこの範囲を出たら検定を終える
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+}
+
+/// SPRT の判定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtStatus {
+    Continue,
+    /// 帰無仮説を採択した（差は `elo0` 程度）
+    AcceptH0,
+    /// 対立仮説を採択した（差は `elo1` 程度）
+    AcceptH1,
+}
+
+/// 大会の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub format: Format,
+    /// 1つの組み合わせで行う、先後を入れ替えた2局の組の数
+    pub rounds: usize,
+    pub time_control: Option<TimeControl>,
+    pub sprt: Option<Sprt>,
+    /// 対局を始める序盤の手順。組ごとに順番に使う
+    pub openings: Vec<Vec<Coord>>,
+    pub seed: u64,
+}
+
+/// ある参加者から見た、1つの組み合わせの対局結果の集計
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// 各対局の終局時の石数の差
+    pub disc_diffs: Vec<i32>,
+}
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// 勝ちを 1、引き分けを 0.5 とした得点率
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// 1局あたりの得点の分散
+    ///
+    /// 全勝などで分散が 0 にならないよう、各結果の数に `prior` を足して計算する。
+    fn variance(&self, prior: f64) -> f64 {
+        let (w, d, l) = (self.wins as f64 + prior, self.draws as f64 + prior, self.losses as f64 + prior);
+        let n = w + d + l;
+        let s = (w + d / 2.0) / n;
+        (w * (1.0 - s).powi(2) + d * (0.5 - s).powi(2) + l * s.powi(2)) / n
+    }
+
+    /// 得点率から推定した Elo レーティング差と、その 95% 信頼区間の幅の半分を返す
+    ///
+    /// 全勝や全敗では推定できないので None を返す。
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let n = self.games() as f64;
+        let s = self.score();
+        if self.games() == 0 || s <= 0.0 || s >= 1.0 {
+            return None;
+        }
+        let margin = 1.96 * (self.variance(0.0) / n).sqrt();
+        let upper = score_to_elo((s + margin).min(0.999));
+        let lower = score_to_elo((s - margin).max(0.001));
+        Some((score_to_elo(s), (upper - lower) / 2.0))
+    }
+
+    /// 石数の差の平均と標準偏差
+    pub fn disc_diff(&self) -> (f64, f64) {
+        let n = self.disc_diffs.len() as f64;
+        if n == 0.0 {
+            return (0.0, 0.0);
+        }
+        let mean = self.disc_diffs.iter().sum::<i32>() as f64 / n;
+        let variance = self.disc_diffs.iter().map(|&d| (d as f64 - mean).powi(2)).sum::<f64>() / n;
+        (mean, variance.sqrt())
+    }
+
+    /// 3値（勝ち・引き分け・負け）の分布を正規近似した対数尤度比
+    pub fn llr(&self, sprt: &Sprt) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let variance = self.variance(1e-3);
+        let s0 = elo_to_score(sprt.elo0);
+        let s1 = elo_to_score(sprt.elo1);
+        self.games() as f64 * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn sprt(&self, sprt: &Sprt) -> SprtStatus {
+        let (lower, upper) = sprt.bounds();
+        let llr = self.llr(sprt);
+        if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+
+    fn add(&mut self, result: &GameResult) {
+        match result.diff.cmp(&0) {
+            cmp::Ordering::Greater => self.wins += 1,
+            cmp::Ordering::Equal => self.draws += 1,
+            cmp::Ordering::Less => self.losses += 1,
+        }
+        self.disc_diffs.push(result.diff);
+    }
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// 1つの組み合わせ。集計は `first` から見たもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pairing {
+    pub first: usize,
+    pub second: usize,
+    pub tally: Tally,
+    /// 行った2局の組の数
+    pub rounds: usize,
+    pub status: SprtStatus,
+}
+
+/// 1局の結果。`diff` は組み合わせの `first` から見た石数の差
+struct GameResult {
+    pairing: usize,
+    diff: i32,
+}
+
+/// 1局を行い、結果と黒から見た石数の差を返す
+///
+/// 時間切れや反則で終わった場合は、石数にかかわらず負けた側から見た差を -1 以下にする。
+/// 序盤に不正な手が含まれている場合はエラーを返す。
+pub fn play_game(black: PlayerKind, white: PlayerKind, opening: &[Coord], time_control: Option<TimeControl>, seed: u64) -> Result<(Outcome, i32), String> {
+    let mut rng = Rng::new(seed);
+    let mut game = Game::new(black.build(rng.next_u64()), white.build(rng.next_u64()));
+    if let Some(control) = time_control {
+        game.set_time_control(control);
+    }
+    game.replay(opening).map_err(|err| format!("illegal opening: {}", err))?;
+    while game.step() {}
+    let outcome = game.outcome().expect("the game is over");
+    let diff = game.board().black as i32 - game.board().white as i32;
+    let diff = match outcome.winner() {
+        Some(Piece::Black) => cmp::max(diff, 1),
+        Some(Piece::White) => cmp::min(diff, -1),
+        None => diff,
+    };
+    Ok((outcome, diff))
+}
+
+/// 大会を行い、結果を返す
+///
+/// 対局は rayon のスレッドプールで並列に行われる。rayon のない wasm32 では1局ずつ行う。
+/// SPRT が設定されている場合は、判定が出た組み合わせから対局を打ち切る。
+/// 序盤が1つもないか、不正な序盤があればエラーを返す。
+pub fn run(entrants: &[PlayerKind], settings: &Settings) -> Result<Report, String> {
+    let openings = &settings.openings;
+    if openings.is_empty() {
+        return Err("no openings".to_string());
+    }
+    let mut pairings = Vec::new();
+    for first in 0..entrants.len() {
+        for second in first + 1..entrants.len() {
+            if settings.format == Format::Gauntlet && first != 0 {
+                continue;
+            }
+            pairings.push(Pairing {
+                first,
+                second,
+                tally: Tally::default(),
+                rounds: 0,
+                status: SprtStatus::Continue,
+            });
+        }
+    }
+    let offset = Rng::new(settings.seed).below(openings.len());
+    #[cfg(not(target_arch = "wasm32"))]
+    let threads = rayon::current_num_threads();
+    #[cfg(target_arch = "wasm32")]
+    let threads: usize = 1;
+    loop {
+        let active: Vec<usize> = (0..pairings.len())
+            .filter(|&idx| pairings[idx].rounds < settings.rounds && pairings[idx].status == SprtStatus::Continue)
+            .collect();
+        if active.is_empty() {
+            break;
+        }
+        // スレッドを使い切るだけの組を、それぞれの組み合わせから順に取り出す
+        let per_pairing = cmp::max(1, threads.div_ceil(2 * active.len()));
+        let mut jobs = Vec::new();
+        for &idx in &active {
+            let pairing = &mut pairings[idx];
+            let end = cmp::min(pairing.rounds + per_pairing, settings.rounds);
+            for round in pairing.rounds..end {
+                let opening = &openings[(offset + round) % openings.len()];
+                let seed = settings.seed ^ ((idx as u64) << 32 | round as u64);
+                jobs.push((idx, entrants[pairing.first], entrants[pairing.second], opening, seed, true));
+                jobs.push((idx, entrants[pairing.second], entrants[pairing.first], opening, seed, false));
+            }
+            pairing.rounds = end;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let jobs = jobs.into_par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let jobs = jobs.into_iter();
+        let results: Vec<GameResult> = jobs
+            .map(|(pairing, black, white, opening, seed, is_first_black)| {
+                let (_, diff) = play_game(black, white, opening, settings.time_control, seed)?;
+                Ok(GameResult {
+                    pairing,
+                    diff: if is_first_black { diff } else { -diff },
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        for result in &results {
+            pairings[result.pairing].tally.add(result);
+        }
+        if let Some(ref sprt) = settings.sprt {
+            for &idx in &active {
+                pairings[idx].status = pairings[idx].tally.sprt(sprt);
+            }
+        }
+    }
+    Ok(Report {
+        names: entrants.iter().map(|kind| kind.to_string()).collect(),
+        pairings,
+        sprt: settings.sprt,
+    })
+}
+
+/// 大会の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub names: Vec<String>,
+    pub pairings: Vec<Pairing>,
+    pub sprt: Option<Sprt>,
+}
+impl Report {
+    /// 指定の参加者から見た、全対局の集計を返す
+    pub fn total(&self, entrant: usize) -> Tally {
+        let mut total = Tally::default();
+        for pairing in &self.pairings {
+            let tally = &pairing.tally;
+            if pairing.first == entrant {
+                total.wins += tally.wins;
+                total.draws += tally.draws;
+                total.losses += tally.losses;
+                total.disc_diffs.extend(tally.disc_diffs.iter());
+            } else if pairing.second == entrant {
+                total.wins += tally.losses;
+                total.draws += tally.draws;
+                total.losses += tally.wins;
+                total.disc_diffs.extend(tally.disc_diffs.iter().map(|d| -d));
+            }
+        }
+        total
+    }
+
+    /// 指定の2人の組み合わせの、`row` から見た集計を返す
+    fn versus(&self, row: usize, col: usize) -> Option<(u32, u32, u32)> {
+        self.pairings.iter().find_map(|pairing| {
+            let tally = &pairing.tally;
+            if (pairing.first, pairing.second) == (row, col) {
+                Some((tally.wins, tally.draws, tally.losses))
+            } else if (pairing.first, pairing.second) == (col, row) {
+                Some((tally.losses, tally.draws, tally.wins))
+            } else {
+                None
+            }
+        })
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0).max(4);
+        let mut ranking: Vec<(usize, Tally)> = (0..self.names.len()).map(|idx| (idx, self.total(idx))).collect();
+        ranking.sort_by(|a, b| b.1.score().partial_cmp(&a.1.score()).unwrap_or(cmp::Ordering::Equal));
+        writeln!(f, "{:<w$}  Games      W      D      L  Score    Elo vs field  Discs", "Name", w = width)?;
+        for &(idx, ref tally) in &ranking {
+            let elo = match tally.elo() {
+                Some((elo, error)) => format!("{:+6.0} ± {:<4.0}", elo, error),
+                None => format!("{:>13}", "-"),
+            };
+            let (mean, _) = tally.disc_diff();
+            writeln!(
+                f, "{:<w$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>4.1}%  {}  {:+5.1}",
+                self.names[idx], tally.games(), tally.wins, tally.draws, tally.losses,
+                tally.score() * 100.0, elo, mean, w = width,
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Crosstable (W-D-L of the row player):")?;
+        write!(f, "{:<w$}", "", w = width)?;
+        for name in &self.names {
+            write!(f, "  {:>w$}", name, w = width)?;
+        }
+        writeln!(f)?;
+        for row in 0..self.names.len() {
+            write!(f, "{:<w$}", self.names[row], w = width)?;
+            for col in 0..self.names.len() {
+                let cell = match self.versus(row, col) {
+                    Some((w, d, l)) => format!("{}-{}-{}", w, d, l),
+                    None => "".to_string(),
+                };
+                write!(f, "  {:>w$}", cell, w = width)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Pairings:")?;
+        for pairing in &self.pairings {
+            let tally = &pairing.tally;
+            let (mean, stddev) = tally.disc_diff();
+            write!(
+                f, "{} vs {}: +{} ={} -{}, discs {:+.1} (sd {:.1})",
+                self.names[pairing.first], self.names[pairing.second],
+                tally.wins, tally.draws, tally.losses, mean, stddev,
+            )?;
+            if let Some((elo, error)) = tally.elo() {
+                write!(f, ", Elo {:+.0} ± {:.0}", elo, error)?;
+            }
+            if let Some(ref sprt) = self.sprt {
+                let (lower, upper) = sprt.bounds();
+                let verdict = match pairing.status {
+                    SprtStatus::Continue => "inconclusive",
+                    SprtStatus::AcceptH0 => "H0 accepted",
+                    SprtStatus::AcceptH1 => "H1 accepted",
+                };
+                write!(f, ", SPRT LLR {:.2} [{:.2}, {:.2}] {}", tally.llr(sprt), lower, upper, verdict)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openings;
+    use super::*;
+
+    fn tally(wins: u32, draws: u32, losses: u32) -> Tally {
+        Tally {
+            wins,
+            draws,
+            losses,
+            disc_diffs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_tally_elo() {
+        let (elo, error) = tally(30, 20, 30).elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        assert!(error > 0.0);
+        let (elo, _) = tally(75, 0, 25).elo().unwrap();
+        assert!((elo - 190.85).abs() < 0.01, "{}", elo);
+        assert_eq!(None, tally(10, 0, 0).elo());
+    }
+
+    #[test]
+    fn test_tally_disc_diff() {
+        let mut tally = tally(0, 0, 0);
+        tally.disc_diffs = vec![10, -2, 4, 0];
+        assert_eq!((3.0, 21f64.sqrt()), tally.disc_diff());
+    }
+
+    #[test]
+    fn test_tally_sprt() {
+        let sprt = Sprt::new(0.0, 50.0);
+        assert_eq!(SprtStatus::Continue, tally(3, 2, 2).sprt(&sprt));
+        assert_eq!(SprtStatus::AcceptH1, tally(300, 100, 100).sprt(&sprt));
+        assert_eq!(SprtStatus::AcceptH0, tally(200, 100, 300).sprt(&sprt));
+    }
+
+    #[test]
+    fn test_tournament_round_robin() {
+        let entrants = [PlayerKind::Dumb, PlayerKind::Random, PlayerKind::AlphaBeta(2)];
+        let settings = Settings {
+            format: Format::RoundRobin,
+            rounds: 2,
+            time_control: None,
+            sprt: None,
+            openings: openings::suite(),
+            seed: 1,
+        };
+        let report = run(&entrants, &settings).unwrap();
+        assert_eq!(3, report.pairings.len());
+        for pairing in &report.pairings {
+            assert_eq!(4, pairing.tally.games());
+        }
+        for idx in 0..entrants.len() {
+            assert_eq!(8, report.total(idx).games());
+        }
+        assert!(report.to_string().contains("Crosstable"));
+    }
+
+    #[test]
+    fn test_tournament_gauntlet_with_sprt() {
+        let entrants = [PlayerKind::AlphaBeta(2), PlayerKind::Random, PlayerKind::Dumb];
+        let settings = Settings {
+            format: Format::Gauntlet,
+            rounds: 200,
+            time_control: None,
+            sprt: Some(Sprt::new(0.0, 200.0)),
+            openings: openings::suite(),
+            seed: 1,
+        };
+        let report = run(&entrants, &settings).unwrap();
+        assert_eq!(2, report.pairings.len());
+        for pairing in &report.pairings {
+            assert_eq!(0, pairing.first);
+            assert_ne!(SprtStatus::Continue, pairing.status);
+            assert!(pairing.rounds < 200);
+        }
+    }
+
+    #[test]
+    fn test_tournament_illegal_opening() {
+        let settings = Settings {
+            format: Format::RoundRobin,
+            rounds: 1,
+            time_control: None,
+            sprt: None,
+            openings: vec![vec![Coord(0, 0), Coord(0, 1)]],
+            seed: 1,
+        };
+        let result = run(&[PlayerKind::Dumb, PlayerKind::Random], &settings);
+        assert_eq!(Err("illegal opening: Black cannot play a1".to_string()), result.map(|_| ()));
+        let settings = Settings { openings: Vec::new(), ..settings };
+        let result = run(&[PlayerKind::Dumb, PlayerKind::Random], &settings);
+        assert_eq!(Err("no openings".to_string()), result.map(|_| ()));
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::{self, Read};
+
+/// 端末から読んだキーやマウスの操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Enter かスペース
+    Enter,
+    Char(char),
+    /// 左ボタンで押した位置。画面の左上を (1, 1) とした桁と行
+    Click(u16, u16),
+}
+
+/// バイト列の先頭から操作を1つ読み、読んだ操作と消費したバイト数を返す
+///
+/// 途中で終わっているエスケープシーケンスは None を返し、続きを待つ。
+/// 知らないシーケンスやマウスのボタンを離した操作は、読み捨てるために `Some((None, n))` を返す。
+pub fn parse_key(buf: &[u8]) -> Option<(Option<Key>, usize)> {
+    let first = *buf.first()?;
+    match first {
+        0x1b => parse_escape(buf),
+        b'\r' | b'\n' | b' ' => Some((Some(Key::Enter), 1)),
+        // 端末を raw モードにすると Ctrl-C はシグナルにならないので、終了のキーとして扱う
+        0x03 => Some((Some(Key::Char('q')), 1)),
+        byte if byte.is_ascii_graphic() => Some((Some(Key::Char(byte as char)), 1)),
+        _ => Some((None, 1)),
+    }
+}
+
+fn parse_escape(buf: &[u8]) -> Option<(Option<Key>, usize)> {
+    match *buf.get(1)? {
+        b'[' | b'O' => {},
+        _ => return Some((None, 1)),
+    }
+    let arrow = match *buf.get(2)? {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'<' if buf[1] == b'[' => return parse_mouse(buf),
+        _ => None,
+    };
+    if arrow.is_some() {
+        return Some((arrow, 3));
+    }
+    // `ESC [ 数字 ; 数字 ~` などの知らないシーケンスは、終わりの文字まで読み捨てる
+    let end = buf[2..].iter().position(|byte| (0x40..=0x7e).contains(byte))?;
+    Some((None, end + 3))
+}
+
+/// SGR 形式のマウスの操作 `ESC [ < ボタン ; 桁 ; 行 M` を読む
+fn parse_mouse(buf: &[u8]) -> Option<(Option<Key>, usize)> {
+    let end = buf[3..].iter().position(|&byte| byte == b'M' || byte == b'm')? + 3;
+    let len = end + 1;
+    let params = match ::std::str::from_utf8(&buf[3..end]) {
+        Ok(params) => params,
+        Err(_) => return Some((None, len)),
+    };
+    let params: Vec<u16> = params.split(';').filter_map(|param| param.parse().ok()).collect();
+    match (&params[..], buf[end]) {
+        (&[0, x, y], b'M') => Some((Some(Key::Click(x, y)), len)),
+        _ => Some((None, len)),
+    }
+}
+
+/// 入力からキーを1つずつ読む
+pub struct Keys<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> Keys<R> {
+    pub fn new(reader: R) -> Self {
+        Keys {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    /// 次の操作を読む。入力が終わった場合は `UnexpectedEof` のエラーになる
+    pub fn next_key(&mut self) -> io::Result<Key> {
+        loop {
+            if let Some((key, len)) = parse_key(&self.buf) {
+                self.buf.drain(..len);
+                match key {
+                    Some(key) => return Ok(key),
+                    None => continue,
+                }
+            }
+            let mut chunk = [0; 64];
+            match self.reader.read(&mut chunk)? {
+                0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the input is closed")),
+                len => self.buf.extend_from_slice(&chunk[..len]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    #[test]
+    fn test_tui_parse_key() {
+        assert_eq!(Some((Some(Key::Up), 3)), parse_key(b"\x1b[A"));
+        assert_eq!(Some((Some(Key::Left), 3)), parse_key(b"\x1bODx"));
+        assert_eq!(Some((Some(Key::Enter), 1)), parse_key(b"\r"));
+        assert_eq!(Some((Some(Key::Char('h')), 1)), parse_key(b"h"));
+        assert_eq!(Some((Some(Key::Click(12, 5)), 10)), parse_key(b"\x1b[<0;12;5M"));
+        assert_eq!(Some((None, 10)), parse_key(b"\x1b[<0;12;5m"));
+        assert_eq!(Some((None, 4)), parse_key(b"\x1b[5~"));
+        assert_eq!(None, parse_key(b"\x1b[<0;1"));
+        assert_eq!(None, parse_key(b"\x1b"));
+        assert_eq!(None, parse_key(b""));
+    }
+
+    #[test]
+    fn test_tui_keys() {
+        let mut keys = Keys::new(Cursor::new(b"\x1b[<0;3;4m\x1b[Bq".to_vec()));
+        assert_eq!(Key::Down, keys.next_key().unwrap());
+        assert_eq!(Key::Char('q'), keys.next_key().unwrap());
+        assert_eq!(io::ErrorKind::UnexpectedEof, keys.next_key().unwrap_err().kind());
+    }
+}
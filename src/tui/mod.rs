@@ -0,0 +1,242 @@
+use std::io::{self, Stdin, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use piece::Piece;
+use board::{Board, Move};
+use coord::Coord;
+use game::{Game, Play, Resignation};
+use players::{AlphaBetaPlayer, PlayerKind};
+use cli::Options;
+use rng::Rng;
+
+mod input;
+mod render;
+
+pub use self::input::{Key, Keys};
+pub use self::render::{View, render, square_at};
+
+/// 評価値のバーのために、1手ごとに探索する深さ
+const EVAL_DEPTH: usize = 4;
+
+/// ヒントのために探索する深さ
+const HINT_DEPTH: usize = 6;
+
+/// 端末を全画面の raw モードにし、drop で元に戻す
+///
+/// raw モードの切り替えには `stty` を使う。
+pub struct Terminal {
+    saved: String,
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        let terminal = Terminal { saved: saved.trim().to_string() };
+        // 別画面に切り替え、カーソルを隠して、SGR 形式のマウス報告を有効にする
+        print!("\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h");
+        io::stdout().flush()?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("the terminal UI needs an interactive terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn draw(view: &View, cursor: Option<Coord>) {
+    print!("{}", render(view, cursor));
+    let _ = io::stdout().flush();
+}
+
+/// 画面上のカーソルやマウスで手を選ぶ人間のプレイヤー
+///
+/// 盤面以外の表示は、対局を進める側が `view` に書いたものを使う。
+pub struct TuiPlayer {
+    view: Arc<Mutex<View>>,
+    keys: Keys<Stdin>,
+    cursor: Coord,
+    resignation: Option<Resignation>,
+}
+
+impl TuiPlayer {
+    pub fn new(view: Arc<Mutex<View>>) -> Self {
+        TuiPlayer {
+            view,
+            keys: Keys::new(io::stdin()),
+            cursor: Coord(3, 2),
+            resignation: None,
+        }
+    }
+
+    fn show(&self, message: &str, cursor: Option<Coord>) {
+        let mut view = self.view.lock().unwrap();
+        view.message = message.to_string();
+        draw(&view, cursor);
+    }
+
+    /// キーを1つ読む。入力が終わっていれば投了する
+    fn next_key(&mut self) -> Option<Key> {
+        match self.keys.next_key() {
+            Ok(Key::Char('q')) | Err(_) => {
+                self.resignation = Some(Resignation::Resigned);
+                None
+            },
+            Ok(key) => Some(key),
+        }
+    }
+}
+
+impl Play for TuiPlayer {
+    fn play(&mut self, piece: Piece, board: &Board) -> Option<Move> {
+        let moves = board.moves(piece);
+        if moves.is_empty() {
+            self.show(&format!("{:?} has no legal moves. Press any key to pass.", piece), None);
+            self.next_key();
+            return None;
+        }
+        if !moves.iter().any(|mov| mov.pos == self.cursor) {
+            self.cursor = moves[0].pos;
+        }
+        let mut message = format!("{:?} to move", piece);
+        loop {
+            self.show(&message, Some(self.cursor));
+            let Coord(x, y) = self.cursor;
+            let pos = match self.next_key()? {
+                Key::Up => {
+                    self.cursor = Coord(x, (y + 7) % 8);
+                    continue;
+                },
+                Key::Down => {
+                    self.cursor = Coord(x, (y + 1) % 8);
+                    continue;
+                },
+                Key::Left => {
+                    self.cursor = Coord((x + 7) % 8, y);
+                    continue;
+                },
+                Key::Right => {
+                    self.cursor = Coord((x + 1) % 8, y);
+                    continue;
+                },
+                Key::Char('h') => {
+                    self.show("Thinking...", Some(self.cursor));
+                    let best = AlphaBetaPlayer::new(HINT_DEPTH).analyze(piece, board).into_iter().next();
+                    if let Some((mov, score)) = best {
+                        self.cursor = mov.pos;
+                        message = format!("Hint: {} ({:+})", mov.pos, score);
+                    }
+                    continue;
+                },
+                Key::Enter => self.cursor,
+                Key::Click(col, row) => match square_at(col, row) {
+                    Some(pos) => {
+                        self.cursor = pos;
+                        pos
+                    },
+                    None => continue,
+                },
+                Key::Char(_) => continue,
+            };
+            match moves.iter().find(|mov| mov.pos == pos) {
+                Some(mov) => return Some(mov.clone()),
+                None => message = format!("{} is not a legal move", pos),
+            }
+        }
+    }
+
+    fn resignation(&mut self) -> Option<Resignation> {
+        self.resignation
+    }
+}
+
+/// 対局の今の状態を画面に反映する
+///
+/// `previous` は直前の手を打つ前の盤面で、ひっくり返された石を求めるのに使う。
+fn update<P1: Play, P2: Play>(view: &mut View, game: &Game<P1, P2>, previous: &Board) {
+    let board = game.board();
+    view.board = board.clone();
+    view.turn = game.turn();
+    view.last_move = game.history().last().and_then(|ply| ply.mov.as_ref()).map(|mov| mov.pos);
+    view.flipped = (0..64)
+        .map(|idx| Coord(idx % 8, idx / 8))
+        .filter(|&pos| {
+            let (before, after) = (previous.matrix()[pos], board.matrix()[pos]);
+            before.is_some() && after.is_some() && before != after
+        })
+        .collect();
+    view.clocks = [
+        game.clock(Piece::Black).map(|clock| clock.to_string()),
+        game.clock(Piece::White).map(|clock| clock.to_string()),
+    ];
+    view.eval = Some(match game.outcome() {
+        Some(_) => (board.black as i32 - board.white as i32).clamp(-64, 64) as i8,
+        None => {
+            let score = AlphaBetaPlayer::new(EVAL_DEPTH).score(game.turn(), board);
+            match game.turn() {
+                Piece::Black => score,
+                Piece::White => -score,
+            }
+        },
+    });
+}
+
+/// 全画面の端末 UI で1局を打つ
+///
+/// 人間の手はカーソルかマウスで選ぶ。盤面や時計、評価値のバーは1手ごとに描き直す。
+pub fn run(options: &Options) -> Result<(), String> {
+    let mut rng = Rng::new(options.seed);
+    let (board, turn) = options.position.clone().unwrap_or((Board::new(), Piece::Black));
+    let names = [options.black.to_string(), options.white.to_string()];
+    let view = Arc::new(Mutex::new(View::new(board.clone(), turn, names)));
+    let mut build = |kind: PlayerKind| -> Box<dyn Play + Send> {
+        match kind {
+            PlayerKind::Human => Box::new(TuiPlayer::new(view.clone())),
+            kind => kind.build(rng.next_u64()),
+        }
+    };
+    let (black, white) = (build(options.black), build(options.white));
+    let mut game = Game::from_position(board, turn, black, white).map_err(|err| format!("invalid position: {}", err))?;
+    if let Some(control) = options.time_control {
+        game.set_time_control(control);
+    }
+    game.set_pondering(options.pondering);
+    game.replay(options.pick_opening(&mut rng)).map_err(|err| format!("invalid opening: {}", err))?;
+    let terminal = Terminal::enter().map_err(|err| err.to_string())?;
+    let mut previous = game.board().clone();
+    loop {
+        {
+            let mut view = view.lock().unwrap();
+            update(&mut view, &game, &previous);
+            view.message = format!("{:?} is thinking...", game.turn());
+            draw(&view, None);
+        }
+        previous = game.board().clone();
+        if !game.step() {
+            break;
+        }
+    }
+    let outcome = game.outcome().expect("the game is over");
+    {
+        let mut view = view.lock().unwrap();
+        update(&mut view, &game, &previous);
+        view.message = format!("{}. Press any key to exit.", outcome);
+        draw(&view, None);
+    }
+    let _ = Keys::new(io::stdin()).next_key();
+    drop(terminal);
+    println!("{}", outcome);
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use piece::Piece;
+use board::Board;
+use coord::Coord;
+
+/// 盤面の1行目 (`1` の行) を描く画面上の行。画面の一番上を 1 とする
+pub const BOARD_TOP: u16 = 4;
+/// `a` の列のマスを描き始める画面上の桁
+pub const BOARD_LEFT: u16 = 5;
+/// 1マスの幅
+pub const CELL_WIDTH: u16 = 3;
+
+/// 評価値のバーの幅
+const EVAL_WIDTH: i32 = 32;
+
+const RESET: &str = "\x1b[0m";
+const BOARD_BG: u8 = 42;
+const CURSOR_BG: u8 = 46;
+const LAST_MOVE_BG: u8 = 41;
+const FLIPPED_BG: u8 = 43;
+const BLACK_FG: u8 = 30;
+const WHITE_FG: u8 = 97;
+const LEGAL_FG: u8 = 33;
+
+/// 画面に描く内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub board: Board,
+    pub turn: Piece,
+    /// 黒と白の対局者の名前
+    pub names: [String; 2],
+    pub last_move: Option<Coord>,
+    /// 直前の手でひっくり返された石
+    pub flipped: Vec<Coord>,
+    /// 黒と白の残り時間の表記。持ち時間がなければ None
+    pub clocks: [Option<String>; 2],
+    /// 黒から見た評価値
+    pub eval: Option<i8>,
+    pub message: String,
+}
+
+impl View {
+    pub fn new(board: Board, turn: Piece, names: [String; 2]) -> Self {
+        View {
+            board,
+            turn,
+            names,
+            last_move: None,
+            flipped: Vec::new(),
+            clocks: [None, None],
+            eval: None,
+            message: String::new(),
+        }
+    }
+}
+
+/// 画面上の位置にあるマスを返す
+pub fn square_at(col: u16, row: u16) -> Option<Coord> {
+    if col < BOARD_LEFT || row < BOARD_TOP {
+        return None;
+    }
+    let (x, y) = ((col - BOARD_LEFT) / CELL_WIDTH, row - BOARD_TOP);
+    if x < 8 && y < 8 {
+        Some(Coord(x as i8, y as i8))
+    } else {
+        None
+    }
+}
+
+/// 画面全体を消して描き直すエスケープシーケンスを返す
+///
+/// 端末は raw モードなので、改行には `\r\n` を使う。`cursor` は選んでいるマス。
+pub fn render(view: &View, cursor: Option<Coord>) -> String {
+    let mut lines = Vec::new();
+    lines.push(format!(
+        "\x1b[1m Reversi{}   {} {}  vs  {} {}",
+        RESET,
+        disc(Piece::Black),
+        view.names[0],
+        disc(Piece::White),
+        view.names[1],
+    ));
+    lines.push(String::new());
+    let files: String = "abcdefgh".chars().map(|file| format!(" {} ", file)).collect();
+    lines.push(format!("    {}", files));
+    let legal: Vec<Coord> = view.board.moves(view.turn).iter().map(|mov| mov.pos).collect();
+    let panel = panel(view);
+    for y in 0..8 {
+        let mut line = format!(" {}  ", y + 1);
+        for x in 0..8 {
+            let pos = Coord(x, y);
+            let bg = if Some(pos) == cursor {
+                CURSOR_BG
+            } else if Some(pos) == view.last_move {
+                LAST_MOVE_BG
+            } else if view.flipped.contains(&pos) {
+                FLIPPED_BG
+            } else {
+                BOARD_BG
+            };
+            let (fg, glyph) = match view.board.matrix()[pos] {
+                Some(Piece::Black) => (BLACK_FG, "●"),
+                Some(Piece::White) => (WHITE_FG, "●"),
+                None if legal.contains(&pos) => (LEGAL_FG, "·"),
+                None => (BLACK_FG, " "),
+            };
+            line.push_str(&format!("\x1b[{};{}m {} {}", bg, fg, glyph, RESET));
+        }
+        if let Some(text) = panel.get(y as usize) {
+            line.push_str("   ");
+            line.push_str(text);
+        }
+        lines.push(line);
+    }
+    lines.push(String::new());
+    if let Some(eval) = view.eval {
+        lines.push(format!(" {}", eval_bar(eval)));
+    }
+    lines.push(format!(" {}", view.message));
+    lines.push(" arrows/mouse: select  enter: play  h: hint  q: resign".to_string());
+    format!("\x1b[H\x1b[2J{}\r\n", lines.join("\x1b[K\r\n"))
+}
+
+fn disc(piece: Piece) -> String {
+    let fg = match piece {
+        Piece::Black => BLACK_FG,
+        Piece::White => WHITE_FG,
+    };
+    format!("\x1b[{};{}m ● {}", BOARD_BG, fg, RESET)
+}
+
+/// 盤面の右に並べる、石の数と時計の表示
+fn panel(view: &View) -> Vec<String> {
+    let mut panel = Vec::new();
+    for (idx, &piece) in [Piece::Black, Piece::White].iter().enumerate() {
+        let marker = if piece == view.turn { "▶" } else { " " };
+        let clock = view.clocks[idx].as_ref().map_or(String::new(), |clock| format!("  {}", clock));
+        let count = match piece {
+            Piece::Black => view.board.black,
+            Piece::White => view.board.white,
+        };
+        panel.push(format!("{} {} {:<6}{:>2}{}", marker, disc(piece), format!("{:?}", piece), count, clock));
+    }
+    panel.push(String::new());
+    panel.push(format!("\x1b[{}m   {} last move", LAST_MOVE_BG, RESET));
+    panel.push(format!("\x1b[{}m   {} flipped", FLIPPED_BG, RESET));
+    panel.push(format!("\x1b[{};{}m · {} legal move", BOARD_BG, LEGAL_FG, RESET));
+    panel
+}
+
+/// 黒から見た評価値を、黒と白の取り分で塗り分けたバーにする
+pub fn eval_bar(eval: i8) -> String {
+    let black = ((eval as i32 + 64) * EVAL_WIDTH / 128).clamp(0, EVAL_WIDTH) as usize;
+    let white = EVAL_WIDTH as usize - black;
+    format!("B [{}{}] W  {:+}", "█".repeat(black), "░".repeat(white), eval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tui_square_at() {
+        assert_eq!(Some(Coord(0, 0)), square_at(BOARD_LEFT, BOARD_TOP));
+        assert_eq!(Some(Coord(0, 0)), square_at(BOARD_LEFT + CELL_WIDTH - 1, BOARD_TOP));
+        assert_eq!(Some(Coord(7, 7)), square_at(BOARD_LEFT + CELL_WIDTH * 8 - 1, BOARD_TOP + 7));
+        assert_eq!(None, square_at(BOARD_LEFT - 1, BOARD_TOP));
+        assert_eq!(None, square_at(BOARD_LEFT + CELL_WIDTH * 8, BOARD_TOP));
+        assert_eq!(None, square_at(BOARD_LEFT, BOARD_TOP + 8));
+    }
+
+    #[test]
+    fn test_tui_eval_bar() {
+        assert_eq!(format!("B [{}{}] W  +0", "█".repeat(16), "░".repeat(16)), eval_bar(0));
+        assert_eq!(format!("B [{}] W  +64", "█".repeat(32)), eval_bar(64));
+        assert_eq!(format!("B [{}] W  -64", "░".repeat(32)), eval_bar(-64));
+    }
+
+    #[test]
+    fn test_tui_render() {
+        let mut view = View::new(Board::new(), Piece::Black, ["human".to_string(), "alphabeta:7".to_string()]);
+        view.clocks = [Some("1:00.0".to_string()), Some("0:59.5".to_string())];
+        view.eval = Some(-2);
+        view.message = "Your move".to_string();
+        let screen = render(&view, Some(Coord(3, 2)));
+        assert!(screen.starts_with("\x1b[H\x1b[2J"));
+        let lines: Vec<&str> = screen.split("\r\n").collect();
+        // 盤面の行は BOARD_TOP 行目から始まる
+        assert!(lines[BOARD_TOP as usize - 1].starts_with(" 1  "));
+        // d3 はカーソル、c4 は合法手
+        assert!(lines[BOARD_TOP as usize + 1].contains(&format!("\x1b[{};{}m · ", CURSOR_BG, LEGAL_FG)));
+        assert!(lines[BOARD_TOP as usize + 2].contains(&format!("\x1b[{};{}m · ", BOARD_BG, LEGAL_FG)));
+        assert!(lines[BOARD_TOP as usize - 1].contains("▶"));
+        assert!(screen.contains("0:59.5"));
+        assert!(screen.contains("W  -2"));
+        assert!(screen.contains("Your move"));
+    }
+}
@@ -0,0 +1,198 @@
+//! `wasm` フィーチャーで有効になる、ブラウザなどの JavaScript から使うための API
+//!
+//! wasm-pack でビルドする。wasm32 では rayon を使わず、探索はすべて1スレッドで行う。
+//!
+//! ```js
+//! import init, { Game, Engine } from "./pkg/reversi.js";
+//!
+//! await init();
+//! const game = new Game();
+//! const engine = new Engine("alphabeta:7");
+//! game.play("f5");
+//! game.play(engine.bestMove(game, 500)); // 0.5 秒で探索
+//! console.log(game.moves(), engine.searchInfo());
+//! ```
+//!
+//! 手は `"d3"` のような座標の文字列で渡し、パスは `"pass"` とする。色は `"black"` と `"white"` で表す。
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+use piece::Piece;
+use board::Board;
+use clock::{Instant, TimeControl};
+use game::{Game, Manual, Play, SearchInfo};
+use players::{self, PlayerKind};
+
+fn piece_name(piece: Piece) -> String {
+    match piece {
+        Piece::Black => "black".to_string(),
+        Piece::White => "white".to_string(),
+    }
+}
+
+/// 外部から手を与えて進める対局
+#[wasm_bindgen(js_name = Game)]
+pub struct WasmGame {
+    game: Game<Manual, Manual>,
+}
+
+#[wasm_bindgen(js_class = Game)]
+impl WasmGame {
+    /// 初期局面から黒の手番で始まる対局を作る
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> WasmGame {
+        WasmGame { game: Game::new(Manual, Manual) }
+    }
+
+    /// `Board::to_position` の形式の局面から始まる対局を作る
+    #[wasm_bindgen(js_name = fromPosition)]
+    pub fn from_position(position: &str) -> Result<WasmGame, JsError> {
+        let (board, turn) = Board::from_position(position).map_err(|err| JsError::new(&err))?;
+        let game = Game::from_position(board, turn, Manual, Manual).map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(WasmGame { game })
+    }
+
+    /// 手番の色。終局していれば undefined
+    #[wasm_bindgen(getter)]
+    pub fn turn(&self) -> Option<String> {
+        match self.game.outcome() {
+            Some(_) => None,
+            None => Some(piece_name(self.game.turn())),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn black(&self) -> u8 {
+        self.game.board().black
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn white(&self) -> u8 {
+        self.game.board().white
+    }
+
+    #[wasm_bindgen(getter, js_name = isOver)]
+    pub fn is_over(&self) -> bool {
+        self.game.outcome().is_some()
+    }
+
+    /// 勝った色。引き分けか対局中なら undefined
+    #[wasm_bindgen(getter)]
+    pub fn winner(&self) -> Option<String> {
+        self.game.outcome().and_then(|outcome| outcome.winner()).map(piece_name)
+    }
+
+    /// 手番の合法手。打てる手がなければ空なので、`play("pass")` でパスする
+    pub fn moves(&self) -> Vec<String> {
+        if self.game.outcome().is_some() {
+            return Vec::new();
+        }
+        self.game.board().moves(self.game.turn()).into_iter().map(|mov| mov.pos.to_string()).collect()
+    }
+
+    /// 盤面を `a1` から `h8` へ行ごとに並べた 64 マス。空きは 0、黒は 1、白は 2
+    pub fn cells(&self) -> Vec<u8> {
+        self.game.board().matrix().to_position_string().chars()
+            .map(|cell| match cell {
+                'X' => 1,
+                'O' => 2,
+                _ => 0,
+            })
+            .collect()
+    }
+
+    /// 手番の手を打つ。不正な手なら例外を投げる
+    pub fn play(&mut self, mov: &str) -> Result<(), JsError> {
+        let result = match mov {
+            "pass" => self.game.apply(None),
+            pos => {
+                let pos = pos.parse().map_err(|err: String| JsError::new(&err))?;
+                self.game.apply_at(pos)
+            },
+        };
+        result.map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// 最後の1手を取り消し、その手を返す。パスなら `"pass"`、取り消す手がなければ undefined
+    pub fn undo(&mut self) -> Option<String> {
+        let ply = self.game.undo()?;
+        Some(ply.mov.map_or("pass".to_string(), |mov| mov.pos.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = toPosition)]
+    pub fn to_position(&self) -> String {
+        self.game.board().to_position(self.game.turn())
+    }
+
+    /// パスを省略した `f5d6c3` のような棋譜
+    pub fn transcript(&self) -> String {
+        self.game.transcript()
+    }
+}
+
+/// `alphabeta:7` のような `PlayerKind` の表記で選ぶコンピュータプレイヤー
+#[wasm_bindgen(js_name = Engine)]
+pub struct WasmEngine {
+    player: Box<dyn Play + Send>,
+    info: Option<(Option<SearchInfo>, Duration)>,
+}
+
+#[wasm_bindgen(js_class = Engine)]
+impl WasmEngine {
+    /// `seed` は無作為に手を選ぶエンジンのみが使う
+    #[wasm_bindgen(constructor)]
+    pub fn new(kind: &str, seed: Option<u32>) -> Result<WasmEngine, JsError> {
+        let kind: PlayerKind = kind.parse().map_err(|err: String| JsError::new(&err))?;
+        if kind == PlayerKind::Human {
+            return Err(JsError::new("a human player cannot be an engine"));
+        }
+        Ok(WasmEngine {
+            player: kind.build(seed.unwrap_or(0) as u64),
+            info: None,
+        })
+    }
+
+    /// 手番の手を考えて返す。打てる手がなければ `"pass"`、終局していれば undefined
+    ///
+    /// `timeMs` を与えるとおよそその時間で探索を打ち切り、省略するとエンジンの深さまで探索する。
+    /// 探索の間はページのスレッドが止まり途中で中断できないので、深い探索では `timeMs` を与える。
+    /// 手は打たないので、対局を進めるには返った手を `Game.play` に渡す。
+    #[wasm_bindgen(js_name = bestMove)]
+    pub fn best_move(&mut self, game: &WasmGame, time_ms: Option<u32>) -> Option<String> {
+        let game = &game.game;
+        if game.outcome().is_some() {
+            return None;
+        }
+        let time = time_ms.filter(|&ms| ms > 0).map(|ms| Duration::from_millis(ms as u64));
+        // 探索中に JavaScript から止める手段はないので、中断のフラグは立たない
+        let stop = Arc::new(AtomicBool::new(false));
+        let started = Instant::now();
+        let (mov, info) = players::search(&mut self.player, game.turn(), game.board(), game.history(), time.map(TimeControl::Byoyomi), &stop, None);
+        self.info = Some((info, started.elapsed()));
+        Some(mov.map_or("pass".to_string(), |mov| mov.pos.to_string()))
+    }
+
+    /// 最後の探索の `{depth, score, best, elapsedMs}`。まだ探索していなければ undefined
+    ///
+    /// 深さと評価値は、途中経過を報告しないエンジンでは undefined になる。
+    #[wasm_bindgen(js_name = searchInfo)]
+    pub fn search_info(&self) -> Result<JsValue, JsValue> {
+        let (info, elapsed) = match self.info {
+            Some((ref info, elapsed)) => (info, elapsed),
+            None => return Ok(JsValue::UNDEFINED),
+        };
+        let object = Object::new();
+        let depth = info.as_ref().map(|info| info.depth as u32);
+        let score = info.as_ref().map(|info| info.score);
+        let best = info.as_ref().and_then(|info| info.best).map(|pos| pos.to_string());
+        Reflect::set(&object, &"depth".into(), &depth.into())?;
+        Reflect::set(&object, &"score".into(), &score.into())?;
+        Reflect::set(&object, &"best".into(), &best.into())?;
+        Reflect::set(&object, &"elapsedMs".into(), &(elapsed.as_secs_f64() * 1000.0).into())?;
+        Ok(object.into())
+    }
+}
@@ -0,0 +1,255 @@
+use std::io::{self, Read, Write};
+
+/// ハンドシェイクでキーに連結する、RFC 6455 で決められた GUID
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// これより大きなフレームは受け付けない
+const MAX_PAYLOAD: u64 = 1 << 20;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// WebSocket でやり取りするメッセージ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// クライアントの `Sec-WebSocket-Key` に対して返す `Sec-WebSocket-Accept` の値を求める
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// 接続を WebSocket に切り替える応答を書く
+pub fn write_handshake<W: Write>(writer: &mut W, key: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key),
+    )?;
+    writer.flush()
+}
+
+/// 接続からメッセージを読む
+///
+/// 分割されたメッセージの途中に制御フレームが割り込んでも、読みかけの内容は失われない。
+pub struct Reader<R> {
+    reader: R,
+    partial: Vec<u8>,
+    opcode: Option<u8>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader {
+            reader,
+            partial: Vec::new(),
+            opcode: None,
+        }
+    }
+
+    /// メッセージを1つ読む
+    ///
+    /// 分割されたメッセージはつなげて返す。クライアントからのフレームにかかったマスクは外す。
+    pub fn read_message(&mut self) -> io::Result<Message> {
+        loop {
+            let (is_final, opcode, payload) = self.read_frame()?;
+            match opcode {
+                OPCODE_CLOSE => return Ok(Message::Close),
+                OPCODE_PING => return Ok(Message::Ping(payload)),
+                OPCODE_PONG => return Ok(Message::Pong(payload)),
+                OPCODE_TEXT | OPCODE_BINARY if self.opcode.is_none() => self.opcode = Some(opcode),
+                OPCODE_CONTINUATION if self.opcode.is_some() => {},
+                _ => return Err(invalid_data("unexpected opcode")),
+            }
+            self.partial.extend_from_slice(&payload);
+            if is_final {
+                break;
+            }
+        }
+        let message = ::std::mem::take(&mut self.partial);
+        match self.opcode.take() {
+            Some(OPCODE_TEXT) => String::from_utf8(message)
+                .map(Message::Text)
+                .map_err(|_| invalid_data("text is not UTF-8")),
+            _ => Ok(Message::Binary(message)),
+        }
+    }
+
+    fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut header = [0; 2];
+        self.reader.read_exact(&mut header)?;
+        let is_final = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+        let is_masked = header[1] & 0x80 != 0;
+        let length = match header[1] & 0x7f {
+            126 => {
+                let mut length = [0; 2];
+                self.reader.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as u64
+            },
+            127 => {
+                let mut length = [0; 8];
+                self.reader.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            },
+            length => length as u64,
+        };
+        if length > MAX_PAYLOAD {
+            return Err(invalid_data("frame is too large"));
+        }
+        let mut mask = [0; 4];
+        if is_masked {
+            self.reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length as usize];
+        self.reader.read_exact(&mut payload)?;
+        if is_masked {
+            for (idx, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[idx % 4];
+            }
+        }
+        Ok((is_final, opcode, payload))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// メッセージを1つのフレームで書く。サーバーから送るのでマスクはかけない
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    let (opcode, payload) = match *message {
+        Message::Text(ref text) => (OPCODE_TEXT, text.as_bytes()),
+        Message::Binary(ref data) => (OPCODE_BINARY, &data[..]),
+        Message::Ping(ref data) => (OPCODE_PING, &data[..]),
+        Message::Pong(ref data) => (OPCODE_PONG, &data[..]),
+        Message::Close => (OPCODE_CLOSE, &[][..]),
+    };
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+/// ハンドシェイクのためだけに使う SHA-1
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for idx in 16..80 {
+            w[idx] = (w[idx - 3] ^ w[idx - 8] ^ w[idx - 14] ^ w[idx - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (idx, &word) in w.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *h = h.wrapping_add(*x);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(&h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (idx, &byte)| n | (byte as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    #[test]
+    fn test_websocket_accept_key() {
+        // RFC 6455 の例
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", accept_key("dGhlIHNhbXBsZSBub25jZQ=="));
+        assert_eq!("", base64(b""));
+        assert_eq!("Zm9vYg==", base64(b"foob"));
+        assert_eq!("Zm9vYmE=", base64(b"fooba"));
+    }
+
+    #[test]
+    fn test_websocket_roundtrip() {
+        let long = "x".repeat(70_000);
+        for message in &[Message::Text("hello".to_string()), Message::Text(long), Message::Close] {
+            let mut buf = Vec::new();
+            write_message(&mut buf, message).unwrap();
+            assert_eq!(message, &Reader::new(Cursor::new(buf)).read_message().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_websocket_masked_fragments() {
+        // "Hel" と "lo" に分割され、マスクのかかったクライアントからのフレーム
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frames = vec![0x01, 0x83];
+        frames.extend_from_slice(&mask);
+        frames.extend(b"Hel".iter().enumerate().map(|(idx, byte)| byte ^ mask[idx % 4]));
+        frames.extend_from_slice(&[0x89, 0x00]);
+        frames.extend_from_slice(&[0x80, 0x82]);
+        frames.extend_from_slice(&mask);
+        frames.extend(b"lo".iter().enumerate().map(|(idx, byte)| byte ^ mask[idx % 4]));
+        let mut reader = Reader::new(Cursor::new(frames));
+        assert_eq!(Message::Ping(Vec::new()), reader.read_message().unwrap());
+        assert_eq!(Message::Text("Hello".to_string()), reader.read_message().unwrap());
+    }
+}
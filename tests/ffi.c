@@ -0,0 +1,117 @@
+/* include/reversi.h の C API を、C のプログラムから一通り呼んで確かめる */
+
+#include <stdio.h>
+#include <string.h>
+#include "reversi.h"
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+        failures++; \
+    } \
+} while (0)
+
+static void test_board(void) {
+    ReversiBoard *board = reversi_board_new();
+    int32_t moves[64];
+    size_t count = reversi_board_moves(board, REVERSI_PIECE_BLACK, moves, 64);
+    CHECK(count == 4);
+    CHECK(moves[0] == 19); /* d3 */
+    CHECK(reversi_board_get(board, 27) == REVERSI_PIECE_WHITE);
+    CHECK(!reversi_board_do_move(board, REVERSI_PIECE_BLACK, 0));
+    CHECK(reversi_board_do_move(board, REVERSI_PIECE_BLACK, 19));
+    CHECK(reversi_board_get(board, 27) == REVERSI_PIECE_BLACK);
+    CHECK(reversi_board_count(board, REVERSI_PIECE_BLACK) == 4);
+    CHECK(reversi_board_count(board, REVERSI_PIECE_WHITE) == 1);
+
+    char position[80];
+    size_t len = reversi_board_to_position(board, REVERSI_PIECE_WHITE, position, sizeof position);
+    CHECK(len == 66);
+    ReversiPiece turn = REVERSI_PIECE_EMPTY;
+    ReversiBoard *copy = reversi_board_from_position(position, &turn);
+    CHECK(copy != NULL);
+    CHECK(turn == REVERSI_PIECE_WHITE);
+    CHECK(reversi_board_count(copy, REVERSI_PIECE_EMPTY) == 59);
+    CHECK(reversi_board_from_position("XO", NULL) == NULL);
+
+    reversi_board_free(copy);
+    reversi_board_free(board);
+    reversi_board_free(NULL);
+}
+
+static void test_game(void) {
+    ReversiGame *game = reversi_game_new();
+    CHECK(reversi_game_turn(game) == REVERSI_PIECE_BLACK);
+    CHECK(!reversi_game_play(game, REVERSI_PASS));
+    CHECK(reversi_game_play(game, 37)); /* f5 */
+    CHECK(reversi_game_play(game, 43)); /* d6 */
+    CHECK(!reversi_game_play(game, 43));
+    CHECK(reversi_game_turn(game) == REVERSI_PIECE_BLACK);
+
+    char transcript[8];
+    CHECK(reversi_game_transcript(game, transcript, sizeof transcript) == 4);
+    CHECK(strcmp(transcript, "f5d6") == 0);
+    CHECK(reversi_game_transcript(game, transcript, 3) == 4);
+    CHECK(strcmp(transcript, "f5") == 0);
+
+    CHECK(reversi_game_undo(game));
+    CHECK(reversi_game_turn(game) == REVERSI_PIECE_WHITE);
+    ReversiBoard *board = reversi_game_board(game);
+    CHECK(reversi_board_count(board, REVERSI_PIECE_BLACK) == 4);
+    reversi_board_free(board);
+    CHECK(reversi_game_status(game) == REVERSI_STATUS_PLAYING);
+    reversi_game_free(game);
+}
+
+static void test_engine(void) {
+    CHECK(reversi_engine_new("human") == NULL);
+    CHECK(reversi_engine_new("chess") == NULL);
+    ReversiEngine *black = reversi_engine_new("alphabeta:3");
+    ReversiEngine *white = reversi_engine_new("random");
+    CHECK(black != NULL && white != NULL);
+
+    ReversiSearchInfo info;
+    CHECK(!reversi_engine_search_info(black, &info));
+
+    /* エンジン同士で最後まで対局させる */
+    ReversiGame *game = reversi_game_new();
+    int plies = 0;
+    while (reversi_game_status(game) == REVERSI_STATUS_PLAYING && plies < 200) {
+        ReversiEngine *engine = reversi_game_turn(game) == REVERSI_PIECE_BLACK ? black : white;
+        int32_t square = reversi_engine_best_move(engine, game, 0);
+        CHECK(reversi_game_play(game, square));
+        plies++;
+    }
+    CHECK(reversi_game_status(game) != REVERSI_STATUS_PLAYING);
+    CHECK(reversi_game_moves(game, NULL, 0) == 0);
+    CHECK(reversi_engine_best_move(black, game, 0) == REVERSI_PASS);
+    CHECK(reversi_engine_search_info(black, &info));
+    reversi_game_free(game);
+
+    /* 持ち時間を指定した探索 */
+    game = reversi_game_new();
+    int32_t square = reversi_engine_best_move(black, game, 200);
+    CHECK(reversi_engine_search_info(black, &info));
+    CHECK(info.best == square);
+    CHECK(info.depth >= 1);
+    CHECK(info.elapsed_ms < 1000);
+    CHECK(reversi_game_play(game, square));
+    reversi_game_free(game);
+
+    reversi_engine_free(black);
+    reversi_engine_free(white);
+}
+
+int main(void) {
+    test_board();
+    test_game();
+    test_engine();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,46 @@
+//! tests/ffi.c をライブラリの staticlib とリンクして実行し、C から API が使えることを確かめる
+
+#![cfg(not(target_arch = "wasm32"))]
+
+extern crate cc;
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_ffi_from_c() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // テストのためにビルドした staticlib は、テストの実行ファイルと同じ target/<profile>/deps に置かれる
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi_test");
+
+    let compiler = cc::Build::new()
+        .target(env!("TARGET"))
+        .host(env!("TARGET"))
+        .opt_level(0)
+        .cargo_metadata(false)
+        .get_compiler();
+    let mut command = compiler.to_command();
+    command
+        .arg(manifest_dir.join("tests/ffi.c"))
+        // build.rs が OUT_DIR に生成した、src/ffi.rs と食い違いのないヘッダーを使う
+        .arg("-I").arg(env!("REVERSI_INCLUDE_DIR"))
+        .arg("-o").arg(&output)
+        .arg(lib_dir.join("libreversi.a"))
+        .args(["-lpthread", "-lm", "-ldl"]);
+    if cfg!(feature = "python") {
+        // python フィーチャーでは、staticlib が libpython の関数を参照する
+        let ldflags = Command::new("python3-config").args(["--ldflags", "--embed"]).output()
+            .expect("failed to run python3-config");
+        command.args(String::from_utf8(ldflags.stdout).unwrap().split_whitespace());
+    }
+    let status = command.status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile tests/ffi.c");
+
+    let result = Command::new(&output).output().unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!("ok\n", String::from_utf8_lossy(&result.stdout));
+}
@@ -0,0 +1,67 @@
+//! wasm32 向けにビルドした JavaScript の API を、ヘッドレスの WASM ランタイムで確かめる
+//!
+//! `cargo test --target wasm32-unknown-unknown --features wasm --test wasm` で実行する。
+//! ランナーには wasm-bindgen-cli の `wasm-bindgen-test-runner` を使い、既定では Node.js で動く。
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+extern crate reversi;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_test;
+extern crate js_sys;
+
+use reversi::wasm::{WasmEngine, WasmGame};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_wasm_game() {
+    let mut game = WasmGame::new();
+    assert_eq!(Some("black".to_string()), game.turn());
+    assert_eq!(vec!["d3", "c4", "f5", "e6"], game.moves());
+    assert!(game.play("a1").is_err());
+    assert!(game.play("pass").is_err());
+    assert!(game.play("z9").is_err());
+    game.play("f5").unwrap();
+    game.play("d6").unwrap();
+    assert_eq!("f5d6", game.transcript());
+    let cells = game.cells();
+    assert_eq!(64, cells.len());
+    assert_eq!((1, 2), (cells[37], cells[43]));
+    assert_eq!(Some("d6".to_string()), game.undo());
+    assert_eq!(Some("white".to_string()), game.turn());
+    let position = game.to_position();
+    assert_eq!(position, WasmGame::from_position(&position).unwrap().to_position());
+    assert!(WasmGame::from_position("XO").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_engine() {
+    assert!(WasmEngine::new("human", None).is_err());
+    let mut black = WasmEngine::new("alphabeta:3", None).unwrap();
+    let mut white = WasmEngine::new("random", Some(1)).unwrap();
+    assert_eq!(JsValue::UNDEFINED, black.search_info().unwrap());
+    let mut game = WasmGame::new();
+    while !game.is_over() {
+        let engine = if game.turn() == Some("black".to_string()) { &mut black } else { &mut white };
+        let mov = engine.best_move(&game, None).unwrap();
+        game.play(&mov).unwrap();
+    }
+    assert_eq!(None, black.best_move(&game, None));
+    assert_eq!(64, game.black() + game.white() + game.cells().iter().filter(|&&cell| cell == 0).count() as u8);
+
+    // 持ち時間を指定しても、1スレッドで探索して手を返す
+    let game = WasmGame::new();
+    let mov = black.best_move(&game, Some(100)).unwrap();
+    assert!(game.moves().contains(&mov));
+    let info = black.search_info().unwrap();
+    let depth = js_sys::Reflect::get(&info, &"depth".into()).unwrap();
+    assert!(depth.as_f64().unwrap() >= 1.0);
+
+    // 深さ 20 の negamax も、持ち時間の中で探索を打ち切る
+    let mut negamax = WasmEngine::new("negamax:20", None).unwrap();
+    let mov = negamax.best_move(&game, Some(100)).unwrap();
+    assert!(game.moves().contains(&mov));
+    let elapsed = js_sys::Reflect::get(&negamax.search_info().unwrap(), &"elapsedMs".into()).unwrap();
+    assert!(elapsed.as_f64().unwrap() < 5000.0);
+}